@@ -0,0 +1,162 @@
+//! Record the versions that cargo resolved for the backends that are plain rust crates, so that the
+//! `list` command can report them. The backends with a C API report their own version, but the
+//! version of `libz-sys` is recorded too: `linking` compiles the zlib source that it ships with.
+//!
+//! Also record the compiler and optimization level, the build information of the rust backends.
+
+/// The crates, and the environment variable that their version ends up in
+const CRATES: [(&str, &str); 11] = [
+    ("miniz_oxide", "ZLIB_BENCH_VERSION_MINIZ_OXIDE"),
+    ("miniz-sys", "ZLIB_BENCH_VERSION_MINIZ_SYS"),
+    ("libdeflater", "ZLIB_BENCH_VERSION_LIBDEFLATER"),
+    ("flate2", "ZLIB_BENCH_VERSION_FLATE2"),
+    ("zopfli", "ZLIB_BENCH_VERSION_ZOPFLI"),
+    ("inflate", "ZLIB_BENCH_VERSION_INFLATE"),
+    ("deflate", "ZLIB_BENCH_VERSION_DEFLATE"),
+    ("yazi", "ZLIB_BENCH_VERSION_YAZI"),
+    ("zune-inflate", "ZLIB_BENCH_VERSION_ZUNE_INFLATE"),
+    ("async-compression", "ZLIB_BENCH_VERSION_ASYNC_COMPRESSION"),
+    ("libz-sys", "ZLIB_BENCH_VERSION_LIBZ_SYS"),
+];
+
+/// The value of a `key = "value"` line
+fn value<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let rest = line.strip_prefix(key)?.trim_start().strip_prefix('=')?;
+    Some(rest.trim().trim_matches('"'))
+}
+
+/// The `[[package]]` entries of a lock file, as (name, version, dependencies)
+fn packages(lock: &str) -> Vec<(&str, &str, Vec<&str>)> {
+    let mut packages = Vec::new();
+    for package in lock.split("[[package]]").skip(1) {
+        let mut lines = package.lines().map(str::trim);
+        let (mut name, mut version, mut dependencies) = ("", "", Vec::new());
+
+        while let Some(line) = lines.next() {
+            if let Some(value) = value(line, "name") {
+                name = value;
+            } else if let Some(value) = value(line, "version") {
+                version = value;
+            } else if line.starts_with("dependencies") {
+                let entries = lines.by_ref().take_while(|line| *line != "]");
+                dependencies.extend(entries.map(|entry| entry.trim_matches([',', '"'])));
+            }
+        }
+
+        packages.push((name, version, dependencies));
+    }
+
+    packages
+}
+
+/// The version of the dependency `name` of this crate. When several versions are in the lock file,
+/// this crate's dependency is written as `name version`.
+fn version<'a>(packages: &[(&str, &'a str, Vec<&str>)], name: &str) -> Option<&'a str> {
+    let (_, _, dependencies) = packages.iter().find(|(n, _, _)| *n == "zlib-bench")?;
+    let dependency = dependencies
+        .iter()
+        .find(|d| d.split(' ').next() == Some(name))?;
+
+    match dependency.split(' ').nth(1) {
+        Some(version) => packages
+            .iter()
+            .find(|(n, v, _)| *n == name && *v == version)
+            .map(|(_, v, _)| *v),
+        None => packages
+            .iter()
+            .find(|(n, _, _)| *n == name)
+            .map(|(_, v, _)| *v),
+    }
+}
+
+fn main() {
+    // not there when this crate is a dependency of another one
+    let lock = format!("{}/Cargo.lock", env!("CARGO_MANIFEST_DIR"));
+    println!("cargo:rerun-if-changed={lock}");
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let lock = std::fs::read_to_string(&lock).unwrap_or_default();
+    let packages = packages(&lock);
+
+    for (name, variable) in CRATES {
+        let version = version(&packages, name).unwrap_or("unknown");
+        println!("cargo:rustc-env={variable}={version}");
+    }
+
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let rustc = std::process::Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok());
+    let rustc = rustc.as_deref().map_or("unknown", str::trim);
+    println!("cargo:rustc-env=ZLIB_BENCH_RUSTC={rustc}");
+
+    let opt_level = std::env::var("OPT_LEVEL").unwrap_or_else(|_| "unknown".into());
+    println!("cargo:rustc-env=ZLIB_BENCH_OPT_LEVEL={opt_level}");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCK: &str = r#"
+# This file is automatically @generated by Cargo.
+version = 3
+
+[[package]]
+name = "flate2"
+version = "1.0.30"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = [
+ "miniz_oxide 0.7.4",
+]
+
+[[package]]
+name = "miniz_oxide"
+version = "0.7.4"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "miniz_oxide"
+version = "0.8.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "zlib-bench"
+version = "0.1.0"
+dependencies = [
+ "flate2",
+ "miniz_oxide 0.8.0",
+]
+"#;
+
+    #[test]
+    fn values() {
+        assert_eq!(value(r#"name = "flate2""#, "name"), Some("flate2"));
+        assert_eq!(value(r#"version="1.0.30""#, "version"), Some("1.0.30"));
+        assert_eq!(value(r#"source = "registry""#, "name"), None);
+    }
+
+    #[test]
+    fn lock_packages() {
+        let packages = packages(LOCK);
+        assert_eq!(packages.len(), 4);
+        assert_eq!(packages[0], ("flate2", "1.0.30", vec!["miniz_oxide 0.7.4"]));
+        assert_eq!(packages[1], ("miniz_oxide", "0.7.4", vec![]));
+        assert_eq!(
+            packages[3],
+            ("zlib-bench", "0.1.0", vec!["flate2", "miniz_oxide 0.8.0"])
+        );
+    }
+
+    #[test]
+    fn dependency_versions() {
+        let lock = packages(LOCK);
+        assert_eq!(version(&lock, "flate2"), Some("1.0.30"));
+        // not the version that flate2 depends on
+        assert_eq!(version(&lock, "miniz_oxide"), Some("0.8.0"));
+        assert_eq!(version(&lock, "zopfli"), None);
+        assert_eq!(version(&packages(""), "flate2"), None);
+    }
+}
@@ -0,0 +1,213 @@
+//! Compare two builds of the same library, e.g. zlib-rs at two commits, or two implementations.
+//!
+//! Both builds are loaded with `dlopen` and run the identical workload. The runs alternate between
+//! the two, so that drift in the machine's state (thermals, frequency, other load) affects both
+//! builds equally rather than whichever happened to run last. Welch's t-test on the times of the
+//! runs then says whether the difference is more than noise.
+
+use std::time::Duration;
+
+use crate::dylib::{self, Library, ZlibDylib};
+use crate::{call, implementations, output_buffer, read_input, stats, time_call, Mode};
+
+/// The runs of one build: (time per run, output bytes)
+struct Runs {
+    times: Vec<Duration>,
+    output_len: usize,
+}
+
+impl Runs {
+    fn median(&self) -> Duration {
+        let mut times = self.times.clone();
+        times.sort();
+        times[times.len() / 2]
+    }
+
+    fn min(&self) -> Duration {
+        *self.times.iter().min().unwrap()
+    }
+}
+
+/// One side of the comparison: a single run into the output buffer, which returns the time of the
+/// call and the output size
+type Side<'a> = &'a dyn Fn(&mut [u8]) -> (Duration, usize);
+
+fn run(side: Side, output: &mut [u8], runs: &mut Runs) {
+    let (delta, output_len) = side(output);
+    runs.times.push(delta);
+    runs.output_len = output_len;
+}
+
+/// Run both sides `n` times, alternating between them
+fn alternate(side_a: Side, side_b: Side, n: usize) -> (Runs, Runs) {
+    let mut runs_a = Runs {
+        times: Vec::with_capacity(n),
+        output_len: 0,
+    };
+    let mut runs_b = Runs {
+        times: Vec::with_capacity(n),
+        output_len: 0,
+    };
+    let mut output = output_buffer();
+
+    // one untimed run of each, to fault in the code and the output buffer
+    run(side_a, &mut output, &mut runs_a);
+    run(side_b, &mut output, &mut runs_b);
+    runs_a.times.clear();
+    runs_b.times.clear();
+
+    for i in 0..n {
+        // alternate which side goes first too
+        if i % 2 == 0 {
+            run(side_a, &mut output, &mut runs_a);
+            run(side_b, &mut output, &mut runs_b);
+        } else {
+            run(side_b, &mut output, &mut runs_b);
+            run(side_a, &mut output, &mut runs_a);
+        }
+    }
+
+    (runs_a, runs_b)
+}
+
+/// How much faster b is than a (by the median, in %), and Welch's t-test on their times
+fn difference(runs_a: &Runs, runs_b: &Runs) -> (f64, stats::Welch) {
+    let speed = runs_a.median().as_secs_f64() / runs_b.median().as_secs_f64();
+
+    let seconds =
+        |runs: &Runs| -> Vec<f64> { runs.times.iter().map(Duration::as_secs_f64).collect() };
+    let test = stats::welch(&seconds(runs_a), &seconds(runs_b));
+
+    (100.0 * (speed - 1.0), test)
+}
+
+fn verdict(test: &stats::Welch, alpha: f64) -> &'static str {
+    match test.p < alpha {
+        true => "significant",
+        false => "not significant",
+    }
+}
+
+pub fn ab_all(mode: Mode, path: &str, level: i32, a: &str, b: &str, n: usize, alpha: f64) {
+    let mut libraries = Vec::new();
+    for (name, library) in [("a", a), ("b", b)] {
+        match Library::open_any(library) {
+            Ok(library) => {
+                println!("# {name}: {} ({})", library.version(), library.path());
+                libraries.push(library);
+            }
+            Err(e) => panic!("{library}: {e}"),
+        }
+    }
+
+    let Ok(input) = read_input(path) else {
+        panic!("error opening {path:?}")
+    };
+    let input = &input;
+
+    let side = |library| {
+        move |output: &mut [u8]| {
+            dylib::select(library);
+            time_call(call::<ZlibDylib>, mode, input, output, level)
+        }
+    };
+    let (side_a, side_b) = (side(libraries[0]), side(libraries[1]));
+    let (runs_a, runs_b) = alternate(&side_a, &side_b, n);
+
+    let mb = input.len() as f64 / 1e6;
+    println!("build, median MB/s, best MB/s, output bytes");
+    for (name, runs) in [("a", &runs_a), ("b", &runs_b)] {
+        println!(
+            "{name}, {:.2}, {:.2}, {}",
+            mb / runs.median().as_secs_f64(),
+            mb / runs.min().as_secs_f64(),
+            runs.output_len
+        );
+    }
+
+    let (speed, test) = difference(&runs_a, &runs_b);
+    let size = runs_b.output_len as i64 - runs_a.output_len as i64;
+    println!(
+        "# b vs a: {speed:+.2}% speed (median), {size:+} output bytes ({:+.3}%)",
+        100.0 * size as f64 / runs_a.output_len as f64
+    );
+    println!(
+        "# welch's t-test: t = {:.3}, df = {:.1}, p = {:.4}, {} at alpha = {alpha}",
+        test.t,
+        test.df,
+        test.p,
+        verdict(&test, alpha)
+    );
+}
+
+/// What `compare` measures
+pub struct Configs {
+    /// decompress the input, rather than compress it at `levels`
+    pub inflate: bool,
+    pub levels: Vec<i32>,
+}
+
+/// Run two implementations that are compiled in (or registered) head to head: for every
+/// configuration, both alternate on the same input, and one row has the speedup of b over a, the
+/// difference in output size, and whether the difference in speed is significant
+pub fn compare_all(a: &str, b: &str, path: &str, configs: &Configs, n: usize, alpha: f64) {
+    let find = |name: &str| match implementations().find(|(n, _, _, _, _, _)| *n == name) {
+        Some(implementation) => implementation,
+        None => panic!("invalid implementation: {name:?}"),
+    };
+    let (implementation_a, implementation_b) = (find(a), find(b));
+    for (name, _, _, _, version, build) in [implementation_a, implementation_b] {
+        println!("# {name}: {}, {}", version(), build());
+    }
+
+    let configs: Vec<_> = match configs.inflate {
+        true => vec![(Mode::Inflate, 0)],
+        false => configs
+            .levels
+            .iter()
+            .map(|level| (Mode::Deflate, *level))
+            .collect(),
+    };
+
+    let Ok(input) = read_input(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    let mb = input.len() as f64 / 1e6;
+    println!(
+        "config, {a} MB/s, {b} MB/s, speedup %, {a} bytes, {b} bytes, size delta %, p, at alpha = {alpha}"
+    );
+    for (mode, level) in configs {
+        let config = match mode {
+            Mode::Inflate => "inflate".to_string(),
+            Mode::Deflate => format!("deflate {level}"),
+        };
+
+        let (_, _, call_a, capabilities_a, _, _) = implementation_a;
+        let (_, _, call_b, capabilities_b, _, _) = implementation_b;
+        let unsupported = [(a, capabilities_a), (b, capabilities_b)]
+            .into_iter()
+            .find_map(|(name, c)| c.check(mode, level).err().map(|e| format!("{name}: {e}")));
+        if let Some(reason) = unsupported {
+            println!("# {config}: skipped, {reason}");
+            continue;
+        }
+
+        let side_a = |output: &mut [u8]| time_call(call_a, mode, &input, output, level);
+        let side_b = |output: &mut [u8]| time_call(call_b, mode, &input, output, level);
+        let (runs_a, runs_b) = alternate(&side_a, &side_b, n);
+
+        let (speed, test) = difference(&runs_a, &runs_b);
+        let size = runs_b.output_len as f64 / runs_a.output_len as f64 - 1.0;
+        println!(
+            "{config}, {:.2}, {:.2}, {speed:+.2}, {}, {}, {:+.3}, {:.4}, {}",
+            mb / runs_a.median().as_secs_f64(),
+            mb / runs_b.median().as_secs_f64(),
+            runs_a.output_len,
+            runs_b.output_len,
+            100.0 * size,
+            test.p,
+            verdict(&test, alpha),
+        );
+    }
+}
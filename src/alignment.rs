@@ -0,0 +1,141 @@
+//! Sensitivity to the alignment of the input and output buffers.
+//!
+//! SIMD code paths often load and store in 16/32/64-byte chunks, and may be slower (or take a
+//! different path altogether) when a buffer does not start at an alignment boundary. Users rarely
+//! control the alignment of their buffers, so we place one of them at every offset from 0 to 63
+//! bytes past a 64-byte boundary and report the throughput for each offset.
+
+use std::time::Duration;
+
+use crate::{
+    DeflateConfig, DeflateImplementation, InflateConfig, Method, Mode, ReturnCode, Strategy,
+};
+
+/// The largest alignment that any of the SIMD code paths cares about
+const ALIGN: usize = 64;
+
+fn run<T: DeflateImplementation>(
+    mode: Mode,
+    input: &[u8],
+    output: &mut [u8],
+    level: i32,
+) -> Duration {
+    let start = std::time::Instant::now();
+
+    let res = match mode {
+        Mode::Inflate => {
+            let config = InflateConfig { window_bits: 15 };
+            T::uncompress_slice(output, input, config).1
+        }
+        Mode::Deflate => {
+            let config = DeflateConfig {
+                level,
+                method: Method::Deflated,
+                window_bits: 15,
+                mem_level: 8,
+                strategy: Strategy::Default,
+            };
+            T::compress_slice(output, input, config).1
+        }
+    };
+
+    let elapsed = start.elapsed();
+    assert_eq!(res, ReturnCode::Ok);
+
+    elapsed
+}
+
+type Run = fn(Mode, &[u8], &mut [u8], i32) -> Duration;
+
+const BACKENDS: &[(&str, Run)] = &[
+    #[cfg(feature = "og")]
+    ("og", run::<crate::ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", run::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", run::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    ("cloudflare", run::<crate::ZlibCloudflare>),
+    #[cfg(feature = "miniz")]
+    ("miniz", run::<crate::MinizOxide>),
+    #[cfg(feature = "libdeflate")]
+    ("libdeflate", run::<crate::Libdeflate>),
+    #[cfg(feature = "miniz-c")]
+    ("miniz-c", run::<crate::MinizC>),
+];
+
+/// The buffer whose alignment is varied; the other one is always aligned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Buffer {
+    Input,
+    Output,
+}
+
+/// Returns a slice of `buffer` that starts `offset` bytes past an `ALIGN`-byte boundary
+fn at_offset(buffer: &mut [u8], offset: usize, len: usize) -> &mut [u8] {
+    let start = buffer.as_ptr().align_offset(ALIGN) + offset;
+    &mut buffer[start..][..len]
+}
+
+pub fn alignment_all(buffer: Buffer, mode: Mode, path: &str, level: i32) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    // the best of a couple of runs: the differences we're looking for are small
+    let n = 3;
+    let mb = input.len() as f64 / 1_000_000.0;
+
+    let output_len = (1 << 28) - 2 * ALIGN;
+    let mut input_buffer = vec![0u8; input.len() + 2 * ALIGN];
+    let mut output_buffer = vec![0u8; 1 << 28];
+
+    let mut throughput = vec![Vec::with_capacity(ALIGN); BACKENDS.len()];
+
+    let names: Vec<_> = BACKENDS.iter().map(|(name, _)| *name).collect();
+    println!("offset, {}", names.join(", "));
+    for offset in 0..ALIGN {
+        let (input_offset, output_offset) = match buffer {
+            Buffer::Input => (offset, 0),
+            Buffer::Output => (0, offset),
+        };
+
+        let input = {
+            let slice = at_offset(&mut input_buffer, input_offset, input.len());
+            slice.copy_from_slice(&input);
+            &*slice
+        };
+        let output = at_offset(&mut output_buffer, output_offset, output_len);
+
+        let mut row = Vec::with_capacity(BACKENDS.len());
+        for (i, (_, run)) in BACKENDS.iter().enumerate() {
+            let best = (0..n)
+                .map(|_| run(mode, input, output, level))
+                .min()
+                .unwrap();
+
+            let mbs = mb / best.as_secs_f64();
+            throughput[i].push(mbs);
+            row.push(format!("{mbs:.2}"));
+        }
+
+        println!("{offset}, {}", row.join(", "));
+    }
+
+    println!();
+    println!("implementation, min MB/s, max MB/s, slowest offset, spread %");
+    for (name, throughput) in names.iter().zip(throughput) {
+        let (slowest, min) = throughput
+            .iter()
+            .copied()
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        let max = throughput.iter().copied().fold(f64::MIN, f64::max);
+
+        println!(
+            "{name}, {min:.2}, {max:.2}, {slowest}, {:.1}",
+            100.0 * (max - min) / max
+        );
+    }
+}
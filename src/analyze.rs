@@ -0,0 +1,247 @@
+//! Parse a raw deflate stream and count what the compressor chose to emit.
+//!
+//! Throughput alone does not explain why one implementation is faster than another at the same
+//! level: a compressor can go faster by finding fewer (or shorter) matches, or by giving up and
+//! emitting stored blocks. This walks the block structure and the literal/length codes (after
+//! zlib's `contrib/puff`), without producing any output.
+
+/// Match length buckets: 3, 4-7, 8-15, 16-63, 64-257, 258
+pub const BUCKETS: [&str; 6] = ["3", "4-7", "8-15", "16-63", "64-257", "258"];
+
+#[derive(Debug, Default, Clone)]
+pub struct Stats {
+    pub stored_blocks: usize,
+    pub fixed_blocks: usize,
+    pub dynamic_blocks: usize,
+    /// uncompressed bytes in stored blocks
+    pub stored_bytes: u64,
+    pub literals: u64,
+    pub matches: u64,
+    /// uncompressed bytes covered by matches
+    pub match_bytes: u64,
+    pub lengths: [u64; BUCKETS.len()],
+}
+
+impl Stats {
+    pub fn total_bytes(&self) -> u64 {
+        self.stored_bytes + self.literals + self.match_bytes
+    }
+}
+
+struct BitReader<'a> {
+    input: &'a [u8],
+    /// position in bits
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..n {
+            let Some(byte) = self.input.get(self.pos / 8) else {
+                return Err("unexpected end of input".to_string());
+            };
+            value |= ((byte >> (self.pos % 8)) as u32 & 1) << i;
+            self.pos += 1;
+        }
+
+        Ok(value)
+    }
+}
+
+/// A canonical huffman code, decoded one bit at a time
+struct Huffman {
+    count: [u16; 16],
+    symbol: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut count = [0u16; 16];
+        for &len in lengths {
+            count[len as usize] += 1;
+        }
+
+        let mut offsets = [0u16; 16];
+        for len in 1..15 {
+            offsets[len + 1] = offsets[len] + count[len];
+        }
+
+        let mut symbol = vec![0; lengths.len()];
+        for (s, &len) in lengths.iter().enumerate() {
+            if len != 0 {
+                symbol[offsets[len as usize] as usize] = s as u16;
+                offsets[len as usize] += 1;
+            }
+        }
+
+        count[0] = 0;
+        Self { count, symbol }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.count[len] as i32;
+            if code - count < first {
+                return Ok(self.symbol[(index + (code - first)) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err("invalid huffman code".to_string())
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+fn bucket(length: u16) -> usize {
+    match length {
+        3 => 0,
+        4..=7 => 1,
+        8..=15 => 2,
+        16..=63 => 3,
+        64..=257 => 4,
+        _ => 5,
+    }
+}
+
+fn codes(
+    reader: &mut BitReader,
+    lencode: &Huffman,
+    distcode: &Huffman,
+    stats: &mut Stats,
+) -> Result<(), String> {
+    loop {
+        let symbol = lencode.decode(reader)? as usize;
+        match symbol {
+            0..=255 => stats.literals += 1,
+            256 => return Ok(()),
+            _ => {
+                let i = symbol - 257;
+                if i >= LENGTH_BASE.len() {
+                    return Err(format!("invalid length symbol {symbol}"));
+                }
+                let length = LENGTH_BASE[i] + reader.bits(LENGTH_EXTRA[i] as u32)? as u16;
+
+                let d = distcode.decode(reader)? as usize;
+                if d >= DISTANCE_EXTRA.len() {
+                    return Err(format!("invalid distance symbol {d}"));
+                }
+                reader.bits(DISTANCE_EXTRA[d] as u32)?;
+
+                stats.matches += 1;
+                stats.match_bytes += length as u64;
+                stats.lengths[bucket(length)] += 1;
+            }
+        }
+    }
+}
+
+fn dynamic(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    const ORDER: [usize; 19] = [
+        16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+    ];
+
+    let nlen = reader.bits(5)? as usize + 257;
+    let ndist = reader.bits(5)? as usize + 1;
+    let ncode = reader.bits(4)? as usize + 4;
+
+    let mut lengths = [0u8; 320];
+    for &i in &ORDER[..ncode] {
+        lengths[i] = reader.bits(3)? as u8;
+    }
+    let lencode = Huffman::new(&lengths[..19]);
+
+    let mut index = 0;
+    while index < nlen + ndist {
+        let symbol = lencode.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 if index > 0 => (lengths[index - 1], 3 + reader.bits(2)?),
+            16 => return Err("repeat with no previous length".to_string()),
+            17 => (0, 3 + reader.bits(3)?),
+            _ => (0, 11 + reader.bits(7)?),
+        };
+
+        for _ in 0..repeat {
+            if index == nlen + ndist {
+                return Err("too many lengths".to_string());
+            }
+            lengths[index] = value;
+            index += 1;
+        }
+    }
+
+    Ok((
+        Huffman::new(&lengths[..nlen]),
+        Huffman::new(&lengths[nlen..nlen + ndist]),
+    ))
+}
+
+fn fixed() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+/// Count the blocks and symbols in a raw deflate stream
+pub fn analyze(input: &[u8]) -> Result<Stats, String> {
+    let mut reader = BitReader { input, pos: 0 };
+    let mut stats = Stats::default();
+
+    loop {
+        let last = reader.bits(1)?;
+        match reader.bits(2)? {
+            0 => {
+                // stored: skip to the next byte boundary, then LEN and NLEN
+                reader.pos = reader.pos.next_multiple_of(8);
+                let len = reader.bits(16)?;
+                let nlen = reader.bits(16)?;
+                if len != !nlen & 0xffff {
+                    return Err("stored block length mismatch".to_string());
+                }
+
+                reader.pos += 8 * len as usize;
+                if reader.pos > 8 * input.len() {
+                    return Err("unexpected end of input".to_string());
+                }
+
+                stats.stored_blocks += 1;
+                stats.stored_bytes += len as u64;
+            }
+            1 => {
+                let (lencode, distcode) = fixed();
+                codes(&mut reader, &lencode, &distcode, &mut stats)?;
+                stats.fixed_blocks += 1;
+            }
+            2 => {
+                let (lencode, distcode) = dynamic(&mut reader)?;
+                codes(&mut reader, &lencode, &distcode, &mut stats)?;
+                stats.dynamic_blocks += 1;
+            }
+            _ => return Err("invalid block type".to_string()),
+        }
+
+        if last == 1 {
+            return Ok(stats);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! The cost of async streaming, with `async-compression` on a tokio runtime.
+//!
+//! Services tend to (de)compress through async streams rather than whole buffers. Every poll goes
+//! through the async machinery and an internal buffer, so this measures async-compression next to
+//! the implementations that it wraps. It uses flate2, so the `flate2-*` features select what it
+//! calls into, and `flate2` through its `Compress`/`Decompress` is measured as well.
+
+use std::sync::OnceLock;
+
+use async_compression::tokio::bufread::{DeflateDecoder, DeflateEncoder, ZlibDecoder, ZlibEncoder};
+use async_compression::Level;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::runtime::Runtime;
+
+use crate::ng_versions::measure;
+use crate::{
+    helper, Capabilities, DeflateConfig, DeflateImplementation, Flate2, InflateConfig, Mode,
+    ReturnCode, ZlibNg, ZlibRs, FLATE2,
+};
+
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+    // a single thread, like the other implementations
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+    })
+}
+
+/// Read all of `reader` into `output`
+fn read_into(output: &mut [u8], mut reader: impl AsyncRead + Unpin) -> (&mut [u8], ReturnCode) {
+    let result = runtime().block_on(async {
+        let mut all = Vec::with_capacity(output.len());
+        reader.read_to_end(&mut all).await.map(|_| all)
+    });
+
+    match result {
+        Ok(all) if all.len() <= output.len() => {
+            let output = &mut output[..all.len()];
+            output.copy_from_slice(&all);
+            (output, ReturnCode::Ok)
+        }
+        Ok(_) => (&mut [], ReturnCode::BufError),
+        Err(_) => (&mut [], ReturnCode::DataError),
+    }
+}
+
+pub struct AsyncCompression;
+
+impl DeflateImplementation for AsyncCompression {
+    const NAME: &'static str = "async-compression";
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        gzip: false,
+        min_window_bits: 15,
+        dictionary: false,
+        strategies: false,
+        mem_level: false,
+        ..Capabilities::ZLIB
+    };
+
+    fn version() -> String {
+        env!("ZLIB_BENCH_VERSION_ASYNC_COMPRESSION").to_string()
+    }
+
+    fn uncompress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        match config.window_bits {
+            -15 => read_into(output, DeflateDecoder::new(input)),
+            15 => read_into(output, ZlibDecoder::new(input)),
+            _ => (&mut [], ReturnCode::StreamError),
+        }
+    }
+
+    fn compress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let level = match config.level {
+            -1 => Level::Default,
+            0..=9 => Level::Precise(config.level),
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+
+        match config.window_bits {
+            -15 => read_into(output, DeflateEncoder::with_quality(input, level)),
+            15 => read_into(output, ZlibEncoder::with_quality(input, level)),
+            _ => (&mut [], ReturnCode::StreamError),
+        }
+    }
+}
+
+pub fn async_all(mode: Mode, path: &str, level: i32) {
+    let backends = [
+        ("rs", helper::<ZlibRs> as _),
+        ("ng", helper::<ZlibNg> as _),
+        (FLATE2, helper::<Flate2> as _),
+        ("async-compression", helper::<AsyncCompression> as _),
+    ];
+
+    println!("implementation, MB/s, output bytes");
+    for (name, f) in backends {
+        let (mb_per_s, output_len) = measure(f, mode, path, level);
+        println!("{name}, {mb_per_s:.2}, {output_len}");
+    }
+}
@@ -0,0 +1,175 @@
+//! BGZF, the blocked gzip format used by bgzip/samtools/htslib.
+//!
+//! The input is split into blocks of at most `BLOCK_INPUT` bytes. Every block is compressed as an
+//! independent gzip member, with a `BC` extra subfield that records the size of the member so that
+//! readers can seek to block boundaries without decompressing.
+
+use crate::{
+    DeflateConfig, DeflateImplementation, InflateConfig, Method, ReturnCode, Strategy, ZlibOg,
+};
+
+/// The maximum amount of input per block that bgzip uses, so that the compressed member is
+/// guaranteed to fit in 64 KiB.
+const BLOCK_INPUT: usize = 0xff00;
+
+/// The maximum size of a compressed member
+const BLOCK_MAX: usize = 1 << 16;
+
+/// gzip header with FEXTRA set and a `BC` subfield. The last 2 bytes (BSIZE) are filled in per block.
+const HEADER: [u8; 18] = [
+    0x1f, 0x8b, 8, 4, 0, 0, 0, 0, 0, 0xff, 6, 0, b'B', b'C', 2, 0, 0, 0,
+];
+
+/// gzip header (10) + extra field (8) + trailer (8)
+const FRAMING: usize = HEADER.len() + 8;
+
+/// The empty block that marks the end of a BGZF file
+const EOF: [u8; 28] = [
+    0x1f, 0x8b, 8, 4, 0, 0, 0, 0, 0, 0xff, 6, 0, b'B', b'C', 2, 0, 0x1b, 0, 3, 0, 0, 0, 0, 0, 0, 0,
+    0, 0,
+];
+
+fn crc32(bytes: &[u8]) -> u32 {
+    unsafe { libz_sys::crc32(0, bytes.as_ptr(), bytes.len() as _) as u32 }
+}
+
+fn push_member(output: &mut Vec<u8>, cdata: &[u8], block: &[u8]) {
+    let bsize = (FRAMING + cdata.len() - 1) as u16;
+
+    output.extend_from_slice(&HEADER[..16]);
+    output.extend_from_slice(&bsize.to_le_bytes());
+    output.extend_from_slice(cdata);
+    output.extend_from_slice(&crc32(block).to_le_bytes());
+    output.extend_from_slice(&(block.len() as u32).to_le_bytes());
+}
+
+pub fn encode<T: DeflateImplementation>(input: &[u8], level: i32) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() + input.len() / 16 + EOF.len());
+    let mut scratch = vec![0u8; BLOCK_MAX];
+
+    for block in input.chunks(BLOCK_INPUT) {
+        // raw deflate; we write the gzip framing ourselves
+        let mut config = DeflateConfig {
+            level,
+            method: Method::Deflated,
+            window_bits: -15,
+            mem_level: 8,
+            strategy: Strategy::Default,
+        };
+
+        let (cdata, err) = T::compress_slice(&mut scratch, block, config);
+        assert_eq!(err, ReturnCode::Ok);
+
+        if FRAMING + cdata.len() <= BLOCK_MAX {
+            push_member(&mut output, cdata, block);
+        } else {
+            // incompressible data: stored blocks always fit
+            config.level = 0;
+            let (cdata, err) = T::compress_slice(&mut scratch, block, config);
+            assert_eq!(err, ReturnCode::Ok);
+
+            push_member(&mut output, cdata, block);
+        }
+    }
+
+    output.extend_from_slice(&EOF);
+
+    output
+}
+
+pub fn decode<T: DeflateImplementation>(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut scratch = vec![0u8; BLOCK_MAX];
+
+    let mut rest = input;
+    while !rest.is_empty() {
+        assert_eq!(rest[..4], HEADER[..4], "not a BGZF member");
+
+        let xlen = u16::from_le_bytes([rest[10], rest[11]]) as usize;
+        let bsize = u16::from_le_bytes([rest[16], rest[17]]) as usize + 1;
+
+        let (member, remainder) = rest.split_at(bsize);
+        rest = remainder;
+
+        let cdata = &member[12 + xlen..member.len() - 8];
+        let (trailer_crc, trailer_len) = member[member.len() - 8..].split_at(4);
+        let crc = u32::from_le_bytes(trailer_crc.try_into().unwrap());
+        let isize = u32::from_le_bytes(trailer_len.try_into().unwrap()) as usize;
+
+        let config = InflateConfig { window_bits: -15 };
+        let (block, err) = T::uncompress_slice(&mut scratch[..isize], cdata, config);
+        assert_eq!(err, ReturnCode::Ok);
+        assert_eq!(crc32(block), crc, "crc mismatch");
+
+        output.extend_from_slice(block);
+    }
+
+    output
+}
+
+type Encode = fn(&[u8], i32) -> Vec<u8>;
+type Decode = fn(&[u8]) -> Vec<u8>;
+
+const BACKENDS: &[(&str, Encode, Decode)] = &[
+    ("og", encode::<ZlibOg>, decode::<ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", encode::<crate::ZlibNg>, decode::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", encode::<crate::ZlibRs>, decode::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    (
+        "cloudflare",
+        encode::<crate::ZlibCloudflare>,
+        decode::<crate::ZlibCloudflare>,
+    ),
+    #[cfg(feature = "miniz")]
+    (
+        "miniz",
+        encode::<crate::MinizOxide>,
+        decode::<crate::MinizOxide>,
+    ),
+    #[cfg(feature = "libdeflate")]
+    (
+        "libdeflate",
+        encode::<crate::Libdeflate>,
+        decode::<crate::Libdeflate>,
+    ),
+    #[cfg(feature = "miniz-c")]
+    ("miniz-c", encode::<crate::MinizC>, decode::<crate::MinizC>),
+];
+
+pub fn bgzf_all(path: &str, level: i32) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    let n = 5;
+    let mb = input.len() as f64 / 1_000_000.0;
+
+    println!("implementation, blocks, compressed bytes, encode MB/s, decode MB/s");
+    for (name, encode, decode) in BACKENDS {
+        let start = std::time::Instant::now();
+        let mut encoded = Vec::new();
+        for _ in 0..n {
+            encoded = encode(&input, level);
+        }
+        let encode_time = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let mut decoded = Vec::new();
+        for _ in 0..n {
+            decoded = decode(&encoded);
+        }
+        let decode_time = start.elapsed();
+
+        assert!(decoded == input, "{name}: roundtrip mismatch");
+
+        println!(
+            "{name}, {}, {}, {}, {}",
+            input.len().div_ceil(BLOCK_INPUT),
+            encoded.len(),
+            n as f64 * mb / encode_time.as_secs_f64(),
+            n as f64 * mb / decode_time.as_secs_f64(),
+        );
+    }
+}
@@ -0,0 +1,85 @@
+//! Chromium's fork of zlib.
+//!
+//! Chromium carries its own SIMD optimizations (crc32 and adler32 with SIMD, a faster inflate
+//! window copy, and a different hash for deflate), separate from both zlib-ng and cloudflare. There
+//! is no sys crate for it, so the fork is fetched and built as a shared library with its
+//! `CMakeLists.txt` (this needs `git`, `cmake` and a C compiler), and loaded with `dlopen`.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::dylib::{self, Abi, Library, ZlibDylib};
+use crate::ng_versions::{measure, run};
+use crate::{helper, Mode, FUNCTIONS};
+
+const REPOSITORY: &str = "https://chromium.googlesource.com/chromium/src/third_party/zlib";
+
+/// The shared library in the build directory, its name depends on the version of the fork
+fn find_library(dir: &Path) -> Option<String> {
+    std::fs::read_dir(dir).ok()?.flatten().find_map(|entry| {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let found = name.starts_with("libz") && name.ends_with(".so");
+        found.then(|| entry.path().display().to_string())
+    })
+}
+
+/// Fetch and build the fork (once), and return the path of the shared library
+fn build() -> Result<String, String> {
+    let dir = format!("{}/target/chromium-zlib", env!("CARGO_MANIFEST_DIR"));
+    let build = format!("{dir}/build");
+    if let Some(library) = find_library(Path::new(&build)) {
+        return Ok(library);
+    }
+
+    let source = format!("{dir}/source");
+    if !Path::new(&source).exists() {
+        run(Command::new("git").args(["clone", "--quiet", "--depth=1", REPOSITORY, &source]))?;
+    }
+
+    run(Command::new("cmake").args([
+        "-S",
+        &source,
+        "-B",
+        &build,
+        "-DCMAKE_BUILD_TYPE=Release",
+        "-DBUILD_SHARED_LIBS=ON",
+        "-DENABLE_SIMD_OPTIMIZATIONS=ON",
+    ]))?;
+    run(Command::new("cmake").args(["--build", &build, "--parallel"]))?;
+
+    find_library(Path::new(&build)).ok_or(format!("no libz*.so in {build}"))
+}
+
+/// `library` is a path to a shared library of the fork, or `None` to build it
+pub fn load(library: Option<&str>) -> Result<&'static Library, String> {
+    let path = match library {
+        Some(path) => path.to_string(),
+        None => build()?,
+    };
+
+    // inside of Chromium the symbols are prefixed, a standalone build may not do that
+    Library::open(&path, Abi::Chromium).or_else(|_| Library::open(&path, Abi::Zlib))
+}
+
+pub fn chromium_all(mode: Mode, path: &str, level: i32, library: Option<&str>) {
+    let library = match load(library) {
+        Ok(library) => library,
+        Err(e) => panic!("chromium zlib unavailable: {e}"),
+    };
+    println!("# chromium: {} ({})", library.version(), library.path());
+
+    println!("implementation, MB/s, output bytes");
+    for &(name, f, _, capabilities, _, _) in FUNCTIONS {
+        if let Err(reason) = capabilities.check(mode, level) {
+            println!("# {name}: skipped, {reason}");
+            continue;
+        }
+
+        let (mb_per_s, output_len) = measure(f, mode, path, level);
+        println!("{name}, {mb_per_s:.2}, {output_len}");
+    }
+
+    dylib::select(library);
+    let (mb_per_s, output_len) = measure(helper::<ZlibDylib>, mode, path, level);
+    println!("chromium, {mb_per_s:.2}, {output_len}");
+}
@@ -0,0 +1,149 @@
+//! Streaming with output buffers of awkward sizes.
+//!
+//! Implementations process data in internal chunks (a SIMD register, a 258-byte match, a 64K
+//! window), and the places where `avail_out` runs out in the middle of such a chunk are where
+//! off-by-one bugs hide, and where performance can fall off a cliff. We stream the whole file
+//! through output buffers of a given size, check that no call writes past `avail_out`, and verify
+//! the result.
+
+use core::mem::MaybeUninit;
+use std::time::Duration;
+
+use crate::{
+    DeflateConfig, DeflateImplementation, Flush, InflateConfig, Method, Mode, ReturnCode, Strategy,
+    ZlibImplementation, ZlibOg,
+};
+
+/// Written just past the end of the output buffer before every call, and checked after.
+const CANARY: u8 = 0xa5;
+
+/// Stream `input` through at most `chunk` bytes of output space per call
+fn stream<T: ZlibImplementation>(
+    mode: Mode,
+    input: &[u8],
+    level: i32,
+    chunk: usize,
+    capacity: usize,
+) -> Result<(Vec<u8>, Duration), String> {
+    let mut stream = MaybeUninit::zeroed();
+    let err = match mode {
+        Mode::Inflate => T::inflate_init(stream.as_mut_ptr(), InflateConfig { window_bits: 15 }),
+        Mode::Deflate => {
+            let config = DeflateConfig {
+                level,
+                method: Method::Deflated,
+                window_bits: 15,
+                mem_level: 8,
+                strategy: Strategy::Default,
+            };
+            T::deflate_init(stream.as_mut_ptr(), config)
+        }
+    };
+    if err != ReturnCode::Ok {
+        return Err(format!("init: {err:?}"));
+    }
+    let stream = unsafe { stream.assume_init_mut() };
+
+    // one extra byte for the canary
+    let mut output = vec![0u8; capacity + 1];
+
+    let start = std::time::Instant::now();
+
+    T::set_in(stream, input);
+
+    let result = loop {
+        let total_out = T::total_out(stream);
+        if total_out == capacity {
+            break Err("output does not fit".to_string());
+        }
+
+        let end = Ord::min(total_out + chunk, capacity);
+        output[end] = CANARY;
+        T::set_out(stream, &output[total_out..end]);
+
+        let err = match mode {
+            Mode::Inflate => T::inflate(stream, Flush::NoFlush),
+            Mode::Deflate => T::deflate(stream, Flush::Finish),
+        };
+
+        if output[end] != CANARY || T::total_out(stream) > end {
+            break Err(format!("wrote past avail_out at offset {end}"));
+        }
+
+        match err {
+            ReturnCode::Ok => {}
+            ReturnCode::StreamEnd => break Ok(()),
+            err => break Err(format!("{err:?} at output offset {total_out}")),
+        }
+    };
+
+    let elapsed = start.elapsed();
+
+    let total_out = T::total_out(stream);
+    match mode {
+        Mode::Inflate => T::inflate_end(stream),
+        Mode::Deflate => T::deflate_end(stream),
+    };
+
+    result?;
+    output.truncate(total_out);
+
+    Ok((output, elapsed))
+}
+
+type Stream = fn(Mode, &[u8], i32, usize, usize) -> Result<(Vec<u8>, Duration), String>;
+
+const BACKENDS: &[(&str, Stream)] = &[
+    ("og", stream::<ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", stream::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", stream::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    ("cloudflare", stream::<crate::ZlibCloudflare>),
+];
+
+pub fn output_chunks_all(mode: Mode, path: &str, level: i32, sizes: &[usize]) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    let mb = input.len() as f64 / 1_000_000.0;
+
+    // the expected output when inflating, or the output buffer size when deflating
+    let (reference, capacity) = match mode {
+        Mode::Inflate => {
+            let mut output = vec![0u8; 1 << 28];
+            let config = InflateConfig { window_bits: 15 };
+            let (output, err) = ZlibOg::uncompress_slice(&mut output, &input, config);
+            assert_eq!(err, ReturnCode::Ok);
+
+            (output.to_vec(), output.len())
+        }
+        Mode::Deflate => (Vec::new(), 2 * input.len() + 1024),
+    };
+
+    let verify = |output: &[u8]| match mode {
+        Mode::Inflate => output == reference,
+        Mode::Deflate => {
+            let mut decompressed = vec![0u8; input.len()];
+            let config = InflateConfig { window_bits: 15 };
+            let (decompressed, err) = ZlibOg::uncompress_slice(&mut decompressed, output, config);
+
+            err == ReturnCode::Ok && decompressed == input
+        }
+    };
+
+    println!("implementation, output chunk, status, MB/s");
+    for (name, stream) in BACKENDS {
+        for &chunk in sizes {
+            match stream(mode, &input, level, chunk, capacity) {
+                Ok((output, elapsed)) if verify(&output) => {
+                    println!("{name}, {chunk}, ok, {}", mb / elapsed.as_secs_f64())
+                }
+                Ok(_) => println!("{name}, {chunk}, FAILED (wrong output), 0"),
+                Err(e) => println!("{name}, {chunk}, FAILED ({e}), 0"),
+            }
+        }
+    }
+}
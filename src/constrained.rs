@@ -0,0 +1,203 @@
+//! Streaming through one small output buffer, like a consumer that writes every piece out (to a
+//! socket, to a file) before it asks for the next one.
+//!
+//! The other commands give the implementation one output buffer that is large enough for all of
+//! the output, so an inflate can refer back into what it already wrote, and a deflate finishes in a
+//! single call. Here the same `avail_out` bytes (16 KiB by default) are reused for every call, so
+//! there are many calls, the output written so far is gone, and inflate has to keep (and wrap) its
+//! own copy of the window. The drained bytes are copied out, as a consumer would. Inflated output is
+//! compared with the whole-buffer output of the same implementation; deflated output may differ
+//! from it (the blocks can end elsewhere when the output runs out), so it is inflated again.
+
+use core::mem::MaybeUninit;
+use std::time::Duration;
+
+use crate::{
+    deflate_config, inflate_config, timer, Capabilities, DeflateImplementation, Flush, Mode,
+    ReturnCode, ZlibImplementation,
+};
+
+/// The number of times every measurement is repeated, the median is reported
+const RUNS: usize = 5;
+
+fn median(mut deltas: Vec<Duration>) -> Duration {
+    deltas.sort();
+    deltas[deltas.len() / 2]
+}
+
+/// Stream `input` through `buffer`, appending what every call writes to `output`, and return the
+/// number of calls
+fn drain<T: ZlibImplementation>(
+    mode: Mode,
+    input: &[u8],
+    level: i32,
+    buffer: &mut [u8],
+    output: &mut Vec<u8>,
+) -> Result<usize, String> {
+    let mut stream = MaybeUninit::zeroed();
+    let err = match mode {
+        Mode::Inflate => T::inflate_init(stream.as_mut_ptr(), inflate_config()),
+        Mode::Deflate => T::deflate_init(stream.as_mut_ptr(), deflate_config(level)),
+    };
+    if err != ReturnCode::Ok {
+        return Err(format!("init: {err:?}"));
+    }
+    let stream = unsafe { stream.assume_init_mut() };
+
+    T::set_in(stream, input);
+
+    let mut calls = 0;
+    let result = loop {
+        let before = T::total_out(stream);
+        T::set_out(stream, buffer);
+
+        let err = match mode {
+            Mode::Inflate => T::inflate(stream, Flush::NoFlush),
+            Mode::Deflate => T::deflate(stream, Flush::Finish),
+        };
+        calls += 1;
+
+        output.extend_from_slice(&buffer[..T::total_out(stream) - before]);
+
+        match err {
+            ReturnCode::Ok => {}
+            ReturnCode::StreamEnd => break Ok(calls),
+            err => break Err(format!("{err:?} after {calls} calls")),
+        }
+    };
+
+    match mode {
+        Mode::Inflate => T::inflate_end(stream),
+        Mode::Deflate => T::deflate_end(stream),
+    };
+
+    result
+}
+
+/// The median time of the whole-buffer call and of `drain`, the number of calls of `drain`, and
+/// the uncompressed size
+type Measured = (Duration, Duration, usize, usize);
+
+fn measure<T: ZlibImplementation>(
+    mode: Mode,
+    input: &[u8],
+    level: i32,
+    size: usize,
+) -> Result<Measured, String> {
+    let capacity = match mode {
+        Mode::Inflate => 1 << 28,
+        Mode::Deflate => 2 * input.len() + 1024,
+    };
+    let mut whole = vec![0u8; capacity];
+
+    let mut once = || {
+        let start = timer::now();
+        let (output, err) = match mode {
+            Mode::Inflate => T::uncompress_slice(&mut whole, input, inflate_config()),
+            Mode::Deflate => T::compress_slice(&mut whole, input, deflate_config(level)),
+        };
+        let elapsed = start.elapsed();
+
+        match err {
+            ReturnCode::Ok => Ok((elapsed, output.len())),
+            err => Err(format!("whole buffer: {err:?}")),
+        }
+    };
+    let mut deltas = Vec::with_capacity(RUNS);
+    let mut len = 0;
+    for _ in 0..RUNS {
+        let (delta, n) = once()?;
+        deltas.push(delta);
+        len = n;
+    }
+    let whole_time = median(deltas);
+    let expected = &whole[..len];
+
+    let mut check = vec![0u8; input.len()];
+    let mut valid = |output: &[u8]| match mode {
+        Mode::Inflate => output == expected,
+        Mode::Deflate => {
+            let (decompressed, err) = T::uncompress_slice(&mut check, output, inflate_config());
+            err == ReturnCode::Ok && decompressed == input
+        }
+    };
+
+    let mut buffer = vec![0u8; size];
+    let mut output = Vec::with_capacity(len);
+    let mut deltas = Vec::with_capacity(RUNS);
+    let mut calls = 0;
+    for _ in 0..RUNS {
+        output.clear();
+
+        let start = timer::now();
+        calls = drain::<T>(mode, input, level, &mut buffer, &mut output)?;
+        deltas.push(start.elapsed());
+
+        if !valid(&output) {
+            return Err("wrong output".to_string());
+        }
+    }
+
+    let uncompressed = match mode {
+        Mode::Inflate => len,
+        Mode::Deflate => input.len(),
+    };
+
+    Ok((whole_time, median(deltas), calls, uncompressed))
+}
+
+type Measure = fn(Mode, &[u8], i32, usize) -> Result<Measured, String>;
+
+const BACKENDS: &[(&str, Capabilities, Measure)] = &[
+    #[cfg(feature = "og")]
+    ("og", crate::ZlibOg::CAPABILITIES, measure::<crate::ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", crate::ZlibNg::CAPABILITIES, measure::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", crate::ZlibRs::CAPABILITIES, measure::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    (
+        "cloudflare",
+        crate::ZlibCloudflare::CAPABILITIES,
+        measure::<crate::ZlibCloudflare>,
+    ),
+    (
+        "system",
+        crate::dylib::SystemZlib::CAPABILITIES,
+        measure::<crate::dylib::SystemZlib>,
+    ),
+];
+
+pub fn constrained_output_all(mode: Mode, path: &str, level: i32, sizes: &[usize]) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    // in MB of uncompressed data per second, for both modes
+    println!("implementation, output buffer, calls, MB/s (whole buffer), MB/s, % slower");
+    for (name, capabilities, measure) in BACKENDS {
+        if let Err(reason) = capabilities.available() {
+            println!("# {name}: skipped, {reason}");
+            continue;
+        }
+        for &size in sizes {
+            let (whole, constrained, calls, uncompressed) = match measure(mode, &input, level, size)
+            {
+                Ok(measured) => measured,
+                Err(e) => {
+                    println!("# {name} with a {size} byte buffer: failed, {e}");
+                    continue;
+                }
+            };
+
+            let mb = uncompressed as f64 / 1e6;
+            let slower = constrained.as_secs_f64() / whole.as_secs_f64() - 1.0;
+            println!(
+                "{name}, {size}, {calls}, {:.2}, {:.2}, {:.1}",
+                mb / whole.as_secs_f64(),
+                mb / constrained.as_secs_f64(),
+                100.0 * slower,
+            );
+        }
+    }
+}
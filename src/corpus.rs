@@ -0,0 +1,272 @@
+//! A directory as the input of `deflate-all` and `inflate-all`: every file in it is measured on its
+//! own, and then the results are added up per implementation.
+//!
+//! One file says little about a corpus: an implementation can be fast on text and slow on images.
+//! The per-file report is the usual one, and the aggregate treats the corpus as one job (the total
+//! uncompressed bytes over the total time) next to the geometric means of the per-file speeds and
+//! ratios, which weigh every file the same however large it is, the usual summary of compression
+//! benchmarks. Every implementation also counts the files where it is faster (and compresses
+//! better) than a baseline, `--baseline <implementation>` or else the first one.
+
+use std::panic::AssertUnwindSafe;
+use std::time::Duration;
+
+use crate::guard::Allocation;
+use crate::progress::Progress;
+use crate::{
+    dylib, inflate_config, input_len, interrupt, output_hash, read_input, run_all,
+    set_inflate_hash, DeflateImplementation, Mode, Options, ReturnCode,
+};
+
+/// The regular files in `dir`, sorted, so that runs go through them in the same order
+fn files(dir: &str) -> Vec<String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => panic!("error reading {dir:?}: {e}"),
+    };
+
+    let mut files: Vec<_> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+        .map(|entry| entry.path().to_string_lossy().into_owned())
+        .collect();
+    files.sort();
+    files
+}
+
+fn decompress<T: DeflateImplementation>(output: &mut [u8], input: &[u8]) -> Option<u64> {
+    let (output, err) = T::uncompress_slice(output, input, inflate_config());
+    (err == ReturnCode::Ok).then(|| output_hash(output))
+}
+
+type Decompress = fn(&mut [u8], &[u8]) -> Option<u64>;
+
+/// The implementations that decide what a compressed file decompresses to, in order
+const REFERENCES: &[Decompress] = &[
+    #[cfg(feature = "og")]
+    decompress::<crate::ZlibOg>,
+    #[cfg(feature = "ng")]
+    decompress::<crate::ZlibNg>,
+    #[cfg(feature = "rs")]
+    decompress::<crate::ZlibRs>,
+    #[cfg(feature = "cloudflare")]
+    decompress::<crate::ZlibCloudflare>,
+];
+
+/// The hash of what `path` decompresses to, by the first reference implementation that can, which
+/// `helper` checks the output of every implementation against
+fn reference_hash(path: &str) -> Result<u64, String> {
+    let input = read_input(path).map_err(|e| format!("error opening {path:?}: {e}"))?;
+    let mut output = vec![0u8; Allocation::HEAP.output_len];
+
+    let system = dylib::system()
+        .is_ok()
+        .then_some(decompress::<dylib::SystemZlib> as Decompress);
+    REFERENCES
+        .iter()
+        .copied()
+        .chain(system)
+        .find_map(|decompress| decompress(&mut output, &input))
+        .ok_or_else(|| {
+            "not a zlib stream that any reference implementation decompresses".to_string()
+        })
+}
+
+/// What a panic was raised with
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload
+            .downcast_ref::<String>()
+            .map_or("panicked", String::as_str),
+    }
+}
+
+/// Wins, ties and losses against the baseline
+#[derive(Default)]
+struct Score {
+    wins: usize,
+    ties: usize,
+    losses: usize,
+}
+
+impl Score {
+    fn add(&mut self, value: f64, baseline: f64) {
+        match value.total_cmp(&baseline) {
+            std::cmp::Ordering::Greater => self.wins += 1,
+            std::cmp::Ordering::Equal => self.ties += 1,
+            std::cmp::Ordering::Less => self.losses += 1,
+        }
+    }
+}
+
+/// The files of one implementation
+#[derive(Default)]
+struct Aggregate {
+    files: usize,
+    input_bytes: u64,
+    output_bytes: u64,
+    uncompressed_bytes: u64,
+    /// the median steady-state time of every file, together
+    time: Duration,
+    /// the natural logarithms of the per-file MB/s and ratios, for the geometric means, of the files
+    /// where both are positive (an empty output has neither)
+    geomean_files: usize,
+    ln_mb_per_s: f64,
+    ln_ratio: f64,
+    /// the file with the lowest MB/s
+    slowest: Option<(f64, String)>,
+}
+
+pub fn corpus_all(mode: Mode, dir: &str, level: i32, options: &Options) {
+    assert!(options.threads.is_none(), "--threads takes a single file");
+
+    let files = files(dir);
+    if files.is_empty() {
+        panic!("no files in {dir:?}");
+    }
+    println!("# corpus: {} files in {dir}", files.len());
+
+    // in bytes of input, assuming that every byte takes about as long
+    let mut progress = Progress::new(files.iter().map(|path| input_len(path) as f64).sum());
+
+    let mut aggregates: Vec<(&'static str, Aggregate)> = Vec::new();
+    // the (implementation, MB/s, ratio) of every file
+    let mut per_file: Vec<Vec<(&'static str, f64, f64)>> = Vec::new();
+    for (done, path) in files.iter().enumerate() {
+        if interrupt::interrupted() {
+            println!();
+            println!("# interrupted: {done} of {} files measured", files.len());
+            break;
+        }
+        println!();
+        println!("# file: {path}");
+
+        let input_bytes = input_len(path);
+        if input_bytes == 0 {
+            println!("# {path}: skipped, empty");
+            continue;
+        }
+
+        // a failing file is skipped, rather than losing what the others measured
+        let run = || {
+            if let Mode::Inflate = mode {
+                set_inflate_hash(reference_hash(path)?);
+            }
+            Ok(run_all(mode, path, level, options, Some(&mut progress)))
+        };
+        let result = match std::panic::catch_unwind(AssertUnwindSafe(run)) {
+            Ok(result) => result,
+            Err(payload) => Err(panic_message(&*payload).to_string()),
+        };
+        progress.clear();
+        progress.advance(input_bytes as f64);
+        let results = match result {
+            Ok(results) => results,
+            Err(e) => {
+                println!("# {path}: skipped, {e}");
+                continue;
+            }
+        };
+
+        let mut cells = Vec::new();
+        for (name, deltas, _, output_len, _) in results {
+            // of the steady state iterations, like the summary of `run_all`
+            let mut steady = deltas[1..].to_vec();
+            steady.sort();
+            let median = steady[steady.len() / 2];
+
+            let uncompressed = match mode {
+                Mode::Deflate => input_bytes,
+                Mode::Inflate => output_len as u64,
+            };
+            let mb_per_s = uncompressed as f64 / 1e6 / median.as_secs_f64();
+            let ratio = match mode {
+                Mode::Deflate => input_bytes as f64 / output_len as f64,
+                Mode::Inflate => output_len as f64 / input_bytes as f64,
+            };
+            cells.push((name, mb_per_s, ratio));
+
+            let index = match aggregates.iter().position(|(n, _)| *n == name) {
+                Some(index) => index,
+                None => {
+                    aggregates.push((name, Aggregate::default()));
+                    aggregates.len() - 1
+                }
+            };
+            let aggregate = &mut aggregates[index].1;
+
+            aggregate.files += 1;
+            aggregate.input_bytes += input_bytes;
+            aggregate.output_bytes += output_len as u64;
+            aggregate.uncompressed_bytes += uncompressed;
+            aggregate.time += median;
+            if mb_per_s > 0.0 && ratio > 0.0 {
+                aggregate.geomean_files += 1;
+                aggregate.ln_mb_per_s += mb_per_s.ln();
+                aggregate.ln_ratio += ratio.ln();
+            }
+            if aggregate
+                .slowest
+                .as_ref()
+                .is_none_or(|(slowest, _)| mb_per_s < *slowest)
+            {
+                aggregate.slowest = Some((mb_per_s, path.clone()));
+            }
+        }
+        per_file.push(cells);
+    }
+
+    if aggregates.is_empty() {
+        return;
+    }
+
+    let baseline = match &options.baseline {
+        Some(baseline) => baseline.as_str(),
+        None => aggregates[0].0,
+    };
+    if !aggregates.iter().any(|(name, _)| *name == baseline) {
+        panic!("the baseline {baseline:?} did not run");
+    }
+
+    // against the baseline, on the files where both ran
+    let mut scores: Vec<(Score, Score)> = aggregates.iter().map(|_| Default::default()).collect();
+    for cells in &per_file {
+        let Some(&(_, base_speed, base_ratio)) = cells.iter().find(|(n, ..)| *n == baseline) else {
+            continue;
+        };
+        for &(name, speed, ratio) in cells {
+            let index = aggregates.iter().position(|(n, _)| *n == name).unwrap();
+            scores[index].0.add(speed, base_speed);
+            scores[index].1.add(ratio, base_ratio);
+        }
+    }
+
+    println!();
+    println!("# corpus: {dir}, level {level}, baseline: {baseline}");
+    println!("implementation, files, input bytes, output bytes, ratio, geometric mean ratio, corpus MB/s, geometric mean MB/s, slowest file MB/s, slowest file, faster/tied/slower than the baseline, smaller/tied/larger than the baseline");
+    for ((name, aggregate), (speed, ratio)) in aggregates.into_iter().zip(scores) {
+        let compressed = match mode {
+            Mode::Deflate => aggregate.output_bytes,
+            Mode::Inflate => aggregate.input_bytes,
+        };
+        let (slowest_mb_per_s, slowest) = aggregate.slowest.unwrap_or_default();
+
+        println!(
+            "{name}, {}, {}, {}, {:.3}, {:.3}, {:.2}, {:.2}, {slowest_mb_per_s:.2}, {slowest}, {}/{}/{}, {}/{}/{}",
+            aggregate.files,
+            aggregate.input_bytes,
+            aggregate.output_bytes,
+            aggregate.uncompressed_bytes as f64 / compressed as f64,
+            (aggregate.ln_ratio / aggregate.geomean_files as f64).exp(),
+            aggregate.uncompressed_bytes as f64 / 1e6 / aggregate.time.as_secs_f64(),
+            (aggregate.ln_mb_per_s / aggregate.geomean_files as f64).exp(),
+            speed.wins,
+            speed.ties,
+            speed.losses,
+            ratio.wins,
+            ratio.ties,
+            ratio.losses,
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Hardware performance counters via `perf_event_open(2)`.
+//!
+//! Cycles and instructions per byte are less sensitive to frequency scaling than MB/s, and are the
+//! numbers that optimization work on the implementations is usually discussed in. Counters are not
+//! always available (virtual machines, containers, `perf_event_paranoid`, any OS but Linux), in
+//! which case `open` explains why.
+
+#[cfg(target_os = "linux")]
+const PERF_TYPE_HARDWARE: u32 = 0;
+const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+
+const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+const PERF_EVENT_IOC_RESET: libc::c_ulong = 0x2403;
+
+#[cfg(target_os = "linux")]
+const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 8;
+
+/// `struct perf_event_attr` up to `PERF_ATTR_SIZE_VER5`
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    /// disabled (bit 0), exclude_kernel (bit 5), exclude_hv (bit 6), ...
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+    config2: u64,
+    branch_sample_type: u64,
+    sample_regs_user: u64,
+    sample_stack_user: u32,
+    clockid: i32,
+    sample_regs_intr: u64,
+    aux_watermark: u32,
+    sample_max_stack: u16,
+    reserved: u16,
+}
+
+#[cfg(target_os = "linux")]
+struct Counter(libc::c_int);
+
+#[cfg(target_os = "linux")]
+impl Counter {
+    fn open(config: u64) -> Result<Self, String> {
+        let attr = PerfEventAttr {
+            type_: PERF_TYPE_HARDWARE,
+            size: core::mem::size_of::<PerfEventAttr>() as u32,
+            config,
+            // start disabled, and only count user space (allowed with perf_event_paranoid = 2)
+            flags: 1 | (1 << 5) | (1 << 6),
+            ..Default::default()
+        };
+
+        // this process, any cpu, no group
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                &attr as *const PerfEventAttr,
+                0,
+                -1,
+                -1,
+                PERF_FLAG_FD_CLOEXEC,
+            )
+        };
+
+        match fd {
+            -1 => Err(std::io::Error::last_os_error().to_string()),
+            fd => Ok(Counter(fd as libc::c_int)),
+        }
+    }
+
+    fn ioctl(&self, request: libc::c_ulong) {
+        unsafe { libc::ioctl(self.0, request as _, 0) };
+    }
+
+    fn read(&self) -> u64 {
+        let mut value = 0u64;
+        let n = unsafe { libc::read(self.0, (&mut value as *mut u64).cast(), 8) };
+        assert_eq!(n, 8, "reading a perf counter failed");
+        value
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for Counter {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0) };
+    }
+}
+
+/// Only Linux has `perf_event_open`, elsewhere a counter cannot be opened
+#[cfg(not(target_os = "linux"))]
+enum Counter {}
+
+#[cfg(not(target_os = "linux"))]
+impl Counter {
+    fn open(_config: u64) -> Result<Self, String> {
+        Err("hardware counters need perf_event_open, which only Linux has".to_string())
+    }
+
+    fn ioctl(&self, _request: libc::c_ulong) {
+        match *self {}
+    }
+
+    fn read(&self) -> u64 {
+        match *self {}
+    }
+}
+
+pub struct Counters {
+    cycles: Counter,
+    instructions: Counter,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sample {
+    pub cycles: u64,
+    pub instructions: u64,
+}
+
+impl Counters {
+    pub fn open() -> Result<Self, String> {
+        Ok(Counters {
+            cycles: Counter::open(PERF_COUNT_HW_CPU_CYCLES)?,
+            instructions: Counter::open(PERF_COUNT_HW_INSTRUCTIONS)?,
+        })
+    }
+
+    pub fn start(&self) {
+        for counter in [&self.cycles, &self.instructions] {
+            counter.ioctl(PERF_EVENT_IOC_RESET);
+            counter.ioctl(PERF_EVENT_IOC_ENABLE);
+        }
+    }
+
+    pub fn stop(&self) -> Sample {
+        for counter in [&self.cycles, &self.instructions] {
+            counter.ioctl(PERF_EVENT_IOC_DISABLE);
+        }
+
+        Sample {
+            cycles: self.cycles.read(),
+            instructions: self.instructions.read(),
+        }
+    }
+}
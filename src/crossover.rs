@@ -0,0 +1,207 @@
+//! Where one implementation starts beating another.
+//!
+//! Which implementation is faster often depends on the size of the input: one with a cheap setup
+//! wins on small messages, one with a fast inner loop on large files. A service that picks a codec
+//! per request wants to know where that crossover is on its own data. This binary-searches the size
+//! of a prefix of the input for the point where the faster of the two changes, or with `levels`,
+//! compares them at every level on the whole input. The input is uncompressed in both modes, for
+//! inflate every prefix is compressed first (with the system zlib).
+
+use std::time::Duration;
+
+use crate::{
+    another_run, deflate_config, dylib, inflate_config, timer, DeflateImplementation, Mode,
+    ReturnCode,
+};
+
+/// How long every implementation is repeated for at one size (or level)
+const BUDGET: Duration = Duration::from_millis(100);
+
+/// The smallest prefix that is searched
+const MIN_SIZE: usize = 64;
+
+/// The search stops when the bounds are within this factor of each other
+const PRECISION: f64 = 1.05;
+
+/// The median time of one call on `payload`
+fn time<T: DeflateImplementation>(
+    mode: Mode,
+    payload: &[u8],
+    level: i32,
+) -> Result<Duration, String> {
+    T::CAPABILITIES.check(mode, level)?;
+
+    let capacity = match mode {
+        Mode::Inflate => 1 << 28,
+        Mode::Deflate => 2 * payload.len() + 1024,
+    };
+    let mut output = vec![0u8; capacity];
+
+    let mut deltas = Vec::new();
+    let start = std::time::Instant::now();
+    while another_run(deltas.len(), 0, Some(BUDGET), start.elapsed()) {
+        let call = timer::now();
+        let (_, err) = match mode {
+            Mode::Inflate => T::uncompress_slice(&mut output, payload, inflate_config()),
+            Mode::Deflate => T::compress_slice(&mut output, payload, deflate_config(level)),
+        };
+        deltas.push(call.elapsed());
+
+        if err != ReturnCode::Ok {
+            return Err(format!("{err:?}"));
+        }
+    }
+
+    deltas.sort();
+    Ok(deltas[deltas.len() / 2])
+}
+
+type Time = fn(Mode, &[u8], i32) -> Result<Duration, String>;
+
+const BACKENDS: &[(&str, Time)] = &[
+    #[cfg(feature = "og")]
+    ("og", time::<crate::ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", time::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", time::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    ("cloudflare", time::<crate::ZlibCloudflare>),
+    #[cfg(feature = "miniz")]
+    ("miniz", time::<crate::MinizOxide>),
+    #[cfg(feature = "libdeflate")]
+    ("libdeflate", time::<crate::Libdeflate>),
+    #[cfg(feature = "miniz-c")]
+    ("miniz-c", time::<crate::MinizC>),
+    #[cfg(feature = "flate2")]
+    ("flate2", time::<crate::Flate2>),
+    #[cfg(feature = "classic")]
+    ("classic", time::<crate::Classic>),
+    #[cfg(feature = "yazi")]
+    ("yazi", time::<crate::Yazi>),
+    #[cfg(feature = "zune")]
+    ("zune", time::<crate::ZuneInflate>),
+    ("system", time::<dylib::SystemZlib>),
+];
+
+fn backend(name: &str) -> Time {
+    match BACKENDS.iter().find(|(n, _)| *n == name) {
+        Some((_, time)) => *time,
+        None => panic!("invalid implementation for crossover: {name:?}"),
+    }
+}
+
+/// The prefix of `size` bytes of the input, as what `mode` consumes
+fn payload(mode: Mode, input: &[u8], size: usize) -> Vec<u8> {
+    let prefix = &input[..size];
+    match mode {
+        Mode::Deflate => prefix.to_vec(),
+        Mode::Inflate => {
+            let mut output = vec![0u8; 2 * size + 1024];
+            let config = deflate_config(6);
+            let (compressed, err) = dylib::SystemZlib::compress_slice(&mut output, prefix, config);
+            assert_eq!(err, ReturnCode::Ok);
+            compressed.to_vec()
+        }
+    }
+}
+
+/// Time both implementations on `payload`, print the row, and return whether a is faster
+fn probe(
+    (a, b): (Time, Time),
+    mode: Mode,
+    payload: &[u8],
+    level: i32,
+    label: impl std::fmt::Display,
+) -> Result<bool, String> {
+    let time_a = a(mode, payload, level).map_err(|e| format!("a: {e}"))?;
+    let time_b = b(mode, payload, level).map_err(|e| format!("b: {e}"))?;
+
+    let faster = if time_a < time_b { "a" } else { "b" };
+    println!(
+        "{label}, {:.2}, {:.2}, {:.3}, {faster}",
+        time_a.as_secs_f64() * 1e6,
+        time_b.as_secs_f64() * 1e6,
+        time_b.as_secs_f64() / time_a.as_secs_f64(),
+    );
+
+    Ok(time_a < time_b)
+}
+
+/// Binary search (on a log scale) for the input size where the faster of a and b changes
+fn sizes(sides: (Time, Time), mode: Mode, input: &[u8], level: i32) -> Result<(), String> {
+    let probe = |size| probe(sides, mode, &payload(mode, input, size), level, size);
+
+    let (mut low, mut high) = (Ord::min(MIN_SIZE, input.len()), input.len());
+
+    println!("input bytes, a µs, b µs, a speedup, faster");
+    let at_low = probe(low)?;
+    let at_high = probe(high)?;
+
+    if at_low == at_high {
+        let winner = if at_low { "a" } else { "b" };
+        println!("# no crossover: {winner} is faster from {low} to {high} bytes");
+        return Ok(());
+    }
+
+    while high as f64 / low as f64 > PRECISION && high - low > 1 {
+        let middle = (low as f64 * high as f64).sqrt() as usize;
+        let middle = middle.clamp(low + 1, high - 1);
+
+        match probe(middle)? == at_low {
+            true => low = middle,
+            false => high = middle,
+        }
+    }
+
+    let (before, after) = match at_low {
+        true => ("a", "b"),
+        false => ("b", "a"),
+    };
+    println!("# crossover: {before} is faster up to {low} bytes, {after} from {high} bytes");
+
+    Ok(())
+}
+
+/// Compare a and b at every level, on the whole input
+fn levels(sides: (Time, Time), input: &[u8]) -> Result<(), String> {
+    println!("level, a µs, b µs, a speedup, faster");
+
+    let mut previous = None;
+    for level in 1..=9 {
+        let a_faster = probe(sides, Mode::Deflate, input, level, level)?;
+        if previous.is_some_and(|previous| previous != a_faster) {
+            let winner = if a_faster { "a" } else { "b" };
+            println!("# crossover: {winner} is faster from level {level}");
+        }
+        previous = Some(a_faster);
+    }
+
+    Ok(())
+}
+
+/// The `crossover` command: `crossover inflate <path> <a> <b>`, `crossover deflate <level> <path>
+/// <a> <b>`, or `crossover levels <path> <a> <b>`
+pub fn crossover_all(mode: Option<Mode>, path: &str, level: i32, a: &str, b: &str) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+    println!("# a: {a}, b: {b}");
+
+    // the prefixes that are inflated are compressed with the system zlib
+    if let (Some(Mode::Inflate), Err(e)) = (mode, dylib::system()) {
+        eprintln!("crossover: the system zlib is unavailable ({e})");
+        std::process::exit(1);
+    }
+
+    let sides = (backend(a), backend(b));
+    let result = match mode {
+        Some(mode) => sizes(sides, mode, &input, level),
+        None => levels(sides, &input),
+    };
+
+    if let Err(e) = result {
+        eprintln!("crossover: {e}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,141 @@
+//! How fast does every decoder inflate the output of every encoder?
+//!
+//! Inflate speed depends heavily on the stream: long matches are cheap per byte, stored blocks are
+//! a memcpy, and the shape of the huffman tables determines how often the decoder has to leave its
+//! fast path. So "inflate speed" is really a property of the (producer, decoder) pair, and this
+//! measures all of them.
+
+use crate::{DeflateConfig, DeflateImplementation, InflateConfig, Method, ReturnCode, Strategy};
+
+fn compress<T: DeflateImplementation>(input: &[u8], level: i32) -> Vec<u8> {
+    let config = DeflateConfig {
+        level,
+        method: Method::Deflated,
+        window_bits: 15,
+        mem_level: 8,
+        strategy: Strategy::Default,
+    };
+
+    let mut output = vec![0u8; 2 * input.len() + 1024];
+    let (compressed, err) = T::compress_slice(&mut output, input, config);
+    assert_eq!(err, ReturnCode::Ok);
+
+    let len = compressed.len();
+    output.truncate(len);
+    output
+}
+
+/// Returns the decompressed length, or the error
+fn decompress<T: DeflateImplementation>(
+    input: &[u8],
+    output: &mut [u8],
+) -> Result<usize, ReturnCode> {
+    let config = InflateConfig { window_bits: 15 };
+    match T::uncompress_slice(output, input, config) {
+        (output, ReturnCode::Ok) => Ok(output.len()),
+        (_, err) => Err(err),
+    }
+}
+
+type Compress = fn(&[u8], i32) -> Vec<u8>;
+type Decompress = fn(&[u8], &mut [u8]) -> Result<usize, ReturnCode>;
+
+/// Decoders without an encoder have no producer rows
+const BACKENDS: &[(&str, Option<Compress>, Decompress)] = &[
+    #[cfg(feature = "og")]
+    (
+        "og",
+        Some(compress::<crate::ZlibOg>),
+        decompress::<crate::ZlibOg>,
+    ),
+    #[cfg(feature = "ng")]
+    (
+        "ng",
+        Some(compress::<crate::ZlibNg>),
+        decompress::<crate::ZlibNg>,
+    ),
+    #[cfg(feature = "rs")]
+    (
+        "rs",
+        Some(compress::<crate::ZlibRs>),
+        decompress::<crate::ZlibRs>,
+    ),
+    #[cfg(feature = "cloudflare")]
+    (
+        "cloudflare",
+        Some(compress::<crate::ZlibCloudflare>),
+        decompress::<crate::ZlibCloudflare>,
+    ),
+    #[cfg(feature = "miniz")]
+    (
+        "miniz",
+        Some(compress::<crate::MinizOxide>),
+        decompress::<crate::MinizOxide>,
+    ),
+    #[cfg(feature = "libdeflate")]
+    (
+        "libdeflate",
+        Some(compress::<crate::Libdeflate>),
+        decompress::<crate::Libdeflate>,
+    ),
+    #[cfg(feature = "miniz-c")]
+    (
+        "miniz-c",
+        Some(compress::<crate::MinizC>),
+        decompress::<crate::MinizC>,
+    ),
+    #[cfg(feature = "zune")]
+    ("zune", None, decompress::<crate::ZuneInflate>),
+];
+
+pub fn decoders_all(path: &str, levels: &[i32]) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    // the best of a couple of runs per cell
+    let n = 3;
+    let mb = input.len() as f64 / 1_000_000.0;
+    let mut output = vec![0u8; input.len()];
+
+    let columns: Vec<_> = BACKENDS
+        .iter()
+        .map(|(name, _, _)| format!("{name} MB/s"))
+        .collect();
+    println!("producer, level, compressed bytes, {}", columns.join(", "));
+    for (producer, compress, _) in BACKENDS {
+        let Some(compress) = compress else {
+            continue;
+        };
+
+        for &level in levels {
+            let compressed = compress(&input, level);
+
+            let mut row = Vec::with_capacity(BACKENDS.len());
+            for (decoder, _, decompress) in BACKENDS {
+                let mut best = None;
+                for _ in 0..n {
+                    let start = std::time::Instant::now();
+                    let result = decompress(&compressed, &mut output);
+                    let elapsed = start.elapsed();
+
+                    match result {
+                        Ok(len) if len == input.len() && output == input => {}
+                        Ok(_) => panic!("{decoder}: wrong output for {producer} level {level}"),
+                        Err(err) => panic!("{decoder}: {err:?} for {producer} level {level}"),
+                    }
+
+                    best = Some(best.map_or(elapsed, |best| Ord::min(best, elapsed)));
+                }
+
+                row.push(format!("{:.2}", mb / best.unwrap().as_secs_f64()));
+            }
+
+            println!(
+                "{producer}, {level}, {}, {}",
+                compressed.len(),
+                row.join(", ")
+            );
+        }
+    }
+}
@@ -0,0 +1,440 @@
+//! Preset dictionaries in the middle of a stream.
+//!
+//! Delta-encoding protocols compress every message against the previous one: after a full flush
+//! (which resets the compression history) the compressor is re-primed with `deflateSetDictionary`.
+//! This is only valid for raw deflate, and is a sequence of calls that backends tend to get subtly
+//! different, so we check that every backend accepts it and that the output roundtrips.
+//!
+//! Small messages that share structure (JSON, log lines, protocol buffers) compress much better
+//! with a preset dictionary of common substrings. `dict build` derives one from a corpus, and
+//! measures the gain on samples that were held out of the training set.
+
+use core::mem::MaybeUninit;
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    DeflateConfig, Flush, InflateConfig, Method, ReturnCode, Strategy, ZlibImplementation,
+};
+
+/// The size of the deflate window; a longer dictionary is pointless
+const WINSIZE: usize = 32 * 1024;
+
+/// The size of the messages that the input is split into
+const MESSAGE_SIZE: usize = 16 * 1024;
+
+fn dictionary(previous: &[u8]) -> &[u8] {
+    &previous[previous.len().saturating_sub(WINSIZE)..]
+}
+
+/// Compress the messages into one raw deflate stream, with a full flush after every message.
+///
+/// Returns the compressed stream and the offset in it where every message ends.
+fn compress<T: ZlibImplementation>(
+    messages: &[&[u8]],
+    level: i32,
+    use_dictionary: bool,
+) -> Result<(Vec<u8>, Vec<usize>), String> {
+    let config = DeflateConfig {
+        level,
+        method: Method::Deflated,
+        window_bits: -15,
+        mem_level: 8,
+        strategy: Strategy::Default,
+    };
+
+    let mut stream = MaybeUninit::zeroed();
+    let err = T::deflate_init(stream.as_mut_ptr(), config);
+    if err != ReturnCode::Ok {
+        return Err(format!("deflateInit2: {err:?}"));
+    }
+    let stream = unsafe { stream.assume_init_mut() };
+
+    let input_len: usize = messages.iter().map(|m| m.len()).sum();
+    let mut output = vec![0u8; 2 * input_len + 1024];
+    let mut boundaries = Vec::with_capacity(messages.len());
+
+    let result = (|| {
+        for (i, message) in messages.iter().enumerate() {
+            if use_dictionary && i > 0 {
+                let err = T::deflate_set_dictionary(stream, dictionary(messages[i - 1]));
+                if err != ReturnCode::Ok {
+                    return Err(format!("deflateSetDictionary at message {i}: {err:?}"));
+                }
+            }
+
+            T::set_in(stream, message);
+            T::set_out(stream, &output[T::total_out(stream)..]);
+
+            let err = T::deflate(stream, Flush::FullFlush);
+            if err != ReturnCode::Ok || *T::avail_in_mut(stream) != 0 {
+                return Err(format!("deflate at message {i}: {err:?}"));
+            }
+
+            boundaries.push(T::total_out(stream));
+        }
+
+        T::set_in(stream, &[]);
+        T::set_out(stream, &output[T::total_out(stream)..]);
+        match T::deflate(stream, Flush::Finish) {
+            ReturnCode::StreamEnd => Ok(()),
+            err => Err(format!("deflate finish: {err:?}")),
+        }
+    })();
+
+    let total_out = T::total_out(stream);
+    T::deflate_end(stream);
+
+    result?;
+    output.truncate(total_out);
+
+    Ok((output, boundaries))
+}
+
+fn decompress<T: ZlibImplementation>(
+    compressed: &[u8],
+    boundaries: &[usize],
+    messages: &[&[u8]],
+    use_dictionary: bool,
+) -> Result<Vec<u8>, String> {
+    let config = InflateConfig { window_bits: -15 };
+
+    let mut stream = MaybeUninit::zeroed();
+    let err = T::inflate_init(stream.as_mut_ptr(), config);
+    if err != ReturnCode::Ok {
+        return Err(format!("inflateInit2: {err:?}"));
+    }
+    let stream = unsafe { stream.assume_init_mut() };
+
+    let output_len: usize = messages.iter().map(|m| m.len()).sum();
+    let mut output = vec![0u8; output_len];
+
+    let result = (|| {
+        let mut start = 0;
+        for (i, end) in boundaries.iter().copied().enumerate() {
+            if use_dictionary && i > 0 {
+                let err = T::inflate_set_dictionary(stream, dictionary(messages[i - 1]));
+                if err != ReturnCode::Ok {
+                    return Err(format!("inflateSetDictionary at message {i}: {err:?}"));
+                }
+            }
+
+            T::set_in(stream, &compressed[start..end]);
+            T::set_out(stream, &output[T::total_out(stream)..]);
+
+            let err = T::inflate(stream, Flush::SyncFlush);
+            if err != ReturnCode::Ok || *T::avail_in_mut(stream) != 0 {
+                return Err(format!("inflate at message {i}: {err:?}"));
+            }
+
+            start = end;
+        }
+
+        Ok(())
+    })();
+
+    let total_out = T::total_out(stream);
+    T::inflate_end(stream);
+
+    result?;
+    output.truncate(total_out);
+
+    Ok(output)
+}
+
+type Compress = fn(&[&[u8]], i32, bool) -> Result<(Vec<u8>, Vec<usize>), String>;
+type Decompress = fn(&[u8], &[usize], &[&[u8]], bool) -> Result<Vec<u8>, String>;
+
+const BACKENDS: &[(&str, Compress, Decompress)] = &[
+    #[cfg(feature = "og")]
+    ("og", compress::<crate::ZlibOg>, decompress::<crate::ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", compress::<crate::ZlibNg>, decompress::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", compress::<crate::ZlibRs>, decompress::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    (
+        "cloudflare",
+        compress::<crate::ZlibCloudflare>,
+        decompress::<crate::ZlibCloudflare>,
+    ),
+];
+
+pub fn flush_dictionary_all(path: &str, level: i32) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    let messages: Vec<&[u8]> = input.chunks(MESSAGE_SIZE).collect();
+    let mb = input.len() as f64 / 1_000_000.0;
+
+    println!("implementation, status, bytes flush only, bytes with dictionary, MB/s flush only, MB/s with dictionary");
+    for (name, compress, decompress) in BACKENDS {
+        let mut sizes = [0; 2];
+        let mut speeds = [0.0; 2];
+        let mut status = String::from("ok");
+
+        for (i, use_dictionary) in [false, true].into_iter().enumerate() {
+            let start = std::time::Instant::now();
+            let result = compress(&messages, level, use_dictionary);
+            let elapsed = start.elapsed();
+
+            let roundtrip = result.and_then(|(compressed, boundaries)| {
+                let output = decompress(&compressed, &boundaries, &messages, use_dictionary)?;
+
+                match output == input {
+                    true => Ok(compressed.len()),
+                    false => Err("roundtrip mismatch".to_string()),
+                }
+            });
+
+            match roundtrip {
+                Ok(size) => {
+                    sizes[i] = size;
+                    speeds[i] = mb / elapsed.as_secs_f64();
+                }
+                Err(e) => status = format!("FAILED ({e})"),
+            }
+        }
+
+        println!(
+            "{name}, {status}, {}, {}, {}, {}",
+            sizes[0], sizes[1], speeds[0], speeds[1]
+        );
+    }
+}
+
+/// The length of the substrings that we count
+const KEY: usize = 16;
+
+/// The amount of context that is added to the dictionary for every selected substring
+const SEGMENT: usize = 64;
+
+/// Every `HOLD_OUT`th sample is used for the benchmark rather than for training
+const HOLD_OUT: usize = 10;
+
+/// Training on more data makes the counting slow, but barely improves the dictionary
+const MAX_TRAINING: usize = 4 << 20;
+
+/// In how many samples a substring occurs, the last sample that it occurred in, and where (sample,
+/// offset) it first occurs
+type Count = (usize, usize, (usize, usize));
+
+/// Build a dictionary of at most `WINSIZE` bytes from the substrings that occur in the most samples.
+///
+/// The most common content goes at the end of the dictionary, where the match distances (and
+/// therefore the cost of using it) are the smallest.
+pub fn build(samples: &[&[u8]]) -> Vec<u8> {
+    let mut counts: HashMap<&[u8], Count> = HashMap::new();
+    for (i, sample) in samples.iter().enumerate() {
+        for (offset, key) in sample.windows(KEY).enumerate() {
+            let entry = counts.entry(key).or_insert((0, i, (i, offset)));
+            if entry.0 == 0 || entry.1 != i {
+                // only count a substring once per sample
+                entry.0 += 1;
+                entry.1 = i;
+            }
+        }
+    }
+
+    let mut candidates: Vec<_> = counts
+        .into_iter()
+        .filter(|(_, (count, _, _))| *count > 1)
+        .map(|(key, (count, _, first))| (count, key, first))
+        .collect();
+    // ties are broken on the substring itself so that the dictionary is deterministic
+    candidates.sort_unstable_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+
+    let mut covered = HashSet::new();
+    let mut segments = Vec::new();
+    let mut len = 0;
+
+    for (_, key, (sample, offset)) in candidates {
+        if len >= WINSIZE {
+            break;
+        }
+        if covered.contains(key) {
+            continue;
+        }
+
+        // the first occurrence, with some context along
+        let sample = samples[sample];
+        let segment = &sample[offset..Ord::min(offset + SEGMENT, sample.len())];
+        let segment = &segment[..Ord::min(segment.len(), WINSIZE - len)];
+
+        covered.extend(segment.windows(KEY));
+        segments.push(segment);
+        len += segment.len();
+    }
+
+    // most common last
+    segments
+        .iter()
+        .rev()
+        .flat_map(|s| s.iter().copied())
+        .collect()
+}
+
+/// Compress one sample in the zlib format, optionally with a preset dictionary
+fn compress_sample<T: ZlibImplementation>(
+    sample: &[u8],
+    output: &mut [u8],
+    level: i32,
+    dictionary: Option<&[u8]>,
+) -> Result<usize, String> {
+    let config = DeflateConfig {
+        level,
+        method: Method::Deflated,
+        window_bits: 15,
+        mem_level: 8,
+        strategy: Strategy::Default,
+    };
+
+    let mut stream = MaybeUninit::zeroed();
+    let err = T::deflate_init(stream.as_mut_ptr(), config);
+    if err != ReturnCode::Ok {
+        return Err(format!("deflateInit2: {err:?}"));
+    }
+    let stream = unsafe { stream.assume_init_mut() };
+
+    let result = (|| {
+        if let Some(dictionary) = dictionary {
+            let err = T::deflate_set_dictionary(stream, dictionary);
+            if err != ReturnCode::Ok {
+                return Err(format!("deflateSetDictionary: {err:?}"));
+            }
+        }
+
+        T::set_in(stream, sample);
+        T::set_out(stream, output);
+
+        match T::deflate(stream, Flush::Finish) {
+            ReturnCode::StreamEnd => Ok(T::total_out(stream)),
+            err => Err(format!("deflate: {err:?}")),
+        }
+    })();
+
+    T::deflate_end(stream);
+
+    result
+}
+
+fn decompress_sample<T: ZlibImplementation>(
+    compressed: &[u8],
+    output: &mut [u8],
+    dictionary: Option<&[u8]>,
+) -> Result<usize, String> {
+    let config = InflateConfig { window_bits: 15 };
+
+    let mut stream = MaybeUninit::zeroed();
+    let err = T::inflate_init(stream.as_mut_ptr(), config);
+    if err != ReturnCode::Ok {
+        return Err(format!("inflateInit2: {err:?}"));
+    }
+    let stream = unsafe { stream.assume_init_mut() };
+
+    let result = (|| {
+        T::set_in(stream, compressed);
+        T::set_out(stream, output);
+
+        let mut err = T::inflate(stream, Flush::Finish);
+        if let (ReturnCode::NeedDict, Some(dictionary)) = (err, dictionary) {
+            match T::inflate_set_dictionary(stream, dictionary) {
+                ReturnCode::Ok => err = T::inflate(stream, Flush::Finish),
+                err => return Err(format!("inflateSetDictionary: {err:?}")),
+            }
+        }
+
+        match err {
+            ReturnCode::StreamEnd => Ok(T::total_out(stream)),
+            err => Err(format!("inflate: {err:?}")),
+        }
+    })();
+
+    T::inflate_end(stream);
+
+    result
+}
+
+/// Compress and decompress every sample, returning the compressed size and the compression time
+fn roundtrip<T: ZlibImplementation>(
+    samples: &[&[u8]],
+    level: i32,
+    dictionary: Option<&[u8]>,
+) -> Result<(usize, std::time::Duration), String> {
+    let max = samples.iter().map(|s| s.len()).max().unwrap_or(0);
+    let mut compressed = vec![0u8; 2 * max + 1024];
+    let mut decompressed = vec![0u8; max];
+
+    let mut total = 0;
+    let mut elapsed = std::time::Duration::ZERO;
+    for sample in samples {
+        let start = std::time::Instant::now();
+        let len = compress_sample::<T>(sample, &mut compressed, level, dictionary)?;
+        elapsed += start.elapsed();
+
+        let n = decompress_sample::<T>(&compressed[..len], &mut decompressed, dictionary)?;
+        if decompressed[..n] != **sample {
+            return Err("roundtrip mismatch".to_string());
+        }
+
+        total += len;
+    }
+
+    Ok((total, elapsed))
+}
+
+type Roundtrip = fn(&[&[u8]], i32, Option<&[u8]>) -> Result<(usize, std::time::Duration), String>;
+
+const SAMPLE_BACKENDS: &[(&str, Roundtrip)] = &[
+    #[cfg(feature = "og")]
+    ("og", roundtrip::<crate::ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", roundtrip::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", roundtrip::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    ("cloudflare", roundtrip::<crate::ZlibCloudflare>),
+];
+
+pub fn dict_build_all(path: &str, output: &str, sample_size: usize, level: i32) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    let (mut training, mut held_out) = (Vec::new(), Vec::new());
+    for (i, sample) in input.chunks(sample_size).enumerate() {
+        match i % HOLD_OUT {
+            0 => held_out.push(sample),
+            _ => training.push(sample),
+        }
+    }
+    // spread the training samples over the whole corpus
+    let step = training.len().div_ceil(Ord::max(MAX_TRAINING / sample_size, 1));
+    let training: Vec<_> = training.into_iter().step_by(Ord::max(step, 1)).collect();
+
+    let dictionary = build(&training);
+    std::fs::write(output, &dictionary).unwrap();
+    println!(
+        "# dictionary: {} bytes from {} training samples, written to {output:?}",
+        dictionary.len(),
+        training.len(),
+    );
+
+    let mb = held_out.iter().map(|s| s.len()).sum::<usize>() as f64 / 1_000_000.0;
+
+    println!("implementation, status, bytes without dictionary, bytes with dictionary, ratio gain %, MB/s without dictionary, MB/s with dictionary");
+    for (name, roundtrip) in SAMPLE_BACKENDS {
+        let without = roundtrip(&held_out, level, None);
+        let with = roundtrip(&held_out, level, Some(&dictionary));
+
+        match (without, with) {
+            (Ok((plain, plain_time)), Ok((dict, dict_time))) => println!(
+                "{name}, ok, {plain}, {dict}, {:.1}, {:.2}, {:.2}",
+                100.0 * (plain as f64 / dict as f64 - 1.0),
+                mb / plain_time.as_secs_f64(),
+                mb / dict_time.as_secs_f64(),
+            ),
+            (Err(e), _) | (_, Err(e)) => println!("{name}, FAILED ({e}), 0, 0, 0, 0, 0"),
+        }
+    }
+}
@@ -0,0 +1,462 @@
+//! A backend that is loaded at runtime with `dlopen`.
+//!
+//! The statically linked backends are fixed at build time. Loading a shared library instead makes
+//! it possible to benchmark several builds of a library in one process. The trait functions have
+//! no `self`, so the library that `ZlibDylib` calls into is selected globally with `select`.
+//!
+//! `SystemZlib` always calls into the zlib that the operating system ships. That build is often
+//! made with another compiler and other flags than the vendored `libz-sys` build, and it is what
+//! programs that link zlib dynamically actually run.
+
+use std::ffi::{c_char, c_int, c_uint, c_ulong, c_void, CStr, CString};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use std::sync::OnceLock;
+
+use crate::{
+    memory, DeflateConfig, Flush, GzHeader, InflateConfig, ReturnCode, ZlibImplementation,
+};
+
+type Alloc = unsafe extern "C" fn(*mut c_void, c_uint, c_uint) -> *mut c_void;
+type Free = unsafe extern "C" fn(*mut c_void, *mut c_void);
+
+/// zlib's `z_stream`, which starts with the same fields as zlib-ng's `zng_stream`, and is larger.
+/// It is defined here so that loading a library does not depend on any of the backends.
+#[repr(C)]
+pub struct Stream {
+    next_in: *mut u8,
+    avail_in: c_uint,
+    total_in: c_ulong,
+    next_out: *mut u8,
+    avail_out: c_uint,
+    total_out: c_ulong,
+    msg: *const c_char,
+    state: *mut c_void,
+    zalloc: Option<Alloc>,
+    zfree: Option<Free>,
+    opaque: *mut c_void,
+    data_type: c_int,
+    /// A `uLong` here, but a `uint32_t` right after `data_type` in zlib-ng's `zng_stream`, so it is
+    /// at another offset there. It is never read, so it has no accessor that could be wrong.
+    adler: c_ulong,
+    reserved: c_ulong,
+}
+
+// up to `data_type` the layouts are the same, so the fields with an accessor work for both ABIs
+const _: () = assert!(
+    core::mem::offset_of!(Stream, data_type) == core::mem::offset_of!(ZngStream, data_type)
+);
+
+/// zlib-ng's `zng_stream`, only to check its size and layout
+#[repr(C)]
+struct ZngStream {
+    next_in: *const u8,
+    avail_in: u32,
+    total_in: usize,
+    next_out: *mut u8,
+    avail_out: u32,
+    total_out: usize,
+    msg: *const c_char,
+    state: *mut c_void,
+    zalloc: Option<Alloc>,
+    zfree: Option<Free>,
+    opaque: *mut c_void,
+    data_type: c_int,
+    adler: u32,
+    reserved: c_ulong,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Abi {
+    /// zlib's API, as exported by zlib, zlib-ng in compat mode, and zlib-rs's cdylib
+    Zlib,
+    /// zlib-ng's native, `zng_`-prefixed API
+    ZlibNg,
+    /// zlib's API with Chromium's `Cr_z_` prefix, so it does not clash with the system zlib
+    Chromium,
+}
+
+impl Abi {
+    fn prefix(self) -> &'static str {
+        match self {
+            Abi::Zlib => "",
+            Abi::ZlibNg => "zng_",
+            Abi::Chromium => "Cr_z_",
+        }
+    }
+
+    fn version_symbol(self) -> &'static str {
+        match self {
+            Abi::Zlib => "zlibVersion",
+            Abi::ZlibNg => "zlibng_version",
+            Abi::Chromium => "Cr_z_zlibVersion",
+        }
+    }
+
+    /// The version that the init functions check (only the major version has to match)
+    fn version(self) -> &'static str {
+        match self {
+            Abi::Zlib | Abi::Chromium => "1.3.1\0",
+            Abi::ZlibNg => "2.1.0\0",
+        }
+    }
+
+    /// The size of the library's stream struct, that the init functions check
+    fn stream_size(self) -> c_int {
+        match self {
+            Abi::Zlib | Abi::Chromium => core::mem::size_of::<Stream>() as c_int,
+            Abi::ZlibNg => core::mem::size_of::<ZngStream>() as c_int,
+        }
+    }
+}
+
+type Init2 = unsafe extern "C" fn(*mut Stream, c_int, *const c_char, c_int) -> c_int;
+type DeflateInit2 = unsafe extern "C" fn(
+    *mut Stream,
+    c_int,
+    c_int,
+    c_int,
+    c_int,
+    c_int,
+    *const c_char,
+    c_int,
+) -> c_int;
+
+/// The functions of the zlib API that we use
+#[cfg_attr(
+    not(any(feature = "og", feature = "ng", feature = "rs", feature = "cloudflare")),
+    allow(dead_code)
+)]
+pub struct Library {
+    path: String,
+    abi: Abi,
+    version: unsafe extern "C" fn() -> *const c_char,
+    // optional, so that a library that does not export it can still be measured
+    compile_flags: Option<unsafe extern "C" fn() -> c_ulong>,
+    inflate_init2: Init2,
+    inflate: unsafe extern "C" fn(*mut Stream, c_int) -> c_int,
+    inflate_end: unsafe extern "C" fn(*mut Stream) -> c_int,
+    #[cfg_attr(not(feature = "og"), allow(dead_code))]
+    inflate_reset: unsafe extern "C" fn(*mut Stream) -> c_int,
+    inflate_get_header: unsafe extern "C" fn(*mut Stream, *mut GzHeader) -> c_int,
+    inflate_prime: unsafe extern "C" fn(*mut Stream, c_int, c_int) -> c_int,
+    inflate_set_dictionary: unsafe extern "C" fn(*mut Stream, *const u8, c_uint) -> c_int,
+    deflate_init2: DeflateInit2,
+    deflate: unsafe extern "C" fn(*mut Stream, c_int) -> c_int,
+    deflate_end: unsafe extern "C" fn(*mut Stream) -> c_int,
+    #[cfg_attr(not(feature = "og"), allow(dead_code))]
+    deflate_reset: unsafe extern "C" fn(*mut Stream) -> c_int,
+    deflate_set_header: unsafe extern "C" fn(*mut Stream, *mut GzHeader) -> c_int,
+    deflate_set_dictionary: unsafe extern "C" fn(*mut Stream, *const u8, c_uint) -> c_int,
+}
+
+/// Load the library at `path`
+#[cfg(unix)]
+pub(crate) fn dlopen(path: &CStr) -> Result<*mut c_void, String> {
+    // RTLD_DEEPBIND: the library must call its own functions, not the statically linked ones. Only
+    // glibc has it, on macOS the two-level namespace already binds a library to its own functions.
+    #[cfg(all(target_os = "linux", target_env = "gnu"))]
+    let flags = libc::RTLD_NOW | libc::RTLD_LOCAL | libc::RTLD_DEEPBIND;
+    #[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+    let flags = libc::RTLD_NOW | libc::RTLD_LOCAL;
+
+    let handle = unsafe { libc::dlopen(path.as_ptr(), flags) };
+    if handle.is_null() {
+        let error = unsafe { CStr::from_ptr(libc::dlerror()) };
+        return Err(error.to_string_lossy().into_owned());
+    }
+
+    Ok(handle)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn dlopen(_path: &CStr) -> Result<*mut c_void, String> {
+    Err("loading a library at runtime needs dlopen, which only unix has".to_string())
+}
+
+/// # Safety
+///
+/// `F` must be a function pointer type matching the signature of the symbol
+#[cfg(unix)]
+pub(crate) unsafe fn symbol<F: Copy>(handle: *mut c_void, name: &str) -> Result<F, String> {
+    let c_name = CString::new(name).unwrap();
+    let ptr = libc::dlsym(handle, c_name.as_ptr());
+    if ptr.is_null() {
+        return Err(format!("symbol {name} not found"));
+    }
+
+    Ok(core::mem::transmute_copy(&ptr))
+}
+
+#[cfg(not(unix))]
+pub(crate) unsafe fn symbol<F: Copy>(_handle: *mut c_void, _name: &str) -> Result<F, String> {
+    unreachable!("there is no handle without dlopen")
+}
+
+impl Library {
+    /// Load the library at `path`. It is never unloaded.
+    pub fn open(path: &str, abi: Abi) -> Result<&'static Library, String> {
+        let c_path = CString::new(path).map_err(|e| e.to_string())?;
+        let handle = dlopen(&c_path)?;
+
+        let prefix = abi.prefix();
+        let library = unsafe {
+            Library {
+                path: path.to_string(),
+                abi,
+                version: symbol(handle, abi.version_symbol())?,
+                compile_flags: symbol(handle, &format!("{prefix}zlibCompileFlags")).ok(),
+                inflate_init2: symbol(handle, &format!("{prefix}inflateInit2_"))?,
+                inflate: symbol(handle, &format!("{prefix}inflate"))?,
+                inflate_end: symbol(handle, &format!("{prefix}inflateEnd"))?,
+                inflate_reset: symbol(handle, &format!("{prefix}inflateReset"))?,
+                inflate_get_header: symbol(handle, &format!("{prefix}inflateGetHeader"))?,
+                inflate_prime: symbol(handle, &format!("{prefix}inflatePrime"))?,
+                inflate_set_dictionary: symbol(handle, &format!("{prefix}inflateSetDictionary"))?,
+                deflate_init2: symbol(handle, &format!("{prefix}deflateInit2_"))?,
+                deflate: symbol(handle, &format!("{prefix}deflate"))?,
+                deflate_end: symbol(handle, &format!("{prefix}deflateEnd"))?,
+                deflate_reset: symbol(handle, &format!("{prefix}deflateReset"))?,
+                deflate_set_header: symbol(handle, &format!("{prefix}deflateSetHeader"))?,
+                deflate_set_dictionary: symbol(handle, &format!("{prefix}deflateSetDictionary"))?,
+            }
+        };
+
+        Ok(Box::leak(Box::new(library)))
+    }
+
+    /// Load the library at `path`, with whichever of the APIs it exports
+    pub fn open_any(path: &str) -> Result<&'static Library, String> {
+        let mut errors = Vec::new();
+        for abi in [Abi::Zlib, Abi::ZlibNg, Abi::Chromium] {
+            match Library::open(path, abi) {
+                Ok(library) => return Ok(library),
+                // only a missing symbol is worth trying the next API for
+                Err(e) if !e.starts_with("symbol") => return Err(e),
+                Err(e) => errors.push(format!("{abi:?}: {e}")),
+            }
+        }
+
+        Err(errors.join(", "))
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The version that the library reports, e.g. `1.3.1` or `2.1.8`
+    pub fn version(&self) -> String {
+        let version = unsafe { CStr::from_ptr((self.version)()) };
+        version.to_string_lossy().into_owned()
+    }
+
+    /// The options that the library was compiled with, `zlibCompileFlags()`
+    pub fn compile_flags(&self) -> Option<c_ulong> {
+        self.compile_flags.map(|f| unsafe { f() })
+    }
+}
+
+static CURRENT: AtomicPtr<Library> = AtomicPtr::new(core::ptr::null_mut());
+
+/// Make `ZlibDylib` call into `library`
+pub fn select(library: &'static Library) {
+    CURRENT.store(library as *const Library as *mut Library, Ordering::Relaxed);
+}
+
+fn current() -> &'static Library {
+    let library = CURRENT.load(Ordering::Relaxed);
+    unsafe { library.as_ref() }.expect("no library selected")
+}
+
+/// The system zlib, or the library that `ZLIB_BENCH_SYSTEM_ZLIB` points to
+pub fn system() -> Result<&'static Library, String> {
+    static LIBRARY: OnceLock<Result<&'static Library, String>> = OnceLock::new();
+
+    let default = match cfg!(target_os = "macos") {
+        true => "/usr/lib/libz.1.dylib",
+        false => "libz.so.1",
+    };
+
+    LIBRARY
+        .get_or_init(|| {
+            let path = std::env::var("ZLIB_BENCH_SYSTEM_ZLIB").unwrap_or_else(|_| default.into());
+            Library::open(&path, Abi::Zlib)
+        })
+        .clone()
+}
+
+/// Where a `Dylib` backend finds the library to call into
+pub trait Source {
+    const NAME: &'static str;
+
+    const SYSTEM_ZLIB: bool = false;
+
+    fn load() -> Result<&'static Library, String>;
+
+    fn library() -> &'static Library {
+        match Self::load() {
+            Ok(library) => library,
+            Err(e) => panic!("{}: {e}", Self::NAME),
+        }
+    }
+}
+
+/// The library that was selected with `select`
+pub struct Selected;
+
+impl Source for Selected {
+    const NAME: &'static str = "dlopen";
+
+    fn load() -> Result<&'static Library, String> {
+        Ok(current())
+    }
+}
+
+pub struct System;
+
+impl Source for System {
+    const NAME: &'static str = "system zlib";
+
+    const SYSTEM_ZLIB: bool = true;
+
+    fn load() -> Result<&'static Library, String> {
+        system()
+    }
+}
+
+pub struct Dylib<S>(PhantomData<S>);
+
+pub type ZlibDylib = Dylib<Selected>;
+pub type SystemZlib = Dylib<System>;
+
+impl<S: Source> ZlibImplementation for Dylib<S> {
+    type Stream = Stream;
+
+    const NAME: &'static str = S::NAME;
+
+    const SYSTEM_ZLIB: bool = S::SYSTEM_ZLIB;
+
+    fn zlib_version() -> String {
+        match S::load() {
+            Ok(library) => library.version(),
+            Err(e) => format!("unavailable ({e})"),
+        }
+    }
+
+    fn zlib_compile_flags() -> Option<c_ulong> {
+        S::load().ok()?.compile_flags()
+    }
+
+    fn inflate_init(strm: *mut Self::Stream, config: InflateConfig) -> ReturnCode {
+        let library = S::library();
+        ReturnCode::from(unsafe {
+            (library.inflate_init2)(
+                strm,
+                config.window_bits,
+                library.abi.version().as_ptr().cast(),
+                library.abi.stream_size(),
+            )
+        })
+    }
+
+    fn inflate(strm: &mut Self::Stream, flush: Flush) -> ReturnCode {
+        ReturnCode::from(unsafe { (S::library().inflate)(strm, flush as _) })
+    }
+
+    fn inflate_end(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { (S::library().inflate_end)(strm) })
+    }
+
+    fn inflate_reset(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { (S::library().inflate_reset)(strm) })
+    }
+
+    fn deflate_init(strm: *mut Self::Stream, config: DeflateConfig) -> ReturnCode {
+        let library = S::library();
+        ReturnCode::from(unsafe {
+            (library.deflate_init2)(
+                strm,
+                config.level,
+                config.method as i32,
+                config.window_bits,
+                config.mem_level,
+                config.strategy as i32,
+                library.abi.version().as_ptr().cast(),
+                library.abi.stream_size(),
+            )
+        })
+    }
+
+    fn deflate(strm: &mut Self::Stream, flush: Flush) -> ReturnCode {
+        ReturnCode::from(unsafe { (S::library().deflate)(strm, flush as _) })
+    }
+
+    fn deflate_end(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { (S::library().deflate_end)(strm) })
+    }
+
+    fn deflate_reset(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { (S::library().deflate_reset)(strm) })
+    }
+
+    fn deflate_set_header(strm: &mut Self::Stream, head: &mut GzHeader) -> ReturnCode {
+        ReturnCode::from(unsafe { (S::library().deflate_set_header)(strm, head) })
+    }
+
+    fn deflate_set_dictionary(strm: &mut Self::Stream, dictionary: &[u8]) -> ReturnCode {
+        let (ptr, len) = (dictionary.as_ptr(), dictionary.len() as _);
+        ReturnCode::from(unsafe { (S::library().deflate_set_dictionary)(strm, ptr, len) })
+    }
+
+    fn inflate_get_header(strm: &mut Self::Stream, head: &mut GzHeader) -> ReturnCode {
+        ReturnCode::from(unsafe { (S::library().inflate_get_header)(strm, head) })
+    }
+
+    fn inflate_prime(strm: &mut Self::Stream, bits: i32, value: i32) -> ReturnCode {
+        ReturnCode::from(unsafe { (S::library().inflate_prime)(strm, bits, value) })
+    }
+
+    fn inflate_set_dictionary(strm: &mut Self::Stream, dictionary: &[u8]) -> ReturnCode {
+        let (ptr, len) = (dictionary.as_ptr(), dictionary.len() as _);
+        ReturnCode::from(unsafe { (S::library().inflate_set_dictionary)(strm, ptr, len) })
+    }
+
+    fn set_in(strm: &mut Self::Stream, input: &[u8]) {
+        strm.avail_in = input.len() as _;
+        strm.next_in = input.as_ptr() as *mut _;
+    }
+
+    fn set_out_raw<T>(strm: &mut Self::Stream, ptr: *const T, len: usize) {
+        strm.avail_out = len as _;
+        strm.next_out = ptr as *mut _;
+    }
+
+    fn avail_out_mut(strm: &mut Self::Stream) -> &mut core::ffi::c_uint {
+        &mut strm.avail_out
+    }
+
+    fn avail_in_mut(strm: &mut Self::Stream) -> &mut core::ffi::c_uint {
+        &mut strm.avail_in
+    }
+
+    fn total_out(strm: &Self::Stream) -> usize {
+        strm.total_out as usize
+    }
+
+    fn data_type(strm: &Self::Stream) -> i32 {
+        strm.data_type
+    }
+
+    fn set_allocator(
+        strm: *mut Self::Stream,
+        zalloc: memory::Alloc,
+        zfree: memory::Free,
+        opaque: *mut c_void,
+    ) {
+        unsafe {
+            core::ptr::addr_of_mut!((*strm).zalloc).write(Some(zalloc));
+            core::ptr::addr_of_mut!((*strm).zfree).write(Some(zfree));
+            core::ptr::addr_of_mut!((*strm).opaque).write(opaque);
+        }
+    }
+}
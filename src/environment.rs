@@ -0,0 +1,209 @@
+//! Inspect the machine for common sources of timing noise before a benchmark run.
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub name: &'static str,
+    pub value: String,
+    /// whether this setting is known to make timings less reliable
+    pub noisy: bool,
+}
+
+fn read(path: &str) -> Option<String> {
+    std::fs::read_to_string(path)
+        .ok()
+        .map(|s| s.trim().to_string())
+}
+
+pub fn check() -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    let mut governors: Vec<String> = std::fs::read_dir("/sys/devices/system/cpu")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path().join("cpufreq/scaling_governor");
+            read(path.to_str()?)
+        })
+        .collect();
+    governors.sort();
+    governors.dedup();
+    if !governors.is_empty() {
+        findings.push(Finding {
+            name: "governor",
+            noisy: governors.iter().any(|g| g != "performance"),
+            value: governors.join("/"),
+        });
+    }
+
+    if let Some(smt) = read("/sys/devices/system/cpu/smt/active") {
+        findings.push(Finding {
+            name: "smt",
+            noisy: smt == "1",
+            value: if smt == "1" { "active" } else { "inactive" }.to_string(),
+        });
+    }
+
+    if let Some(aslr) = read("/proc/sys/kernel/randomize_va_space") {
+        findings.push(Finding {
+            name: "aslr",
+            noisy: aslr != "0",
+            value: aslr,
+        });
+    }
+
+    // the active setting is the one in brackets, e.g. `always [madvise] never`
+    if let Some(thp) = read("/sys/kernel/mm/transparent_hugepage/enabled") {
+        let value = thp
+            .split_whitespace()
+            .find_map(|s| s.strip_prefix('[')?.strip_suffix(']'))
+            .unwrap_or(&thp)
+            .to_string();
+
+        findings.push(Finding {
+            name: "transparent hugepages",
+            noisy: value == "always",
+            value,
+        });
+    }
+
+    let load = read("/proc/loadavg").and_then(|s| s.split_whitespace().next()?.parse::<f64>().ok());
+    if let Some(load) = load {
+        findings.push(Finding {
+            name: "load average",
+            // another process is keeping (at least) one core busy
+            noisy: load >= 1.0,
+            value: format!("{load:.2}"),
+        });
+    }
+
+    if let Some(cpus) = affinity() {
+        findings.push(Finding {
+            name: "cpu affinity",
+            noisy: false,
+            value: cpus,
+        });
+    }
+
+    findings
+}
+
+/// The fraction of the highest clock speed of a run below which an implementation is flagged as
+/// throttled
+pub const THROTTLED: f64 = 0.9;
+
+/// The current clock speed of this core, in steps of a fixed dependency chain per second. Only the
+/// ratio of two measurements means something: a core that thermal-throttles (or otherwise lowers
+/// its clock) takes longer for the same steps, while the wall clock runs at a constant rate. Takes
+/// around a millisecond.
+pub fn clock_speed() -> f64 {
+    const STEPS: u32 = 1 << 20;
+
+    let start = std::time::Instant::now();
+    // unknown to the compiler, so that it cannot fold the steps together
+    let (mut x, multiplier) = std::hint::black_box((1u64, 6_364_136_223_846_793_005u64));
+    for _ in 0..STEPS {
+        // every step depends on the previous one, so this runs at one step per few cycles
+        x = x
+            .wrapping_mul(multiplier)
+            .wrapping_add(1_442_695_040_888_963_407);
+    }
+    std::hint::black_box(x);
+
+    f64::from(STEPS) / start.elapsed().as_secs_f64()
+}
+
+/// Pin this thread to `cpu` (`--pin-cpu`), so that the scheduler does not move it to another core
+/// during a measurement. On a machine with several sockets, or with fast and slow cores, the
+/// timings would otherwise depend on where the benchmark happened to run.
+#[cfg(target_os = "linux")]
+pub fn pin_cpu(cpu: usize) -> std::io::Result<()> {
+    if cpu >= libc::CPU_SETSIZE as usize {
+        return Err(std::io::Error::from_raw_os_error(libc::EINVAL));
+    }
+
+    let mut set = unsafe { core::mem::zeroed::<libc::cpu_set_t>() };
+    unsafe { libc::CPU_SET(cpu, &mut set) };
+
+    // 0 is the calling thread
+    let size = core::mem::size_of::<libc::cpu_set_t>();
+    match unsafe { libc::sched_setaffinity(0, size, &set) } {
+        0 => Ok(()),
+        _ => Err(std::io::Error::last_os_error()),
+    }
+}
+
+/// Only Linux has `sched_setaffinity`
+#[cfg(not(target_os = "linux"))]
+pub fn pin_cpu(_cpu: usize) -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+/// The cpus that this thread may run on, as e.g. `0-3,6`
+#[cfg(target_os = "linux")]
+fn affinity() -> Option<String> {
+    let mut set = unsafe { core::mem::zeroed::<libc::cpu_set_t>() };
+    let size = core::mem::size_of::<libc::cpu_set_t>();
+    if unsafe { libc::sched_getaffinity(0, size, &mut set) } != 0 {
+        return None;
+    }
+
+    let cpus = (0..libc::CPU_SETSIZE as usize).filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) });
+
+    // consecutive cpus as a range
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for cpu in cpus {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == cpu => *end = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+
+    let ranges: Vec<_> = ranges
+        .iter()
+        .map(|&(start, end)| match start == end {
+            true => start.to_string(),
+            false => format!("{start}-{end}"),
+        })
+        .collect();
+    Some(ranges.join(","))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn affinity() -> Option<String> {
+    None
+}
+
+/// The name of this machine, to tell results from several machines apart
+pub fn hostname() -> String {
+    read("/proc/sys/kernel/hostname").unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Print the findings as comment lines, so they end up next to the results.
+pub fn print(findings: &[Finding]) {
+    for finding in findings {
+        let marker = if finding.noisy { " (noisy)" } else { "" };
+        println!("# {}: {}{marker}", finding.name, finding.value);
+    }
+}
+
+/// Measure the memory bandwidth in GB/s (bytes read plus bytes written) with a large `memcpy`.
+pub fn memory_bandwidth() -> f64 {
+    // much larger than any last-level cache
+    const LEN: usize = 1 << 28;
+
+    let src = vec![1u8; LEN];
+    let mut dst = vec![0u8; LEN];
+
+    let best = (0..5)
+        .map(|_| {
+            let start = std::time::Instant::now();
+            dst.copy_from_slice(&src);
+            std::hint::black_box(&mut dst);
+            start.elapsed()
+        })
+        .min()
+        .unwrap();
+
+    (2 * LEN) as f64 / best.as_secs_f64() / 1e9
+}
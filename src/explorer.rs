@@ -0,0 +1,211 @@
+//! Bundle results into one self-contained HTML file to explore them in a browser.
+//!
+//! A benchmark campaign produces many `--jsonl` files (several machines, inputs and levels). The
+//! explorer combines all of their cells into a single file, without external scripts or styles, with
+//! filters per dimension, a sortable table and a bar chart, so the whole campaign can be shared as
+//! one attachment.
+
+use crate::jsonl::{escape, field};
+
+/// The dimensions that can be filtered on, in the order of the table columns
+const DIMENSIONS: [&str; 5] = ["machine", "corpus", "mode", "level", "implementation"];
+
+const TEMPLATE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>zlib-bench results</title>
+<style>
+body { font-family: sans-serif; margin: 1em 2em; }
+fieldset { display: inline-block; vertical-align: top; margin: 0 1em 1em 0; }
+label { display: block; white-space: nowrap; }
+table { border-collapse: collapse; margin-top: 1em; }
+th, td { padding: 2px 8px; text-align: left; }
+th { cursor: pointer; border-bottom: 1px solid #888; }
+td.number { text-align: right; font-variant-numeric: tabular-nums; }
+tr:nth-child(even) { background: #f4f4f4; }
+svg text { font-size: 12px; }
+</style>
+</head>
+<body>
+<h1>zlib-bench results</h1>
+<div id="filters"></div>
+<div>
+Chart <select id="metric">
+<option value="mb_per_s">MB/s</option>
+<option value="output_bytes">output bytes</option>
+</select>
+colored by <select id="color"></select>
+</div>
+<svg id="chart" width="900"></svg>
+<table id="table"></table>
+<script>
+const DATA = /*DATA*/;
+const DIMENSIONS = /*DIMENSIONS*/;
+const COLORS = ["#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7", "#9c755f", "#bab0ac"];
+
+const selected = {};
+let sortKey = "mb_per_s", sortDescending = true;
+
+function values(dimension) {
+  return [...new Set(DATA.map(row => row[dimension]))].sort((a, b) => a < b ? -1 : a > b ? 1 : 0);
+}
+
+function rows() {
+  const filtered = DATA.filter(row => DIMENSIONS.every(d => selected[d].has(row[d])));
+  return filtered.sort((a, b) => {
+    const order = a[sortKey] < b[sortKey] ? -1 : a[sortKey] > b[sortKey] ? 1 : 0;
+    return sortDescending ? -order : order;
+  });
+}
+
+function element(name, attributes, text) {
+  const e = document.createElementNS(name === "svg" || name === "rect" || name === "text" ? "http://www.w3.org/2000/svg" : "http://www.w3.org/1999/xhtml", name);
+  for (const [key, value] of Object.entries(attributes || {})) e.setAttribute(key, value);
+  if (text !== undefined) e.textContent = text;
+  return e;
+}
+
+function drawTable(rows) {
+  const table = document.getElementById("table");
+  table.replaceChildren();
+  const columns = [...DIMENSIONS, "mb_per_s", "output_bytes"];
+  const header = element("tr");
+  for (const column of columns) {
+    const arrow = column === sortKey ? (sortDescending ? " ▼" : " ▲") : "";
+    const th = element("th", {}, column.replace(/_/g, " ").replace("mb per s", "MB/s") + arrow);
+    th.onclick = () => {
+      sortDescending = column === sortKey ? !sortDescending : true;
+      sortKey = column;
+      draw();
+    };
+    header.appendChild(th);
+  }
+  table.appendChild(header);
+  for (const row of rows) {
+    const tr = element("tr");
+    for (const column of columns) {
+      const number = typeof row[column] === "number";
+      const text = column === "mb_per_s" ? Number(row[column]).toFixed(2) : String(row[column]);
+      tr.appendChild(element("td", number ? { class: "number" } : {}, text));
+    }
+    table.appendChild(tr);
+  }
+}
+
+function drawChart(rows) {
+  const svg = document.getElementById("chart");
+  svg.replaceChildren();
+  const metric = document.getElementById("metric").value;
+  const color = document.getElementById("color").value;
+  const colors = values(color);
+  // only the dimensions that differ between the visible rows are needed in the labels
+  const varying = DIMENSIONS.filter(d => new Set(rows.map(row => row[d])).size > 1);
+  const max = Math.max(...rows.map(row => row[metric]), 1);
+  const barHeight = 16, labelWidth = 360, width = 900 - labelWidth - 80;
+  svg.setAttribute("height", rows.length * (barHeight + 4) + 10);
+  rows.forEach((row, i) => {
+    const y = i * (barHeight + 4);
+    const label = varying.map(d => row[d]).join(" / ");
+    svg.appendChild(element("text", { x: labelWidth - 6, y: y + 12, "text-anchor": "end" }, label));
+    const fill = COLORS[colors.indexOf(row[color]) % COLORS.length];
+    svg.appendChild(element("rect", { x: labelWidth, y, height: barHeight, width: width * row[metric] / max, fill }));
+    const value = metric === "mb_per_s" ? Number(row[metric]).toFixed(2) : String(row[metric]);
+    svg.appendChild(element("text", { x: labelWidth + width * row[metric] / max + 4, y: y + 12 }, value));
+  });
+}
+
+function draw() {
+  const visible = rows();
+  drawChart(visible);
+  drawTable(visible);
+}
+
+for (const dimension of DIMENSIONS) {
+  selected[dimension] = new Set(values(dimension));
+  const fieldset = element("fieldset");
+  fieldset.appendChild(element("legend", {}, dimension));
+  for (const value of values(dimension)) {
+    const label = element("label");
+    const checkbox = element("input", { type: "checkbox", checked: "" });
+    checkbox.onchange = () => {
+      if (checkbox.checked) selected[dimension].add(value); else selected[dimension].delete(value);
+      draw();
+    };
+    label.appendChild(checkbox);
+    label.appendChild(document.createTextNode(" " + value));
+    fieldset.appendChild(label);
+  }
+  document.getElementById("filters").appendChild(fieldset);
+  document.getElementById("color").appendChild(element("option", { value: dimension }, dimension));
+}
+document.getElementById("color").value = "implementation";
+document.getElementById("metric").onchange = draw;
+document.getElementById("color").onchange = draw;
+draw();
+</script>
+</body>
+</html>
+"##;
+
+/// The cells of a JSON lines file, as JSON objects. Every cell belongs to the `start` event before it.
+fn records(path: &str, records: &mut Vec<String>) -> Result<(), String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+
+    let mut start = None;
+    for line in contents.lines() {
+        match field(line, "event").as_deref() {
+            Some("start") => start = Some(line),
+            Some("cell") => {
+                let Some(start) = start else {
+                    return Err(format!("{path}: a cell without a start event"));
+                };
+                let get =
+                    |line, key| field(line, key).ok_or(format!("{path}: no {key:?} in {line}"));
+
+                // older files have no host, and then the file name has to do
+                let machine = field(start, "host").unwrap_or_else(|| path.to_string());
+                let corpus = get(start, "path")?;
+                let corpus = corpus.rsplit('/').next().unwrap();
+
+                records.push(format!(
+                    "{{\"machine\":{},\"corpus\":{},\"mode\":{},\"level\":{},\"implementation\":{},\"mb_per_s\":{},\"output_bytes\":{}}}",
+                    escape(&machine),
+                    escape(corpus),
+                    escape(&get(line, "mode")?),
+                    get(line, "level")?,
+                    escape(&get(line, "implementation")?),
+                    get(line, "mb_per_s")?,
+                    get(line, "output_bytes")?,
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+pub fn export_explorer(output: &str, inputs: &[String]) {
+    let mut all = Vec::new();
+    for input in inputs {
+        if let Err(e) = records(input, &mut all) {
+            panic!("{e}");
+        }
+    }
+
+    // `<` can only occur inside of strings, where it can be escaped, so `</script>` can't end the script
+    let data = format!("[\n{}\n]", all.join(",\n")).replace('<', "\\u003c");
+    let dimensions: Vec<_> = DIMENSIONS.iter().map(|d| escape(d)).collect();
+
+    let html = TEMPLATE
+        .replace("/*DATA*/", &data)
+        .replace("/*DIMENSIONS*/", &format!("[{}]", dimensions.join(", ")));
+    std::fs::write(output, html).unwrap();
+
+    println!(
+        "# {} cells from {} files written to {output:?}",
+        all.len(),
+        inputs.len()
+    );
+}
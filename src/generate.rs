@@ -0,0 +1,116 @@
+//! Synthetic inputs that target one stage of the compressor.
+//!
+//! Real corpora mix everything together, which makes it hard to tell which stage of a compressor is
+//! responsible for a difference in speed. These generators produce data that is deterministic (the
+//! same bytes every run) and that stresses one stage in particular.
+
+use crate::rng::Rng;
+
+/// Every section of the output uses one of the patterns
+const SECTION: usize = 64 * 1024;
+
+/// zlib's insert hash with the default `memLevel` of 8: `hash_bits` is 15, and the shift is 5
+fn zlib_hash(bytes: [u8; 3]) -> u16 {
+    let h = ((bytes[0] as u32) << 10) ^ ((bytes[1] as u32) << 5) ^ bytes[2] as u32;
+    (h & 0x7fff) as u16
+}
+
+/// Data that stresses the LZ77 match finder.
+///
+/// Sections alternate between
+///
+/// - near-matches: copies of a pattern with one byte changed, so that every candidate in a long
+///   hash chain matches for a while and then fails;
+/// - maximal matches: a block that repeats at a distance larger than the maximum match length, so
+///   that every match is 258 bytes long and the match finder has to decide when to stop looking;
+/// - hash collisions: 3-byte strings that all land in the same bucket of zlib's hash table, so
+///   that its hash chains are long but almost never contain a real match.
+pub fn matches(size: usize, rng: &mut Rng) -> Vec<u8> {
+    // all 3-byte strings that collide with the first one in zlib's hash
+    let colliding: Vec<[u8; 3]> = (0..1u32 << 24)
+        .map(|i| [(i >> 16) as u8, (i >> 8) as u8, i as u8])
+        .filter(|bytes| zlib_hash(*bytes) == 0)
+        .collect();
+
+    let mut output = Vec::with_capacity(size + SECTION);
+    while output.len() < size {
+        let end = output.len() + SECTION;
+        match rng.below(3) {
+            0 => {
+                let len = 16 + rng.below(112) as usize;
+                let pattern: Vec<u8> = (0..len).map(|_| rng.next_u64() as u8).collect();
+                while output.len() < end {
+                    let start = output.len();
+                    output.extend_from_slice(&pattern);
+                    let i = start + rng.below(len as u64) as usize;
+                    output[i] = rng.next_u64() as u8;
+                }
+            }
+            1 => {
+                let len = 259 + rng.below(1024) as usize;
+                let block: Vec<u8> = (0..len).map(|_| rng.next_u64() as u8).collect();
+                while output.len() < end {
+                    output.extend_from_slice(&block);
+                }
+            }
+            _ => {
+                while output.len() < end {
+                    let i = rng.below(colliding.len() as u64) as usize;
+                    output.extend_from_slice(&colliding[i]);
+                }
+            }
+        }
+    }
+
+    output.truncate(size);
+    output
+}
+
+/// Literal-heavy data that stresses the entropy coder.
+///
+/// Every segment (4 to 16 KiB) draws its bytes from a Zipf-like distribution over all 256 byte
+/// values, with a random skew and a random assignment of byte values to ranks. All byte values
+/// occur, so the dynamic huffman tables are large and have long codes, and the distribution changes
+/// all the time, so a tree built for one block is a poor fit for the next.
+pub fn entropy(size: usize, rng: &mut Rng) -> Vec<u8> {
+    let mut output = Vec::with_capacity(size);
+    while output.len() < size {
+        let len = 4096 + rng.below(12 * 1024) as usize;
+
+        let mut symbols: Vec<u8> = (0..=255).collect();
+        for i in (1..symbols.len()).rev() {
+            symbols.swap(i, rng.below(i as u64 + 1) as usize);
+        }
+
+        // an exponent between 0.5 (fairly flat) and 2.0 (very skewed)
+        let exponent = 0.5 + rng.below(1500) as f64 / 1000.0;
+        let mut cumulative = Vec::with_capacity(256);
+        let mut total = 0.0;
+        for rank in 0..256 {
+            total += 1.0 / ((rank + 1) as f64).powf(exponent);
+            cumulative.push(total);
+        }
+
+        for _ in 0..len {
+            let u = (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64 * total;
+            let rank = cumulative.partition_point(|&c| c <= u);
+            output.push(symbols[Ord::min(rank, 255)]);
+        }
+    }
+
+    output.truncate(size);
+    output
+}
+
+pub fn generate_all(kind: &str, path: &str, size: usize) {
+    let mut rng = Rng::new(0);
+
+    let output = match kind {
+        "matches" => matches(size, &mut rng),
+        "entropy" => entropy(size, &mut rng),
+        other => panic!("invalid generator {other:?}"),
+    };
+
+    std::fs::write(path, &output).unwrap();
+    println!("# {kind}: {} bytes written to {path:?}", output.len());
+}
@@ -0,0 +1,175 @@
+//! Buffers that are flush against a protected guard page.
+//!
+//! The buffer is mapped with an inaccessible page on either side, and placed either at the start
+//! (`Front`) or at the end (`Back`) of its pages. Any read or write past the start or end of the
+//! buffer then faults immediately, instead of silently touching some other part of the heap.
+
+use core::ops::{Deref, DerefMut};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Guard {
+    /// the buffer starts right after a guard page: catches accesses before the start
+    Front,
+    /// the buffer ends right before a guard page: catches accesses past the end
+    Back,
+}
+
+impl Guard {
+    pub fn parse(s: &str) -> Self {
+        // the guard pages are set up with `mmap` and `mprotect`
+        if !cfg!(unix) {
+            panic!("--guard is only supported on unix");
+        }
+
+        match s {
+            "front" => Guard::Front,
+            "back" => Guard::Back,
+            other => panic!("invalid guard {other:?}"),
+        }
+    }
+
+    /// The name on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Guard::Front => "front",
+            Guard::Back => "back",
+        }
+    }
+}
+
+/// How `helper` allocates its input and output buffers
+#[derive(Debug, Clone, Copy)]
+pub struct Allocation {
+    pub guard: Option<Guard>,
+    pub output_len: usize,
+}
+
+impl Allocation {
+    /// Plain heap allocations, and an output buffer that is large enough for any of our inputs
+    pub const HEAP: Self = Allocation {
+        guard: None,
+        output_len: 1 << 28,
+    };
+}
+
+pub struct GuardedBuffer {
+    map: *mut u8,
+    #[cfg_attr(not(unix), allow(dead_code))]
+    map_len: usize,
+    start: usize,
+    len: usize,
+}
+
+impl GuardedBuffer {
+    #[cfg(unix)]
+    pub fn new(len: usize, guard: Guard) -> Self {
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let data_len = Ord::max(len.div_ceil(page), 1) * page;
+        let map_len = page + data_len + page;
+
+        let map = unsafe {
+            libc::mmap(
+                core::ptr::null_mut(),
+                map_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(map, libc::MAP_FAILED, "mmap failed");
+        let map = map.cast::<u8>();
+
+        unsafe {
+            let err = libc::mprotect(map.cast(), page, libc::PROT_NONE);
+            assert_eq!(err, 0, "mprotect failed");
+            let err = libc::mprotect(map.add(page + data_len).cast(), page, libc::PROT_NONE);
+            assert_eq!(err, 0, "mprotect failed");
+        }
+
+        let start = match guard {
+            Guard::Front => page,
+            Guard::Back => page + data_len - len,
+        };
+
+        Self {
+            map,
+            map_len,
+            start,
+            len,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn new(_len: usize, _guard: Guard) -> Self {
+        unreachable!("--guard is rejected when it is parsed")
+    }
+}
+
+#[cfg(unix)]
+impl Drop for GuardedBuffer {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.map.cast(), self.map_len) };
+    }
+}
+
+impl Deref for GuardedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { core::slice::from_raw_parts(self.map.add(self.start), self.len) }
+    }
+}
+
+impl DerefMut for GuardedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.map.add(self.start), self.len) }
+    }
+}
+
+pub enum Buffer {
+    Heap(Vec<u8>),
+    Guarded(GuardedBuffer),
+}
+
+impl Buffer {
+    /// A zeroed buffer of `len` bytes
+    pub fn new(len: usize, guard: Option<Guard>) -> Self {
+        match guard {
+            None => Buffer::Heap(vec![0; len]),
+            Some(guard) => Buffer::Guarded(GuardedBuffer::new(len, guard)),
+        }
+    }
+
+    /// A buffer with the contents of `bytes`; they are only copied when a guard is used
+    pub fn from_vec(bytes: Vec<u8>, guard: Option<Guard>) -> Self {
+        match guard {
+            None => Buffer::Heap(bytes),
+            Some(guard) => {
+                let mut buffer = GuardedBuffer::new(bytes.len(), guard);
+                buffer.copy_from_slice(&bytes);
+                Buffer::Guarded(buffer)
+            }
+        }
+    }
+}
+
+impl Deref for Buffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Buffer::Heap(vec) => vec,
+            Buffer::Guarded(buffer) => buffer,
+        }
+    }
+}
+
+impl DerefMut for Buffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            Buffer::Heap(vec) => vec,
+            Buffer::Guarded(buffer) => buffer,
+        }
+    }
+}
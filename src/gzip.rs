@@ -0,0 +1,261 @@
+//! Check that gzip header metadata survives a roundtrip through every pair of backends.
+//!
+//! The producer writes the header with `deflateSetHeader`, the consumer reads it back with
+//! `inflateGetHeader`, and every field is compared against what was written.
+
+use core::mem::MaybeUninit;
+
+use crate::{
+    DeflateConfig, Flush, GzHeader, InflateConfig, Method, ReturnCode, Strategy, ZlibImplementation,
+};
+
+/// gzip framing: 15 bits of window, plus 16
+const WINDOW_BITS: i32 = 15 + 16;
+
+/// The header fields we can compare. Strings are stored without their NUL terminator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fields {
+    pub text: i32,
+    pub time: u64,
+    pub os: i32,
+    pub extra: Vec<u8>,
+    pub name: Vec<u8>,
+    pub comment: Vec<u8>,
+}
+
+impl Fields {
+    fn expected() -> Self {
+        Fields {
+            text: 1,
+            time: 1_234_567_890,
+            os: 3,
+            // a single subfield with id `ZB` and 4 bytes of data
+            extra: b"ZB\x04\x00data".to_vec(),
+            name: b"silesia-small.tar".to_vec(),
+            comment: b"written by zlib-bench".to_vec(),
+        }
+    }
+
+    /// The names of the fields that differ
+    fn differences(&self, other: &Self) -> Vec<&'static str> {
+        let mut differences = Vec::new();
+
+        if self.text != other.text {
+            differences.push("text");
+        }
+        if self.time != other.time {
+            differences.push("time");
+        }
+        if self.os != other.os {
+            differences.push("os");
+        }
+        if self.extra != other.extra {
+            differences.push("extra");
+        }
+        if self.name != other.name {
+            differences.push("name");
+        }
+        if self.comment != other.comment {
+            differences.push("comment");
+        }
+
+        differences
+    }
+}
+
+fn compress<T: ZlibImplementation>(input: &[u8], fields: &Fields) -> Result<Vec<u8>, ReturnCode> {
+    let config = DeflateConfig {
+        level: 6,
+        method: Method::Deflated,
+        window_bits: WINDOW_BITS,
+        mem_level: 8,
+        strategy: Strategy::Default,
+    };
+
+    let mut extra = fields.extra.clone();
+    let mut name = fields.name.clone();
+    name.push(0);
+    let mut comment = fields.comment.clone();
+    comment.push(0);
+
+    // the stream keeps a pointer to the header until the header is written
+    let mut head = GzHeader {
+        text: fields.text,
+        time: fields.time as _,
+        xflags: 0,
+        os: fields.os,
+        extra: extra.as_mut_ptr(),
+        extra_len: extra.len() as _,
+        extra_max: 0,
+        name: name.as_mut_ptr(),
+        name_max: 0,
+        comment: comment.as_mut_ptr(),
+        comm_max: 0,
+        hcrc: 1,
+        done: 0,
+    };
+
+    let mut stream = MaybeUninit::zeroed();
+    let err = T::deflate_init(stream.as_mut_ptr(), config);
+    if err != ReturnCode::Ok {
+        return Err(err);
+    }
+    let stream = unsafe { stream.assume_init_mut() };
+
+    let err = T::deflate_set_header(stream, &mut head);
+    if err != ReturnCode::Ok {
+        T::deflate_end(stream);
+        return Err(err);
+    }
+
+    let mut output = vec![0u8; 2 * input.len() + 1024];
+    T::set_in(stream, input);
+    T::set_out(stream, &output);
+
+    let err = T::deflate(stream, Flush::Finish);
+    let total_out = T::total_out(stream);
+    T::deflate_end(stream);
+
+    match err {
+        ReturnCode::StreamEnd => {
+            output.truncate(total_out);
+            Ok(output)
+        }
+        err => Err(err),
+    }
+}
+
+fn read_header<T: ZlibImplementation>(compressed: &[u8]) -> Result<Fields, ReturnCode> {
+    let config = InflateConfig {
+        window_bits: WINDOW_BITS,
+    };
+
+    let mut extra = vec![0u8; 256];
+    let mut name = vec![0u8; 256];
+    let mut comment = vec![0u8; 256];
+
+    let mut head = GzHeader {
+        text: 0,
+        time: 0,
+        xflags: 0,
+        os: 0,
+        extra: extra.as_mut_ptr(),
+        extra_len: 0,
+        extra_max: extra.len() as _,
+        name: name.as_mut_ptr(),
+        name_max: name.len() as _,
+        comment: comment.as_mut_ptr(),
+        comm_max: comment.len() as _,
+        hcrc: 0,
+        done: 0,
+    };
+
+    let mut stream = MaybeUninit::zeroed();
+    let err = T::inflate_init(stream.as_mut_ptr(), config);
+    if err != ReturnCode::Ok {
+        return Err(err);
+    }
+    let stream = unsafe { stream.assume_init_mut() };
+
+    let err = T::inflate_get_header(stream, &mut head);
+    if err != ReturnCode::Ok {
+        T::inflate_end(stream);
+        return Err(err);
+    }
+
+    // the decompressed data itself is not interesting here
+    let mut output = vec![0u8; 1 << 16];
+    T::set_in(stream, compressed);
+    let err = loop {
+        T::set_out(stream, &output);
+        match T::inflate(stream, Flush::NoFlush) {
+            ReturnCode::Ok => continue,
+            err => break err,
+        }
+    };
+    output.clear();
+    T::inflate_end(stream);
+
+    if err != ReturnCode::StreamEnd {
+        return Err(err);
+    }
+
+    if head.done != 1 {
+        return Err(ReturnCode::DataError);
+    }
+
+    let c_str = |buf: &[u8]| buf.iter().copied().take_while(|b| *b != 0).collect();
+    extra.truncate(Ord::min(head.extra_len, head.extra_max) as usize);
+
+    Ok(Fields {
+        text: head.text,
+        time: head.time as u64,
+        os: head.os,
+        extra,
+        name: c_str(&name),
+        comment: c_str(&comment),
+    })
+}
+
+type Compress = fn(&[u8], &Fields) -> Result<Vec<u8>, ReturnCode>;
+type ReadHeader = fn(&[u8]) -> Result<Fields, ReturnCode>;
+
+const BACKENDS: &[(&str, Compress, ReadHeader)] = &[
+    #[cfg(feature = "og")]
+    (
+        "og",
+        compress::<crate::ZlibOg>,
+        read_header::<crate::ZlibOg>,
+    ),
+    #[cfg(feature = "ng")]
+    (
+        "ng",
+        compress::<crate::ZlibNg>,
+        read_header::<crate::ZlibNg>,
+    ),
+    #[cfg(feature = "rs")]
+    (
+        "rs",
+        compress::<crate::ZlibRs>,
+        read_header::<crate::ZlibRs>,
+    ),
+    #[cfg(feature = "cloudflare")]
+    (
+        "cloudflare",
+        compress::<crate::ZlibCloudflare>,
+        read_header::<crate::ZlibCloudflare>,
+    ),
+];
+
+pub fn gzip_header_all(path: &str) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    let expected = Fields::expected();
+
+    println!("producer, consumer, status");
+    for (producer, compress, _) in BACKENDS {
+        let compressed = match compress(&input, &expected) {
+            Ok(compressed) => compressed,
+            Err(err) => {
+                println!("{producer}, *, compression failed: {err:?}");
+                continue;
+            }
+        };
+
+        for (consumer, _, read_header) in BACKENDS {
+            let status = match read_header(&compressed) {
+                Ok(fields) => match expected.differences(&fields)[..] {
+                    [] => "ok".to_string(),
+                    ref differences => format!("MISMATCH ({})", differences.join(" ")),
+                },
+                Err(err) => format!("FAILED ({err:?})"),
+            };
+
+            println!("{producer}, {consumer}, {status}");
+        }
+    }
+
+    println!("miniz, *, unsupported (no gzip header API)");
+}
@@ -0,0 +1,241 @@
+//! Random access into a gzip/zlib stream, after zlib's `examples/zran.c`.
+//!
+//! Building the index decompresses the whole stream once with `Z_BLOCK`, and records an access
+//! point at a deflate block boundary roughly every `span` bytes of output. An access point stores
+//! the position in the input (including the bit offset), and the 32K of output that precedes it.
+//! Reading at an offset then starts a raw inflate at the nearest access point, primes it with the
+//! leftover bits via `inflatePrime`, and restores the window with `inflateSetDictionary`.
+
+use core::mem::MaybeUninit;
+
+use crate::rng::Rng;
+use crate::{Flush, InflateConfig, ReturnCode, ZlibImplementation};
+
+/// The size of the deflate window
+const WINSIZE: usize = 32 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Point {
+    /// offset in the uncompressed data
+    out: usize,
+    /// offset in the compressed data of the first byte that is not (fully) consumed
+    input: usize,
+    /// number of bits (1-7) of the byte at `input - 1` that are still to be consumed, or 0
+    bits: i32,
+    /// the uncompressed data preceding this point, at most `WINSIZE` bytes
+    window: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Index {
+    points: Vec<Point>,
+    /// total length of the uncompressed data
+    length: usize,
+}
+
+/// Decompress `input` (with a zlib or gzip header), recording access points.
+///
+/// Also returns the decompressed data, which we use to verify the reads.
+pub fn build<T: ZlibImplementation>(
+    input: &[u8],
+    span: usize,
+) -> Result<(Index, Vec<u8>), ReturnCode> {
+    // 15 bits of window, + 32 to automatically detect zlib or gzip
+    let config = InflateConfig {
+        window_bits: 15 + 32,
+    };
+
+    let mut stream = MaybeUninit::zeroed();
+    let err = T::inflate_init(stream.as_mut_ptr(), config);
+    if err != ReturnCode::Ok {
+        return Err(err);
+    }
+    let stream = unsafe { stream.assume_init_mut() };
+
+    let mut output = vec![0u8; 1 << 16];
+    let mut points = Vec::new();
+    let mut last = 0;
+
+    T::set_in(stream, input);
+
+    let err = loop {
+        let total_out = T::total_out(stream);
+        if total_out == output.len() {
+            output.resize(2 * output.len(), 0);
+        }
+        T::set_out(stream, &output[total_out..]);
+
+        // Z_BLOCK returns at the end of every deflate block (and after the header)
+        let err = T::inflate(stream, Flush::Block);
+        match err {
+            ReturnCode::Ok => {}
+            // the output buffer is full, it is grown at the top of the loop
+            ReturnCode::BufError if T::total_out(stream) == output.len() => {}
+            err => break err,
+        }
+
+        let data_type = T::data_type(stream);
+        let total_out = T::total_out(stream);
+
+        // bit 7: at the end of a block; bit 6: that block was the last one
+        let at_boundary = data_type & 128 != 0 && data_type & 64 == 0;
+        if at_boundary && (total_out == 0 || total_out - last > span) {
+            let window_start = total_out.saturating_sub(WINSIZE);
+
+            points.push(Point {
+                out: total_out,
+                input: input.len() - *T::avail_in_mut(stream) as usize,
+                bits: data_type & 7,
+                window: output[window_start..total_out].to_vec(),
+            });
+            last = total_out;
+        }
+    };
+
+    let length = T::total_out(stream);
+    T::inflate_end(stream);
+
+    if err != ReturnCode::StreamEnd {
+        return Err(err);
+    }
+
+    output.truncate(length);
+
+    Ok((Index { points, length }, output))
+}
+
+/// Read `output.len()` bytes of uncompressed data starting at `offset`.
+pub fn extract<T: ZlibImplementation>(
+    index: &Index,
+    input: &[u8],
+    offset: usize,
+    output: &mut [u8],
+) -> Result<(), ReturnCode> {
+    assert!(offset + output.len() <= index.length);
+
+    // the last access point at or before the offset
+    let i = index.points.partition_point(|p| p.out <= offset);
+    let point = &index.points[i.checked_sub(1).expect("there is a point at offset 0")];
+
+    // raw inflate: the access point is in the middle of the deflate stream
+    let config = InflateConfig { window_bits: -15 };
+
+    let mut stream = MaybeUninit::zeroed();
+    let err = T::inflate_init(stream.as_mut_ptr(), config);
+    if err != ReturnCode::Ok {
+        return Err(err);
+    }
+    let stream = unsafe { stream.assume_init_mut() };
+
+    let result = (|| {
+        if point.bits != 0 {
+            let value = input[point.input - 1] as i32 >> (8 - point.bits);
+            match T::inflate_prime(stream, point.bits, value) {
+                ReturnCode::Ok => {}
+                err => return Err(err),
+            }
+        }
+
+        match T::inflate_set_dictionary(stream, &point.window) {
+            ReturnCode::Ok => {}
+            err => return Err(err),
+        }
+
+        T::set_in(stream, &input[point.input..]);
+
+        // decompress up to the offset into a scratch buffer
+        let mut skip = offset - point.out;
+        let discard = vec![0u8; WINSIZE];
+        while skip > 0 {
+            let n = Ord::min(skip, discard.len());
+            T::set_out(stream, &discard[..n]);
+            match T::inflate(stream, Flush::NoFlush) {
+                ReturnCode::Ok => {}
+                err => return Err(err),
+            }
+            skip -= n - *T::avail_out_mut(stream) as usize;
+        }
+
+        T::set_out(stream, output);
+        while *T::avail_out_mut(stream) > 0 {
+            match T::inflate(stream, Flush::NoFlush) {
+                ReturnCode::Ok => {}
+                ReturnCode::StreamEnd if *T::avail_out_mut(stream) == 0 => break,
+                err => return Err(err),
+            }
+        }
+
+        Ok(())
+    })();
+
+    T::inflate_end(stream);
+
+    result
+}
+
+type Build = fn(&[u8], usize) -> Result<(Index, Vec<u8>), ReturnCode>;
+type Extract = fn(&Index, &[u8], usize, &mut [u8]) -> Result<(), ReturnCode>;
+
+const BACKENDS: &[(&str, Build, Extract)] = &[
+    #[cfg(feature = "og")]
+    ("og", build::<crate::ZlibOg>, extract::<crate::ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", build::<crate::ZlibNg>, extract::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", build::<crate::ZlibRs>, extract::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    (
+        "cloudflare",
+        build::<crate::ZlibCloudflare>,
+        extract::<crate::ZlibCloudflare>,
+    ),
+];
+
+pub fn index_all(path: &str, span: usize) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    let reads = 1000;
+    let read_len = 16 * 1024;
+
+    println!("implementation, access points, build MB/s, reads/s");
+    for (name, build, extract) in BACKENDS {
+        let start = std::time::Instant::now();
+        let (index, reference) = match build(&input, span) {
+            Ok(result) => result,
+            Err(err) => {
+                println!("{name}, 0, FAILED ({err:?}), 0");
+                continue;
+            }
+        };
+        let build_time = start.elapsed();
+
+        // the same offsets for every backend
+        let mut rng = Rng::new(0);
+        let mut output = vec![0u8; read_len];
+        let max_offset = index.length.saturating_sub(read_len) as u64 + 1;
+
+        let start = std::time::Instant::now();
+        for _ in 0..reads {
+            let offset = rng.below(max_offset) as usize;
+            let len = Ord::min(read_len, index.length - offset);
+
+            if let Err(err) = extract(&index, &input, offset, &mut output[..len]) {
+                panic!("{name}: read at offset {offset} failed: {err:?}");
+            }
+            assert!(
+                output[..len] == reference[offset..][..len],
+                "{name}: wrong data at offset {offset}"
+            );
+        }
+        let read_time = start.elapsed();
+
+        println!(
+            "{name}, {}, {}, {}",
+            index.points.len(),
+            input.len() as f64 / 1_000_000.0 / build_time.as_secs_f64(),
+            reads as f64 / read_time.as_secs_f64(),
+        );
+    }
+}
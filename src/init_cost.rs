@@ -0,0 +1,202 @@
+//! The cost of setting up and tearing down a stream, next to the cost of the work itself.
+//!
+//! `deflateInit2` allocates and clears the window, the hash tables and the pending buffer, and
+//! `deflateEnd` frees them again; `inflateInit2` and `inflateEnd` do the same on a smaller scale.
+//! How much that costs differs between implementations (zlib-ng's deflate state is larger, and
+//! aligned), and for a small payload it can be more than the (de)compression itself. The `*-all`
+//! commands only see the sum, so this times the three phases separately, for payloads of
+//! increasing size taken from the start of the input.
+
+use core::mem::MaybeUninit;
+use std::time::Duration;
+
+use crate::{
+    another_run, deflate_config, inflate_config, timer, Capabilities, DeflateImplementation, Flush,
+    ReturnCode, ZlibImplementation,
+};
+
+/// How long every phase of one (implementation, mode, payload) is repeated for
+const BUDGET: Duration = Duration::from_millis(200);
+
+/// The median time of init, of the work and of end
+pub type Phases = (Duration, Duration, Duration);
+
+fn median(mut deltas: Vec<Duration>) -> Duration {
+    deltas.sort();
+    deltas[deltas.len() / 2]
+}
+
+/// Compress `payload` into `output` with a fresh stream, and return the compressed length and the
+/// time of every phase
+fn deflate_once<T: ZlibImplementation>(
+    payload: &[u8],
+    output: &mut [u8],
+    level: i32,
+) -> Result<(usize, Phases), ReturnCode> {
+    let mut stream = MaybeUninit::zeroed();
+
+    let start = timer::now();
+    let err = T::deflate_init(stream.as_mut_ptr(), deflate_config(level));
+    let init = start.elapsed();
+    if err != ReturnCode::Ok {
+        return Err(err);
+    }
+    let stream = unsafe { stream.assume_init_mut() };
+
+    T::set_in(stream, payload);
+    T::set_out(stream, output);
+
+    let start = timer::now();
+    let err = T::deflate(stream, Flush::Finish);
+    let work = start.elapsed();
+    let len = T::total_out(stream);
+
+    let start = timer::now();
+    T::deflate_end(stream);
+    let end = start.elapsed();
+
+    match err {
+        ReturnCode::StreamEnd => Ok((len, (init, work, end))),
+        err => Err(err),
+    }
+}
+
+/// Decompress `compressed` into `output` with a fresh stream, and return the decompressed length
+/// and the time of every phase
+fn inflate_once<T: ZlibImplementation>(
+    compressed: &[u8],
+    output: &mut [u8],
+) -> Result<(usize, Phases), ReturnCode> {
+    let mut stream = MaybeUninit::zeroed();
+
+    let start = timer::now();
+    let err = T::inflate_init(stream.as_mut_ptr(), inflate_config());
+    let init = start.elapsed();
+    if err != ReturnCode::Ok {
+        return Err(err);
+    }
+    let stream = unsafe { stream.assume_init_mut() };
+
+    T::set_in(stream, compressed);
+    T::set_out(stream, output);
+
+    let start = timer::now();
+    let err = T::inflate(stream, Flush::Finish);
+    let work = start.elapsed();
+    let len = T::total_out(stream);
+
+    let start = timer::now();
+    T::inflate_end(stream);
+    let end = start.elapsed();
+
+    match err {
+        ReturnCode::StreamEnd => Ok((len, (init, work, end))),
+        err => Err(err),
+    }
+}
+
+/// Repeat `once` for `BUDGET`, and return the median of every phase
+fn phases(mut once: impl FnMut() -> Result<Phases, ReturnCode>) -> Result<Phases, ReturnCode> {
+    let (mut init, mut work, mut end) = (Vec::new(), Vec::new(), Vec::new());
+
+    let start = std::time::Instant::now();
+    while another_run(init.len(), 0, Some(BUDGET), start.elapsed()) {
+        let (i, w, e) = once()?;
+        init.push(i);
+        work.push(w);
+        end.push(e);
+    }
+
+    Ok((median(init), median(work), median(end)))
+}
+
+/// The phases of deflate and of inflate of `payload`
+pub fn measure<T: ZlibImplementation>(
+    payload: &[u8],
+    level: i32,
+) -> Result<(Phases, Phases), String> {
+    let mut compressed = vec![0u8; 2 * payload.len() + 1024];
+    let mut decompressed = vec![0u8; payload.len()];
+
+    let (len, _) = deflate_once::<T>(payload, &mut compressed, level)
+        .map_err(|err| format!("deflate: {err:?}"))?;
+    compressed.truncate(len);
+
+    let (n, _) = inflate_once::<T>(&compressed, &mut decompressed)
+        .map_err(|err| format!("inflate: {err:?}"))?;
+    if decompressed[..n] != *payload {
+        return Err("roundtrip mismatch".to_string());
+    }
+
+    let mut output = vec![0u8; 2 * payload.len() + 1024];
+    let deflate = phases(|| Ok(deflate_once::<T>(payload, &mut output, level)?.1))
+        .map_err(|err| format!("deflate: {err:?}"))?;
+    let inflate = phases(|| Ok(inflate_once::<T>(&compressed, &mut decompressed)?.1))
+        .map_err(|err| format!("inflate: {err:?}"))?;
+
+    Ok((deflate, inflate))
+}
+
+type Measure = fn(&[u8], i32) -> Result<(Phases, Phases), String>;
+
+const BACKENDS: &[(&str, Capabilities, Measure)] = &[
+    #[cfg(feature = "og")]
+    ("og", crate::ZlibOg::CAPABILITIES, measure::<crate::ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", crate::ZlibNg::CAPABILITIES, measure::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", crate::ZlibRs::CAPABILITIES, measure::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    (
+        "cloudflare",
+        crate::ZlibCloudflare::CAPABILITIES,
+        measure::<crate::ZlibCloudflare>,
+    ),
+    (
+        "system",
+        crate::dylib::SystemZlib::CAPABILITIES,
+        measure::<crate::dylib::SystemZlib>,
+    ),
+];
+
+pub fn init_cost_all(path: &str, level: i32, sizes: &[usize]) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    println!("implementation, mode, payload bytes, init ns, work ns, end ns, init+end % of total, MB/s of the work, MB/s with init and end");
+    for (name, capabilities, measure) in BACKENDS {
+        if let Err(reason) = capabilities.available() {
+            println!("# {name}: skipped, {reason}");
+            continue;
+        }
+        for &size in sizes {
+            let payload = &input[..Ord::min(size, input.len())];
+
+            let (deflate, inflate) = match measure(payload, level) {
+                Ok(phases) => phases,
+                Err(e) => {
+                    println!("# {name} with {} bytes: failed, {e}", payload.len());
+                    continue;
+                }
+            };
+
+            for (mode, (init, work, end)) in [("deflate", deflate), ("inflate", inflate)] {
+                let total = init + work + end;
+                let setup = (init + end).as_secs_f64() / total.as_secs_f64();
+                let mb = payload.len() as f64 / 1e6;
+
+                println!(
+                    "{name}, {mode}, {}, {}, {}, {}, {:.1}, {:.2}, {:.2}",
+                    payload.len(),
+                    init.as_nanos(),
+                    work.as_nanos(),
+                    end.as_nanos(),
+                    100.0 * setup,
+                    mb / work.as_secs_f64(),
+                    mb / total.as_secs_f64(),
+                );
+            }
+        }
+    }
+}
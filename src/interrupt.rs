@@ -0,0 +1,50 @@
+//! Ctrl-C during a long run: stop measuring, and report what was measured so far.
+//!
+//! A matrix or a corpus can take an hour, and the default SIGINT throws all of it away. Instead, the
+//! handler only sets a flag. The loops check it before every call, stop, and print (and write to
+//! `--jsonl` and `--samples`) the results they have, marked as partial. The current call always
+//! finishes; a second Ctrl-C, e.g. when that call hangs, exits right away.
+//!
+//! Ctrl-C goes to the whole process group, so with `--isolate` the child that measures gets it too.
+//! The child ignores it, and the parent waits for the current implementation to finish.
+//!
+//! Only unix has the signals to do this with. Elsewhere Ctrl-C keeps its default action, and ends
+//! the run.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn handler(_signal: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+}
+
+/// Handle the first SIGINT by setting the flag that `interrupted` reads
+#[cfg(unix)]
+pub fn install() {
+    let mut action: libc::sigaction = unsafe { core::mem::zeroed() };
+    action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    // the default action is restored after the first signal, so a second one exits
+    action.sa_flags = libc::SA_RESETHAND;
+
+    let err = unsafe { libc::sigaction(libc::SIGINT, &action, core::ptr::null_mut()) };
+    assert_eq!(err, 0, "installing the SIGINT handler failed");
+}
+
+#[cfg(not(unix))]
+pub fn install() {}
+
+/// Ignore SIGINT, in the child processes of `--isolate`
+#[cfg(unix)]
+pub fn ignore() {
+    unsafe { libc::signal(libc::SIGINT, libc::SIG_IGN) };
+}
+
+#[cfg(not(unix))]
+pub fn ignore() {}
+
+/// Whether Ctrl-C was pressed, and the run should stop and report what it has
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
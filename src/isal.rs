@@ -0,0 +1,242 @@
+//! Intel ISA-L's igzip, loaded at runtime from `libisal.so`.
+//!
+//! ISA-L is a hand-tuned (assembly) deflate implementation that is popular for server-side
+//! compression. There is no sys crate for it here, so the shared library that distributions ship
+//! (`libisal2` on Debian) is loaded with `dlopen`; `ZLIB_BENCH_ISAL` can point to another build.
+//!
+//! Only the leading fields of ISA-L's stream structs are part of its documented API, the internal
+//! state that follows has a size that depends on how the library was built. So the structs are
+//! allocated with plenty of room, and only those leading fields are touched.
+
+use std::ffi::{c_int, c_void, CString};
+use std::sync::OnceLock;
+
+use crate::dylib::{dlopen, symbol};
+use crate::{Capabilities, DeflateConfig, DeflateImplementation, InflateConfig, ReturnCode};
+
+/// Larger than `struct isal_zstream` and `struct inflate_state` in any build
+const STATE_SIZE: usize = 1 << 20;
+
+/// Larger than ISA-L's largest level buffer (`ISAL_DEF_LVL3_EXTRA_LARGE`)
+const LEVEL_BUF_SIZE: usize = 8 << 20;
+
+/// `gzip_flag` values of `struct isal_zstream`
+const IGZIP_DEFLATE: u16 = 0;
+const IGZIP_GZIP: u16 = 1;
+const IGZIP_ZLIB: u16 = 3;
+
+/// Return values of `isal_inflate_stateless`
+const ISAL_DECOMP_OK: c_int = 0;
+const ISAL_OUT_OVERFLOW: c_int = 2;
+
+/// Return values of `isal_deflate_stateless`
+const COMP_OK: c_int = 0;
+const STATELESS_OVERFLOW: c_int = -1;
+
+/// The leading fields of `struct isal_zstream`
+#[repr(C)]
+struct ZStream {
+    next_in: *const u8,
+    avail_in: u32,
+    total_in: u32,
+    next_out: *mut u8,
+    avail_out: u32,
+    total_out: u32,
+    hufftables: *mut c_void,
+    level: u32,
+    level_buf_size: u32,
+    level_buf: *mut u8,
+    end_of_stream: u16,
+    flush: u16,
+    gzip_flag: u16,
+    hist_bits: u16,
+}
+
+/// The leading fields of `struct inflate_state`
+#[repr(C)]
+struct InflateState {
+    next_out: *mut u8,
+    avail_out: u32,
+    total_out: u32,
+    next_in: *const u8,
+    read_in: u64,
+    avail_in: u32,
+    read_in_length: i32,
+}
+
+struct Library {
+    deflate_stateless_init: unsafe extern "C" fn(*mut ZStream),
+    deflate_stateless: unsafe extern "C" fn(*mut ZStream) -> c_int,
+    inflate_init: unsafe extern "C" fn(*mut InflateState),
+    inflate_stateless: unsafe extern "C" fn(*mut InflateState) -> c_int,
+}
+
+/// The library that is loaded
+fn path() -> String {
+    std::env::var("ZLIB_BENCH_ISAL").unwrap_or_else(|_| "libisal.so.2".to_string())
+}
+
+fn open() -> Result<Library, String> {
+    let c_path = CString::new(path()).map_err(|e| e.to_string())?;
+    let handle = dlopen(&c_path)?;
+
+    unsafe {
+        Ok(Library {
+            deflate_stateless_init: symbol(handle, "isal_deflate_stateless_init")?,
+            deflate_stateless: symbol(handle, "isal_deflate_stateless")?,
+            inflate_init: symbol(handle, "isal_inflate_init")?,
+            inflate_stateless: symbol(handle, "isal_inflate_stateless")?,
+        })
+    }
+}
+
+fn library() -> &'static Library {
+    static LIBRARY: OnceLock<Library> = OnceLock::new();
+
+    LIBRARY.get_or_init(|| match open() {
+        Ok(library) => library,
+        Err(e) => panic!("isal: {e}"),
+    })
+}
+
+/// ISA-L has levels 0 to 3, none of which stores: level 0 is its fastest compression
+pub fn level(level: i32) -> Option<u32> {
+    match level {
+        0 | 1 => Some(level as u32),
+        2..=5 => Some(2),
+        // like zlib, -1 is the default level, which is 6
+        -1 | 6..=9 => Some(3),
+        _ => None,
+    }
+}
+
+/// A zeroed allocation of `STATE_SIZE` bytes, aligned for `T`
+fn state<T>() -> Vec<u64> {
+    assert!(core::mem::align_of::<T>() <= 8);
+    vec![0u64; STATE_SIZE / 8]
+}
+
+pub struct Isal;
+
+impl DeflateImplementation for Isal {
+    const NAME: &'static str = "isa-l";
+
+    // only the wrappers that are handled in `uncompress_slice` can be inflated
+    const CAPABILITIES: Capabilities = Capabilities {
+        // only the stateless functions are bound
+        streaming: false,
+        gzip: false,
+        dictionary: false,
+        strategies: false,
+        mem_level: false,
+        ..Capabilities::ZLIB
+    };
+
+    // ISA-L has no version function, so report the library instead
+    fn version() -> String {
+        match open() {
+            Ok(_) => path(),
+            Err(e) => format!("unavailable ({e})"),
+        }
+    }
+
+    // a prebuilt library, which does not report how it was built
+    fn build() -> String {
+        "system library".to_string()
+    }
+
+    fn uncompress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let library = library();
+
+        // `crc_flag`, which selects the wrapper, is not one of the leading fields of
+        // `struct inflate_state`. So ISA-L only inflates raw deflate, and the zlib header (without a
+        // preset dictionary) and adler32 trailer are handled here.
+        let raw = match config.window_bits {
+            ..=-1 => input,
+            // too short for the header and the trailer
+            8..=15 if input.len() < 6 => return (&mut [], ReturnCode::DataError),
+            8..=15 => {
+                let (cmf, flg) = (input[0], input[1]);
+                let valid = cmf & 0x0f == 8 && (u16::from(cmf) << 8 | u16::from(flg)) % 31 == 0;
+                if !valid || flg & 0x20 != 0 {
+                    return (&mut [], ReturnCode::DataError);
+                }
+                &input[2..input.len() - 4]
+            }
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+
+        let mut state = state::<InflateState>();
+        let state = state.as_mut_ptr().cast::<InflateState>();
+        let ret = unsafe {
+            (library.inflate_init)(state);
+            (*state).next_in = raw.as_ptr();
+            (*state).avail_in = raw.len() as u32;
+            (*state).next_out = output.as_mut_ptr();
+            (*state).avail_out = output.len() as u32;
+            (library.inflate_stateless)(state)
+        };
+        let len = unsafe { (*state).total_out } as usize;
+
+        match ret {
+            ISAL_DECOMP_OK => {}
+            ISAL_OUT_OVERFLOW => return (&mut [], ReturnCode::BufError),
+            _ => return (&mut [], ReturnCode::DataError),
+        }
+
+        let output = &mut output[..len];
+        if config.window_bits > 0 {
+            let trailer = &input[input.len() - 4..];
+            if adler::adler32_slice(output).to_be_bytes() != trailer {
+                return (&mut [], ReturnCode::DataError);
+            }
+        }
+
+        (output, ReturnCode::Ok)
+    }
+
+    fn compress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let library = library();
+
+        let Some(level) = level(config.level) else {
+            return (&mut [], ReturnCode::StreamError);
+        };
+        let gzip_flag = match config.window_bits {
+            ..=-1 => IGZIP_DEFLATE,
+            16.. => IGZIP_GZIP,
+            _ => IGZIP_ZLIB,
+        };
+
+        let mut level_buf = vec![0u8; LEVEL_BUF_SIZE];
+        let mut stream = state::<ZStream>();
+        let stream = stream.as_mut_ptr().cast::<ZStream>();
+        let ret = unsafe {
+            (library.deflate_stateless_init)(stream);
+            (*stream).next_in = input.as_ptr();
+            (*stream).avail_in = input.len() as u32;
+            (*stream).next_out = output.as_mut_ptr();
+            (*stream).avail_out = output.len() as u32;
+            (*stream).level = level;
+            (*stream).level_buf = level_buf.as_mut_ptr();
+            (*stream).level_buf_size = level_buf.len() as u32;
+            (*stream).end_of_stream = 1;
+            (*stream).gzip_flag = gzip_flag;
+            (library.deflate_stateless)(stream)
+        };
+        let len = unsafe { (*stream).total_out } as usize;
+
+        match ret {
+            COMP_OK => (&mut output[..len], ReturnCode::Ok),
+            STATELESS_OVERFLOW => (&mut [], ReturnCode::BufError),
+            _ => (&mut [], ReturnCode::StreamError),
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! `--isolate`: measure every implementation in a fresh process.
+//!
+//! In one process, the implementations share an allocator (with whatever the previous one left in
+//! its free lists), the page cache state of the input, and the global initialization of the C
+//! libraries (their cpu feature detection, their tables). One implementation can then make the
+//! next one faster or slower. With `--isolate`, this executable runs itself once per implementation
+//! with the hidden `measure` command, which runs the iterations of `run_all` for that implementation
+//! only and writes them to stdout, one line per field, for the parent to read back.
+//!
+//! With `--timeout`, a child that runs longer is killed, and the implementation counts as failed,
+//! so that one that hangs (or is pathologically slow on this input) does not stall the whole run.
+
+use std::io::Read;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::{
+    allocation, another_run, call, counters, dylib, helper, implementations, inflate_hash,
+    mem_level, og_local, set_chunks, set_inflate_hash, set_input, set_mem_level, set_strategy,
+    set_window_bits, strategy, timer, window_bits, Buffers, Call, Function, Measurement, Mode,
+    Options,
+};
+
+/// Run the iterations of `name` in a child process, with the settings of this one
+pub fn measure(
+    name: &str,
+    mode: Mode,
+    path: &str,
+    level: i32,
+    options: &Options,
+    n: usize,
+) -> Result<Measurement, String> {
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+
+    let mut command = Command::new(exe);
+    if timer::cycles() {
+        command.args(["--timer", "cycles"]);
+    }
+    command
+        .args(["measure", mode.name(), &level.to_string(), name, path])
+        .arg(n.to_string())
+        .args(["--window-bits", &window_bits().to_string()])
+        .args(["--strategy", strategy().name()])
+        .args(["--mem-level", &mem_level().to_string()])
+        .args(["--inflate-hash", &inflate_hash().to_string()]);
+    if let Some(guard) = options.guard {
+        command.args(["--guard", guard.name()]);
+    }
+    if let Some(max_time) = options.max_time {
+        command.args(["--max-time", &max_time.as_secs_f64().to_string()]);
+    }
+    if let Some(chunk_in) = options.chunk_in {
+        command.args(["--chunk-in", &chunk_in.to_string()]);
+    }
+    if let Some(chunk_out) = options.chunk_out {
+        command.args(["--chunk-out", &chunk_out.to_string()]);
+    }
+    if let Some(bytes) = options.bytes {
+        command.args(["--bytes", &bytes.to_string()]);
+    }
+    if let Some(target_size) = options.target_size {
+        command.args(["--target-size", &target_size.to_string()]);
+    }
+
+    let output = match options.timeout {
+        None => command.output(),
+        Some(timeout) => run(command, timeout)?,
+    };
+    let output = output.map_err(|e| format!("error running {name}: {e}"))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let last = stderr.lines().last().unwrap_or("").trim();
+        return Err(format!("exited with {} ({last})", output.status));
+    }
+
+    parse(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// How often a child with a timeout is checked on
+const POLL: Duration = Duration::from_millis(10);
+
+/// Like `Command::output`, but kill the child once it has run for `timeout`
+fn run(mut command: Command, timeout: Duration) -> Result<std::io::Result<Output>, String> {
+    let mut child = match command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => return Ok(Err(e)),
+    };
+    let start = Instant::now();
+
+    // read the pipes while waiting, a child that fills one would block until it is killed
+    let stdout = drain(child.stdout.take());
+    let stderr = drain(child.stderr.take());
+
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if start.elapsed() < timeout => std::thread::sleep(POLL),
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("timed out after {timeout:?}"));
+            }
+            Err(e) => return Ok(Err(e)),
+        }
+    };
+
+    Ok(Ok(Output {
+        status,
+        stdout: stdout.join().unwrap(),
+        stderr: stderr.join().unwrap(),
+    }))
+}
+
+fn drain(pipe: Option<impl Read + Send + 'static>) -> std::thread::JoinHandle<Vec<u8>> {
+    let mut pipe = pipe.unwrap();
+    std::thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
+/// The values of the line that starts with `key`
+fn values<'a>(stdout: &'a str, key: &str) -> Result<Vec<&'a str>, String> {
+    let line = stdout.lines().find_map(|line| {
+        let (k, values) = line.split_once(' ').unwrap_or((line, ""));
+        (k == key).then_some(values)
+    });
+
+    match line {
+        Some(values) => Ok(values.split_whitespace().collect()),
+        None => Err(format!("no {key} in the output of the child process")),
+    }
+}
+
+fn numbers<T: std::str::FromStr>(stdout: &str, key: &str) -> Result<Vec<T>, String> {
+    let values = values(stdout, key)?;
+    let numbers = values.iter().map(|value| value.parse());
+    numbers
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("invalid {key} in the output of the child process"))
+}
+
+fn parse(stdout: &str) -> Result<Measurement, String> {
+    let nanoseconds: Vec<u64> = numbers(stdout, "nanoseconds")?;
+    let cycles: Vec<u64> = numbers(stdout, "cycles")?;
+    let instructions: Vec<u64> = numbers(stdout, "instructions")?;
+    let faults: Vec<u64> = numbers(stdout, "faults")?;
+    let output_len: Vec<usize> = numbers(stdout, "output_bytes")?;
+
+    let (&[minor, major], &[output_len]) = (&faults[..], &output_len[..]) else {
+        return Err("invalid output of the child process".to_string());
+    };
+
+    let samples = cycles.iter().zip(&instructions);
+    Ok(Measurement {
+        deltas: (nanoseconds.into_iter())
+            .map(std::time::Duration::from_nanos)
+            .collect(),
+        samples: samples
+            .map(|(&cycles, &instructions)| counters::Sample {
+                cycles,
+                instructions,
+            })
+            .collect(),
+        output_len,
+        faults: (minor, major),
+        clocks: numbers(stdout, "clocks")?,
+    })
+}
+
+/// The `measure` command, in the child process: `measure <mode> <level> <implementation> <path>
+/// <iterations> [options]`
+pub fn child(mut it: impl Iterator<Item = String>) {
+    // the parent decides when to stop, see `interrupt`
+    crate::interrupt::ignore();
+
+    let mode = match it.next().unwrap().as_str() {
+        "inflate" => Mode::Inflate,
+        "deflate" => Mode::Deflate,
+        other => panic!("invalid mode {other:?}"),
+    };
+    let level = it.next().unwrap().parse().unwrap();
+    let name = it.next().unwrap();
+    let path = it.next().unwrap();
+    let n = it.next().unwrap().parse().unwrap();
+    let options = Options::parse(it);
+    set_window_bits(options.window_bits.unwrap_or(15));
+    set_chunks(options.chunk_in, options.chunk_out);
+    set_input(options.bytes, options.target_size);
+    if let Some(hash) = options.inflate_hash {
+        set_inflate_hash(hash);
+    }
+    if let Some(&[strategy]) = options.strategies {
+        set_strategy(strategy);
+    }
+    if let Some(&[mem_level]) = options.mem_levels {
+        set_mem_level(mem_level);
+    }
+
+    let (f, call): (Function, Call) = match name.as_str() {
+        "og-local" => match og_local::load() {
+            Ok(library) => {
+                dylib::select(library);
+                (helper::<dylib::ZlibDylib>, call::<dylib::ZlibDylib>)
+            }
+            Err(e) => panic!("og-local: {e}"),
+        },
+        name => match implementations().find(|(n, _, _, _, _, _)| *n == name) {
+            Some((_, f, call, _, _, _)) => (f, call),
+            None => panic!("invalid implementation: {name:?}"),
+        },
+    };
+
+    let counters = counters::Counters::open();
+    let allocation = allocation(&options, &name, f, mode, &path, level);
+
+    let mut buffers = Buffers::read(&path);
+
+    let mut m = Measurement::default();
+    while another_run(m.deltas.len(), n, options.max_time, m.deltas.iter().sum()) {
+        m.iteration(call, mode, level, &mut buffers, allocation, &counters);
+    }
+
+    line("nanoseconds", m.deltas.iter().map(|d| d.as_nanos()));
+    line("cycles", m.samples.iter().map(|s| s.cycles));
+    line("instructions", m.samples.iter().map(|s| s.instructions));
+    line("output_bytes", [m.output_len]);
+    line("faults", [m.faults.0, m.faults.1]);
+    line("clocks", m.clocks);
+}
+
+/// A line of the output of `child`, read back by `values`
+fn line<T: std::fmt::Display>(key: &str, values: impl IntoIterator<Item = T>) {
+    let values: Vec<_> = values.into_iter().map(|value| value.to_string()).collect();
+    println!("{key} {}", values.join(" "));
+}
@@ -0,0 +1,193 @@
+//! Streaming results as JSON lines.
+//!
+//! A long run writes one line per completed cell (and a line at the start and the end), flushed
+//! immediately, so that the output can be tailed and ingested while the run is in progress, and
+//! the completed cells survive if the run dies halfway.
+
+use std::fmt::Write as _;
+use std::io::Write;
+
+/// One JSON object, built up field by field
+pub struct Line(String);
+
+/// `s` as a JSON string, including the quotes
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// The value of `key` in a line written by `Events`: strings without their quotes and unescaped,
+/// anything else as it was written
+///
+/// This is not a JSON parser: it only understands the flat objects that `Line` produces.
+pub fn field(line: &str, key: &str) -> Option<String> {
+    let start = line.find(&format!("{}:", escape(key)))? + key.len() + 3;
+    let rest = &line[start..];
+
+    let end = match rest.as_bytes().first()? {
+        b'"' => {
+            let mut escaped = false;
+            let end = rest[1..].find(|c| {
+                let done = c == '"' && !escaped;
+                escaped = c == '\\' && !escaped;
+                done
+            })?;
+            return unescape(&rest[1..end + 1]);
+        }
+        b'[' => rest.find(']')? + 1,
+        _ => rest.find([',', '}']).unwrap_or(rest.len()),
+    };
+
+    Some(rest[..end].to_string())
+}
+
+/// The inverse of `escape`, for a string without its quotes
+fn unescape(s: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next()? {
+            'n' => unescaped.push('\n'),
+            'u' => {
+                let code: String = chars.by_ref().take(4).collect();
+                unescaped.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+            }
+            // `"` and `\`
+            c => unescaped.push(c),
+        }
+    }
+
+    Some(unescaped)
+}
+
+impl Line {
+    pub fn new(event: &str) -> Self {
+        Line(format!("{{\"event\":{}", escape(event)))
+    }
+
+    fn key(mut self, key: &str) -> Self {
+        write!(self.0, ",{}:", escape(key)).unwrap();
+        self
+    }
+
+    pub fn str(self, key: &str, value: &str) -> Self {
+        let mut line = self.key(key);
+        line.0.push_str(&escape(value));
+        line
+    }
+
+    pub fn int(self, key: &str, value: impl Into<i128>) -> Self {
+        let mut line = self.key(key);
+        write!(line.0, "{}", value.into()).unwrap();
+        line
+    }
+
+    /// Non-finite values are not valid JSON, and are written as `null`
+    pub fn float(self, key: &str, value: f64) -> Self {
+        let mut line = self.key(key);
+        match value.is_finite() {
+            true => write!(line.0, "{value}").unwrap(),
+            false => line.0.push_str("null"),
+        }
+        line
+    }
+
+    pub fn ints(self, key: &str, values: impl IntoIterator<Item = impl Into<i128>>) -> Self {
+        let mut line = self.key(key);
+        let values: Vec<_> = values.into_iter().map(|v| v.into().to_string()).collect();
+        write!(line.0, "[{}]", values.join(",")).unwrap();
+        line
+    }
+}
+
+pub struct Events(Box<dyn Write>);
+
+impl Events {
+    /// Write to the file at `path`, or to stdout for `-`. The file is truncated by the first run of
+    /// a command, and appended to by the others (see `open_output`).
+    pub fn create(path: &str) -> std::io::Result<Self> {
+        let writer: Box<dyn Write> = match path {
+            "-" => Box::new(std::io::stdout()),
+            path => Box::new(crate::open_output(path)?.0),
+        };
+
+        Ok(Events(writer))
+    }
+
+    pub fn emit(&mut self, line: Line) {
+        writeln!(self.0, "{}}}", line.0).unwrap();
+        self.0.flush().unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_strings() {
+        assert_eq!(escape("zlib-rs"), r#""zlib-rs""#);
+        assert_eq!(escape(r#"a "b" c"#), r#""a \"b\" c""#);
+        assert_eq!(escape(r"C:\corpus"), r#""C:\\corpus""#);
+        assert_eq!(escape("a\nb"), r#""a\nb""#);
+        assert_eq!(escape("\t\u{1}"), r#""\u0009\u0001""#);
+        assert_eq!(escape("é"), "\"é\"");
+    }
+
+    #[test]
+    fn fields() {
+        let line = Line::new("cell")
+            .str("implementation", "zlib-rs")
+            .int("level", -1)
+            .float("mb_per_s", 123.5)
+            .float("ratio", f64::NAN)
+            .ints("deltas", [1u64, 2, 3])
+            .0
+            + "}";
+
+        assert_eq!(field(&line, "event"), Some("cell".into()));
+        assert_eq!(field(&line, "implementation"), Some("zlib-rs".into()));
+        assert_eq!(field(&line, "level"), Some("-1".into()));
+        assert_eq!(field(&line, "mb_per_s"), Some("123.5".into()));
+        assert_eq!(field(&line, "ratio"), Some("null".into()));
+        assert_eq!(field(&line, "deltas"), Some("[1,2,3]".into()));
+        assert_eq!(field(&line, "path"), None);
+    }
+
+    #[test]
+    fn field_after_a_string_with_quotes() {
+        let line = Line::new("start").str("path", r#"a "b", c"#).int("n", 1).0 + "}";
+        assert_eq!(field(&line, "n"), Some("1".into()));
+    }
+
+    #[test]
+    fn string_roundtrip() {
+        for value in [
+            "",
+            r#"a "b", c"#,
+            r"C:\corpus\",
+            "a\nb",
+            "\t\u{1f}",
+            "é \\\"",
+        ] {
+            let line = Line::new("start").str("path", value).0 + "}";
+            assert_eq!(field(&line, "path").as_deref(), Some(value));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! A closer look at level 1.
+//!
+//! Level 1 is where implementations differ the most: zlib-ng uses `deflate_quick` (static huffman
+//! trees, a single hash probe, no lazy matching), and zlib-rs has its own fast path. The deflate
+//! stream that each of them produces shows what they trade away for speed: how many (and how long)
+//! matches they find, and how much of the output ends up in stored or fixed-huffman blocks. Small
+//! buffers are common at this level, so we also measure throughput at several buffer sizes.
+
+use crate::analyze::{self, BUCKETS};
+use crate::{DeflateConfig, DeflateImplementation, Method, ReturnCode, Strategy};
+
+/// Every chunk of the input is compressed independently
+const CHUNK_SIZES: [usize; 4] = [4 << 10, 64 << 10, 1 << 20, usize::MAX];
+
+/// Compress every chunk of `input` independently as raw deflate, and return the last output
+fn compress<T: DeflateImplementation>(input: &[u8], level: i32, chunk_size: usize) -> Vec<u8> {
+    let config = DeflateConfig {
+        level,
+        method: Method::Deflated,
+        window_bits: -15,
+        mem_level: 8,
+        strategy: Strategy::Default,
+    };
+
+    let mut output = vec![0u8; 2 * Ord::min(chunk_size, input.len()) + 1024];
+    let mut len = 0;
+    for chunk in input.chunks(chunk_size) {
+        let (compressed, err) = T::compress_slice(&mut output, chunk, config);
+        assert_eq!(err, ReturnCode::Ok);
+        len = compressed.len();
+    }
+
+    output.truncate(len);
+    output
+}
+
+type Compress = fn(&[u8], i32, usize) -> Vec<u8>;
+
+const BACKENDS: &[(&str, Compress)] = &[
+    #[cfg(feature = "og")]
+    ("og", compress::<crate::ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", compress::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", compress::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    ("cloudflare", compress::<crate::ZlibCloudflare>),
+    #[cfg(feature = "miniz")]
+    ("miniz", compress::<crate::MinizOxide>),
+    #[cfg(feature = "libdeflate")]
+    ("libdeflate", compress::<crate::Libdeflate>),
+    #[cfg(feature = "miniz-c")]
+    ("miniz-c", compress::<crate::MinizC>),
+];
+
+pub fn level1_all(path: &str, level: i32) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    let mb = input.len() as f64 / 1_000_000.0;
+
+    println!("implementation, compressed bytes, stored blocks, fixed blocks, dynamic blocks, % bytes stored, literals, matches, mean match length, matches {}", BUCKETS.join(", matches "));
+    for &(name, compress) in BACKENDS {
+        let compressed = compress(&input, level, usize::MAX);
+
+        let stats = match analyze::analyze(&compressed) {
+            Ok(stats) => stats,
+            Err(e) => {
+                // the other columns are left empty, so that the row still lines up with the header
+                let empty = vec!["-"; 7 + BUCKETS.len()];
+                println!("{name}, FAILED ({e}), {}", empty.join(", "));
+                continue;
+            }
+        };
+        assert_eq!(
+            stats.total_bytes(),
+            input.len() as u64,
+            "{name}: wrong size"
+        );
+
+        let lengths: Vec<_> = stats.lengths.iter().map(|n| n.to_string()).collect();
+        // an all-literal (or empty) output has no matches to average
+        let mean_length = match stats.matches {
+            0 => "-".to_string(),
+            n => format!("{:.2}", stats.match_bytes as f64 / n as f64),
+        };
+        println!(
+            "{name}, {}, {}, {}, {}, {:.2}, {}, {}, {mean_length}, {}",
+            compressed.len(),
+            stats.stored_blocks,
+            stats.fixed_blocks,
+            stats.dynamic_blocks,
+            100.0 * stats.stored_bytes as f64 / input.len() as f64,
+            stats.literals,
+            stats.matches,
+            lengths.join(", "),
+        );
+    }
+
+    println!();
+    println!("implementation, chunk size, MB/s");
+    for &(name, compress) in BACKENDS {
+        for chunk_size in CHUNK_SIZES {
+            let start = std::time::Instant::now();
+            compress(&input, level, chunk_size);
+            let elapsed = start.elapsed();
+
+            let chunk_size = match chunk_size {
+                usize::MAX => "whole file".to_string(),
+                n => n.to_string(),
+            };
+
+            println!("{name}, {chunk_size}, {}", mb / elapsed.as_secs_f64());
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! How do the implementations treat `Z_DEFAULT_COMPRESSION` (-1) and other out-of-range levels?
+//!
+//! zlib maps -1 to level 6 and rejects any other level outside of 0..=9, but forks are free to remap levels
+//! (zlib-ng's levels are not zlib's levels), and miniz-oxide treats any negative level as default.
+//! For every level we report the return code, the output size, and which level in 0..=9 produces
+//! byte-identical output.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::{DeflateConfig, DeflateImplementation, Method, ReturnCode, Strategy};
+
+/// Returns the return code, and the size and hash of the output
+fn compress<T: DeflateImplementation>(input: &[u8], level: i32) -> (ReturnCode, usize, u64) {
+    let config = DeflateConfig {
+        level,
+        method: Method::Deflated,
+        window_bits: 15,
+        mem_level: 8,
+        strategy: Strategy::Default,
+    };
+
+    let mut output = vec![0u8; 2 * input.len() + 1024];
+    let (output, err) = T::compress_slice(&mut output, input, config);
+
+    let mut hasher = DefaultHasher::new();
+    output.hash(&mut hasher);
+
+    (err, output.len(), hasher.finish())
+}
+
+type Compress = fn(&[u8], i32) -> (ReturnCode, usize, u64);
+
+const BACKENDS: &[(&str, Compress)] = &[
+    #[cfg(feature = "og")]
+    ("og", compress::<crate::ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", compress::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", compress::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    ("cloudflare", compress::<crate::ZlibCloudflare>),
+    #[cfg(feature = "miniz")]
+    ("miniz", compress::<crate::MinizOxide>),
+    #[cfg(feature = "libdeflate")]
+    ("libdeflate", compress::<crate::Libdeflate>),
+    #[cfg(feature = "miniz-c")]
+    ("miniz-c", compress::<crate::MinizC>),
+];
+
+pub fn levels_all(path: &str, levels: &[i32]) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    println!("implementation, level, return code, output bytes, same output as level");
+    for (name, compress) in BACKENDS {
+        let reference: Vec<_> = (0..=9).map(|level| compress(&input, level)).collect();
+
+        for &level in levels {
+            let (err, len, hash) = compress(&input, level);
+
+            let same_as = match err {
+                ReturnCode::Ok => reference
+                    .iter()
+                    .position(|(err, _, h)| *err == ReturnCode::Ok && *h == hash)
+                    .map_or("none".to_string(), |l| l.to_string()),
+                _ => "-".to_string(),
+            };
+
+            println!("{name}, {level}, {err:?}, {len}, {same_as}");
+        }
+    }
+}
@@ -0,0 +1,4947 @@
+use core::ffi::c_void;
+use core::mem::MaybeUninit;
+use std::hash::{DefaultHasher, Hash};
+use std::sync::atomic::{AtomicI32, AtomicU64, AtomicUsize, Ordering};
+
+use guard::{Allocation, Buffer, Guard};
+use memory::{Alloc, Free};
+
+mod ab;
+#[cfg(any(
+    feature = "og",
+    feature = "ng",
+    feature = "rs",
+    feature = "cloudflare",
+    feature = "miniz",
+    feature = "libdeflate",
+    feature = "miniz-c"
+))]
+mod alignment;
+#[cfg(any(
+    feature = "og",
+    feature = "ng",
+    feature = "rs",
+    feature = "cloudflare",
+    feature = "miniz",
+    feature = "libdeflate",
+    feature = "miniz-c"
+))]
+mod analyze;
+#[cfg(feature = "async")]
+mod asynchronous;
+#[cfg(feature = "og")]
+mod bgzf;
+mod chromium;
+#[cfg(feature = "og")]
+mod chunks;
+mod constrained;
+mod corpus;
+mod counters;
+mod crossover;
+#[cfg(any(
+    feature = "og",
+    feature = "ng",
+    feature = "rs",
+    feature = "cloudflare",
+    feature = "miniz",
+    feature = "libdeflate",
+    feature = "miniz-c"
+))]
+mod decoders;
+#[cfg(any(feature = "og", feature = "ng", feature = "rs", feature = "cloudflare"))]
+mod dictionary;
+mod dylib;
+mod environment;
+mod explorer;
+mod generate;
+mod guard;
+#[cfg(any(feature = "og", feature = "ng", feature = "rs", feature = "cloudflare"))]
+mod gzip;
+#[cfg(any(feature = "og", feature = "ng", feature = "rs", feature = "cloudflare"))]
+mod index;
+mod init_cost;
+#[cfg(any(feature = "og", feature = "ng"))]
+mod linking;
+mod interrupt;
+mod isal;
+mod isolate;
+mod jsonl;
+#[cfg(any(
+    feature = "og",
+    feature = "ng",
+    feature = "rs",
+    feature = "cloudflare",
+    feature = "miniz",
+    feature = "libdeflate",
+    feature = "miniz-c"
+))]
+mod level1;
+#[cfg(any(
+    feature = "og",
+    feature = "ng",
+    feature = "rs",
+    feature = "cloudflare",
+    feature = "miniz",
+    feature = "libdeflate",
+    feature = "miniz-c"
+))]
+mod levels;
+#[cfg(target_os = "macos")]
+mod libcompression;
+mod matrix;
+mod memcheck;
+mod memory;
+mod messages;
+mod ng_versions;
+mod og_local;
+mod one_byte;
+mod orchestrate;
+#[cfg(feature = "gzp")]
+mod parallel;
+mod profile;
+mod progress;
+mod prometheus;
+mod qemu;
+#[cfg(feature = "zopfli")]
+mod ratio;
+#[cfg(feature = "og")]
+mod records;
+pub mod registry;
+mod rng;
+mod roundtrip;
+#[cfg(feature = "rs")]
+mod rs_features;
+mod sandbox;
+mod scaling;
+mod serve;
+mod size;
+mod stats;
+mod timer;
+mod tools;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "rs")]
+mod watch;
+
+// cargo does not run the tests of a build script, so they run with the tests of the library
+#[cfg(test)]
+#[path = "../build.rs"]
+#[allow(dead_code)]
+mod build_script;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(i32)]
+pub enum ReturnCode {
+    Ok = 0,
+    StreamEnd = 1,
+    NeedDict = 2,
+    ErrNo = -1,
+    StreamError = -2,
+    DataError = -3,
+    MemError = -4,
+    BufError = -5,
+    VersionError = -6,
+}
+
+impl From<i32> for ReturnCode {
+    fn from(value: i32) -> Self {
+        use ReturnCode::*;
+
+        match value {
+            0 => Ok,
+            1 => StreamEnd,
+            2 => NeedDict,
+            -1 => ErrNo,
+            -2 => StreamError,
+            -3 => DataError,
+            -4 => MemError,
+            -5 => BufError,
+            -6 => VersionError,
+            _ => panic!("invalid return code {value}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct InflateConfig {
+    pub window_bits: i32,
+}
+
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Method {
+    #[default]
+    Deflated = 8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum Strategy {
+    #[default]
+    Default = 0,
+    Filtered = 1,
+    HuffmanOnly = 2,
+    Rle = 3,
+    Fixed = 4,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 5] = [
+        Strategy::Default,
+        Strategy::Filtered,
+        Strategy::HuffmanOnly,
+        Strategy::Rle,
+        Strategy::Fixed,
+    ];
+
+    /// The name on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Strategy::Default => "default",
+            Strategy::Filtered => "filtered",
+            Strategy::HuffmanOnly => "huffman-only",
+            Strategy::Rle => "rle",
+            Strategy::Fixed => "fixed",
+        }
+    }
+
+    /// The strategies of `--strategy <name>`, or all of them for `--strategy all`
+    fn parse(name: &str) -> &'static [Strategy] {
+        const ALL: &[Strategy] = &Strategy::ALL;
+
+        match ALL.iter().position(|strategy| strategy.name() == name) {
+            Some(i) => &ALL[i..=i],
+            None if name == "all" => ALL,
+            None => panic!("invalid strategy {name:?}"),
+        }
+    }
+}
+
+/// The strategy that `helper` deflates with, set with `--strategy`
+static STRATEGY: AtomicI32 = AtomicI32::new(Strategy::Default as i32);
+
+fn set_strategy(strategy: Strategy) {
+    STRATEGY.store(strategy as i32, Ordering::Relaxed);
+}
+
+fn strategy() -> Strategy {
+    Strategy::ALL[STRATEGY.load(Ordering::Relaxed) as usize]
+}
+
+/// The window bits that `helper` uses, set with `--window-bits`: negative for raw deflate, plus 16
+/// for the gzip wrapper
+static WINDOW_BITS: AtomicI32 = AtomicI32::new(15);
+
+fn set_window_bits(window_bits: i32) {
+    WINDOW_BITS.store(window_bits, Ordering::Relaxed);
+}
+
+fn window_bits() -> i32 {
+    WINDOW_BITS.load(Ordering::Relaxed)
+}
+
+/// The memory levels of `--mem-level all`
+const MEM_LEVELS: [i32; 9] = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+
+/// The memory levels of `--mem-level <n>`, or all of them for `--mem-level all`
+fn parse_mem_levels(mem_level: &str) -> &'static [i32] {
+    match mem_level {
+        "all" => &MEM_LEVELS,
+        n => match n.parse::<usize>() {
+            Ok(n @ 1..=9) => &MEM_LEVELS[n - 1..n],
+            _ => panic!("invalid memory level {n:?}, it is 1 to 9 or all"),
+        },
+    }
+}
+
+/// The memory level that `helper` deflates with, set with `--mem-level`
+static MEM_LEVEL: AtomicI32 = AtomicI32::new(8);
+
+fn set_mem_level(mem_level: i32) {
+    MEM_LEVEL.store(mem_level, Ordering::Relaxed);
+}
+
+fn mem_level() -> i32 {
+    MEM_LEVEL.load(Ordering::Relaxed)
+}
+
+/// The hash of the output that `helper` checks every inflate against: by default that of the
+/// reference input, in a corpus that of every file in turn
+static INFLATE_HASH: AtomicU64 = AtomicU64::new(15127115900574662295);
+
+fn set_inflate_hash(hash: u64) {
+    INFLATE_HASH.store(hash, Ordering::Relaxed);
+}
+
+fn inflate_hash() -> u64 {
+    INFLATE_HASH.load(Ordering::Relaxed)
+}
+
+fn output_hash(output: &[u8]) -> u64 {
+    use std::hash::Hasher;
+
+    let mut hasher = DefaultHasher::new();
+    output.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The pieces that `helper` feeds the input and drains the output in, set with `--chunk-in` and
+/// `--chunk-out`: at most this many bytes per call, 0 for all of it at once
+static CHUNK_IN: AtomicUsize = AtomicUsize::new(0);
+static CHUNK_OUT: AtomicUsize = AtomicUsize::new(0);
+
+/// With `--bytes`, only this many bytes of the start of the input are used, 0 for all of it
+static INPUT_BYTES: AtomicUsize = AtomicUsize::new(0);
+/// With `--target-size`, the input is repeated up to this many bytes, 0 to use it once
+static TARGET_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+fn set_input(bytes: Option<usize>, target_size: Option<usize>) {
+    INPUT_BYTES.store(bytes.unwrap_or(0), Ordering::Relaxed);
+    TARGET_SIZE.store(target_size.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// A compressed stream that is cut short or repeated does not inflate
+const INFLATE_INPUT: &str = "--bytes and --target-size only apply to deflate, a compressed input that is cut short or repeated is not a valid stream";
+
+/// The input file, or with `--bytes` its start, without reading the rest, and with `--target-size`
+/// repeated until it is that long (the last copy is cut short)
+fn read_input(path: &str) -> std::io::Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut input = match INPUT_BYTES.load(Ordering::Relaxed) {
+        0 => std::fs::read(path)?,
+        bytes => {
+            let mut input = Vec::with_capacity(bytes);
+            let file = std::fs::File::open(path)?;
+            file.take(bytes as u64).read_to_end(&mut input)?;
+            input
+        }
+    };
+
+    let target_size = TARGET_SIZE.load(Ordering::Relaxed);
+    if !input.is_empty() && input.len() < target_size {
+        let copy = input.len();
+        input.reserve_exact(target_size - copy);
+        while input.len() < target_size {
+            let n = Ord::min(copy, target_size - input.len());
+            input.extend_from_within(..n);
+        }
+    }
+
+    Ok(input)
+}
+
+/// The size of what `read_input` returns
+fn input_len(path: &str) -> u64 {
+    let len = std::fs::metadata(path).unwrap().len();
+    let len = match INPUT_BYTES.load(Ordering::Relaxed) {
+        0 => len,
+        bytes => Ord::min(len, bytes as u64),
+    };
+    match TARGET_SIZE.load(Ordering::Relaxed) as u64 {
+        target_size if len > 0 => Ord::max(len, target_size),
+        _ => len,
+    }
+}
+
+/// The size of the pieces of input and of output that are passed per call
+#[derive(Debug, Clone, Copy)]
+struct Chunks {
+    input: usize,
+    output: usize,
+}
+
+fn set_chunks(chunk_in: Option<usize>, chunk_out: Option<usize>) {
+    CHUNK_IN.store(chunk_in.unwrap_or(0), Ordering::Relaxed);
+    CHUNK_OUT.store(chunk_out.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// `None` when both the input and the output are passed all at once
+fn chunks() -> Option<Chunks> {
+    let (input, output) = (
+        CHUNK_IN.load(Ordering::Relaxed),
+        CHUNK_OUT.load(Ordering::Relaxed),
+    );
+    if input == 0 && output == 0 {
+        return None;
+    }
+
+    // the other side as a whole, as far as the `c_uint` lengths of the stream allow
+    let whole = |chunk| match chunk {
+        0 => core::ffi::c_uint::MAX as usize,
+        chunk => chunk,
+    };
+    Some(Chunks {
+        input: whole(input),
+        output: whole(output),
+    })
+}
+
+/// Every combination of `strategies` and `mem_levels`
+fn deflate_settings(strategies: &[Strategy], mem_levels: &[i32]) -> Vec<(Strategy, i32)> {
+    let settings = strategies
+        .iter()
+        .flat_map(|&s| mem_levels.iter().map(move |&m| (s, m)));
+    settings.collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DeflateConfig {
+    pub level: i32,
+    pub method: Method,
+    pub window_bits: i32,
+    pub mem_level: i32,
+    pub strategy: Strategy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Flush {
+    #[default]
+    NoFlush = 0,
+    PartialFlush = 1,
+    SyncFlush = 2,
+    FullFlush = 3,
+    Finish = 4,
+    Block = 5,
+    Trees = 6,
+}
+
+/// Mirrors `gz_header` in zlib.h, which has the same layout in all of the C-compatible backends
+#[repr(C)]
+#[derive(Debug)]
+pub struct GzHeader {
+    pub text: core::ffi::c_int,
+    pub time: core::ffi::c_ulong,
+    pub xflags: core::ffi::c_int,
+    pub os: core::ffi::c_int,
+    pub extra: *mut u8,
+    pub extra_len: core::ffi::c_uint,
+    pub extra_max: core::ffi::c_uint,
+    pub name: *mut u8,
+    pub name_max: core::ffi::c_uint,
+    pub comment: *mut u8,
+    pub comm_max: core::ffi::c_uint,
+    pub hcrc: core::ffi::c_int,
+    pub done: core::ffi::c_int,
+}
+
+/// What a backend supports, so that the runner can skip a configuration up front instead of
+/// failing somewhere inside the backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// compresses (a few backends only decompress)
+    pub deflate: bool,
+    /// decompresses (a few backends only compress)
+    pub inflate: bool,
+    /// the highest compression level, all levels from 0 up to it are accepted, and so is -1
+    pub max_level: i32,
+    /// raw deflate, negative window bits
+    pub raw: bool,
+    /// the zlib wrapper, window bits 8 to 15
+    pub zlib: bool,
+    /// the gzip wrapper, 16 added to the window bits
+    pub gzip: bool,
+    /// the smallest window size, in bits (without the sign of raw deflate or the 16 of gzip)
+    pub min_window_bits: i32,
+    /// the largest window size, in bits
+    pub max_window_bits: i32,
+    /// a preset dictionary
+    pub dictionary: bool,
+    /// the library can be fed its input and output in pieces, rather than all at once
+    pub streaming: bool,
+    /// the strategies other than the default one: filtered, huffman only, rle and fixed
+    pub strategies: bool,
+    /// memory levels other than the default, 8
+    pub mem_level: bool,
+    /// the backend calls into the system zlib, which is loaded at run time and can be missing
+    pub system_zlib: bool,
+}
+
+impl Capabilities {
+    /// Everything that zlib itself supports
+    pub const ZLIB: Self = Self {
+        deflate: true,
+        inflate: true,
+        max_level: 9,
+        raw: true,
+        zlib: true,
+        gzip: true,
+        min_window_bits: 8,
+        max_window_bits: 15,
+        dictionary: true,
+        streaming: true,
+        strategies: true,
+        mem_level: true,
+        system_zlib: false,
+    };
+
+    /// Whether the library can be loaded at all
+    fn available(&self) -> Result<(), String> {
+        match self.system_zlib {
+            true => dylib::system()
+                .map(|_| ())
+                .map_err(|e| format!("unavailable ({e})")),
+            false => Ok(()),
+        }
+    }
+
+    fn wrapper(&self, window_bits: i32) -> Result<(), String> {
+        let (name, supported) = match window_bits {
+            -15..=-8 => ("raw deflate", self.raw),
+            8..=15 => ("the zlib wrapper", self.zlib),
+            24..=31 => ("the gzip wrapper", self.gzip),
+            _ => return Err(format!("window bits {window_bits} are invalid")),
+        };
+
+        if !supported {
+            return Err(format!("{name} is unsupported"));
+        }
+
+        let bits = match window_bits {
+            ..=-1 => -window_bits,
+            24.. => window_bits - 16,
+            _ => window_bits,
+        };
+        match (self.min_window_bits..=self.max_window_bits).contains(&bits) {
+            true => Ok(()),
+            false => Err(format!("window bits {window_bits} are unsupported")),
+        }
+    }
+
+    pub fn check_inflate(&self, config: InflateConfig) -> Result<(), String> {
+        if !self.inflate {
+            return Err("inflate is unsupported".to_string());
+        }
+
+        self.wrapper(config.window_bits)
+    }
+
+    pub fn check_deflate(&self, config: DeflateConfig) -> Result<(), String> {
+        if !self.deflate {
+            return Err("deflate is unsupported".to_string());
+        }
+        if !(config.level == -1 || (0..=self.max_level).contains(&config.level)) {
+            return Err(format!(
+                "level {} is unsupported, the highest level is {}",
+                config.level, self.max_level
+            ));
+        }
+        if config.strategy != Strategy::Default && !self.strategies {
+            return Err(format!(
+                "strategy {} is unsupported",
+                config.strategy.name()
+            ));
+        }
+        if !(1..=9).contains(&config.mem_level) {
+            return Err(format!("memory level {} is invalid", config.mem_level));
+        }
+        if config.mem_level != 8 && !self.mem_level {
+            return Err(format!("memory level {} is unsupported", config.mem_level));
+        }
+
+        self.wrapper(config.window_bits)
+    }
+
+    /// Whether `helper` can run in this mode and at this level
+    fn check(&self, mode: Mode, level: i32) -> Result<(), String> {
+        self.available()?;
+
+        if chunks().is_some() && !self.streaming {
+            return Err("streaming in chunks is unsupported".to_string());
+        }
+
+        match mode {
+            Mode::Inflate => self.check_inflate(inflate_config()),
+            Mode::Deflate => self.check_deflate(deflate_config(level)),
+        }
+    }
+}
+
+/// The string that a C library's version function returns
+#[cfg(any(feature = "og", feature = "ng", feature = "rs", feature = "cloudflare"))]
+fn version_string(version: *const core::ffi::c_char) -> String {
+    let version = unsafe { core::ffi::CStr::from_ptr(version) };
+    version.to_string_lossy().into_owned()
+}
+
+/// The version passed to the init functions. The libraries only check that the major version
+/// matches, so this is not the version that is linked: `zlib_version` reports that.
+#[cfg(any(feature = "og", feature = "rs", feature = "cloudflare"))]
+const HEADER_VERSION: &str = "1.2.8\0";
+
+/// The bits of `zlibCompileFlags()` that change what the library does
+const COMPILE_FLAGS: [(u32, &str); 7] = [
+    (8, "ZLIB_DEBUG"),
+    (9, "ASMV"),
+    (12, "BUILDFIXED"),
+    (13, "DYNAMIC_CRC_TABLE"),
+    (16, "NO_GZCOMPRESS"),
+    (17, "PKZIP_BUG_WORKAROUND"),
+    (18, "FASTEST"),
+];
+
+/// The build information of a C library, from its `zlibCompileFlags()`
+fn compile_flags_string(flags: Option<core::ffi::c_ulong>) -> String {
+    let Some(flags) = flags else {
+        return "compile flags unavailable".to_string();
+    };
+
+    let names: Vec<_> = COMPILE_FLAGS
+        .iter()
+        .filter(|(bit, _)| flags & (1 << bit) != 0)
+        .map(|(_, name)| *name)
+        .collect();
+
+    match names.as_slice() {
+        [] => format!("compile flags 0x{flags:x}"),
+        names => format!("compile flags 0x{flags:x} ({})", names.join(" ")),
+    }
+}
+
+/// The build information of a backend that is written in rust: the compiler, the optimization
+/// level and the target features that it could use without runtime detection
+fn rust_build() -> String {
+    let features = [
+        ("sse4.2", cfg!(target_feature = "sse4.2")),
+        ("pclmulqdq", cfg!(target_feature = "pclmulqdq")),
+        ("avx2", cfg!(target_feature = "avx2")),
+        ("bmi2", cfg!(target_feature = "bmi2")),
+        ("avx512f", cfg!(target_feature = "avx512f")),
+        ("neon", cfg!(target_feature = "neon")),
+        ("crc", cfg!(target_feature = "crc")),
+    ];
+    let features: Vec<_> = features
+        .iter()
+        .filter(|(_, enabled)| *enabled)
+        .map(|(name, _)| *name)
+        .collect();
+
+    format!(
+        "{}, opt-level {}, target features {}",
+        env!("ZLIB_BENCH_RUSTC"),
+        env!("ZLIB_BENCH_OPT_LEVEL"),
+        match features.as_slice() {
+            [] => "none".to_string(),
+            features => features.join(" "),
+        }
+    )
+}
+
+// `og-local` always implements this, but without a backend that has the zlib API the commands that
+// use the rest of it (dictionaries, gzip headers, indexes) are compiled out
+#[cfg_attr(
+    not(any(feature = "og", feature = "ng", feature = "rs", feature = "cloudflare")),
+    allow(dead_code)
+)]
+trait ZlibImplementation {
+    type Stream;
+
+    const NAME: &'static str;
+
+    /// Whether this is the system zlib, see `Capabilities::system_zlib`
+    const SYSTEM_ZLIB: bool = false;
+
+    fn inflate_init(strm: *mut Self::Stream, config: InflateConfig) -> ReturnCode;
+
+    fn inflate(strm: &mut Self::Stream, flush: Flush) -> ReturnCode;
+
+    fn inflate_end(strm: &mut Self::Stream) -> ReturnCode;
+
+    #[cfg_attr(not(feature = "og"), allow(dead_code))]
+    fn inflate_reset(strm: &mut Self::Stream) -> ReturnCode;
+
+    fn deflate_init(strm: *mut Self::Stream, config: DeflateConfig) -> ReturnCode;
+
+    fn deflate(strm: &mut Self::Stream, flush: Flush) -> ReturnCode;
+
+    fn deflate_end(strm: &mut Self::Stream) -> ReturnCode;
+
+    #[cfg_attr(not(feature = "og"), allow(dead_code))]
+    fn deflate_reset(strm: &mut Self::Stream) -> ReturnCode;
+
+    fn deflate_set_header(strm: &mut Self::Stream, head: &mut GzHeader) -> ReturnCode;
+
+    fn deflate_set_dictionary(strm: &mut Self::Stream, dictionary: &[u8]) -> ReturnCode;
+
+    fn inflate_get_header(strm: &mut Self::Stream, head: &mut GzHeader) -> ReturnCode;
+
+    fn inflate_prime(strm: &mut Self::Stream, bits: i32, value: i32) -> ReturnCode;
+
+    fn inflate_set_dictionary(strm: &mut Self::Stream, dictionary: &[u8]) -> ReturnCode;
+
+    fn set_in(strm: &mut Self::Stream, input: &[u8]);
+
+    fn set_out_raw<T>(strm: &mut Self::Stream, ptr: *const T, len: usize);
+
+    fn set_out(strm: &mut Self::Stream, output: &[u8]) {
+        Self::set_out_raw(strm, output.as_ptr(), output.len())
+    }
+
+    fn avail_out_mut(strm: &mut Self::Stream) -> &mut core::ffi::c_uint;
+    fn avail_in_mut(strm: &mut Self::Stream) -> &mut core::ffi::c_uint;
+
+    fn total_out(strm: &Self::Stream) -> usize;
+
+    fn data_type(strm: &Self::Stream) -> i32;
+
+    /// Allocate through `zalloc` and `zfree`, which get `opaque`, rather than with the default
+    /// allocator. Called on the zeroed stream, before it is initialized.
+    fn set_allocator(strm: *mut Self::Stream, zalloc: Alloc, zfree: Free, opaque: *mut c_void);
+
+    /// The version that the library reports, `zlibVersion()`
+    fn zlib_version() -> String;
+
+    /// The options that the library was compiled with, `zlibCompileFlags()`
+    fn zlib_compile_flags() -> Option<core::ffi::c_ulong>;
+}
+
+trait DeflateImplementation {
+    const NAME: &'static str;
+
+    /// The name in diagnostics: `NAME`, unless the implementation only knows its name at runtime
+    fn name() -> &'static str {
+        Self::NAME
+    }
+
+    const CAPABILITIES: Capabilities = Capabilities::ZLIB;
+
+    /// The version that the library reports, or the version of the crate
+    fn version() -> String;
+
+    /// How the library was built
+    fn build() -> String {
+        rust_build()
+    }
+
+    fn uncompress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode);
+
+    fn compress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode);
+
+    /// `uncompress_slice`, with the input fed and the output drained in `chunks`. Only called when
+    /// `CAPABILITIES.streaming`.
+    fn uncompress_chunked<'a>(
+        _output: &'a mut [u8],
+        _input: &[u8],
+        _config: InflateConfig,
+        _chunks: Chunks,
+    ) -> (&'a mut [u8], ReturnCode) {
+        unreachable!("{} can't stream", Self::name())
+    }
+
+    /// `compress_slice`, with the input fed and the output drained in `chunks`. Only called when
+    /// `CAPABILITIES.streaming`.
+    fn compress_chunked<'a>(
+        _output: &'a mut [u8],
+        _input: &[u8],
+        _config: DeflateConfig,
+        _chunks: Chunks,
+    ) -> (&'a mut [u8], ReturnCode) {
+        unreachable!("{} can't stream", Self::name())
+    }
+}
+
+/// `uncompress_slice`, or `uncompress_chunked` with `--chunk-in` or `--chunk-out`
+fn uncompress<'a, T: DeflateImplementation>(
+    output: &'a mut [u8],
+    input: &[u8],
+    config: InflateConfig,
+) -> (&'a mut [u8], ReturnCode) {
+    match chunks() {
+        None => T::uncompress_slice(output, input, config),
+        Some(chunks) => T::uncompress_chunked(output, input, config, chunks),
+    }
+}
+
+/// `compress_slice`, or `compress_chunked` with `--chunk-in` or `--chunk-out`
+fn compress<'a, T: DeflateImplementation>(
+    output: &'a mut [u8],
+    input: &[u8],
+    config: DeflateConfig,
+) -> (&'a mut [u8], ReturnCode) {
+    match chunks() {
+        None => T::compress_slice(output, input, config),
+        Some(chunks) => T::compress_chunked(output, input, config, chunks),
+    }
+}
+
+impl<T: ZlibImplementation> DeflateImplementation for T {
+    const NAME: &'static str = <T as ZlibImplementation>::NAME;
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        system_zlib: T::SYSTEM_ZLIB,
+        ..Capabilities::ZLIB
+    };
+
+    fn version() -> String {
+        T::zlib_version()
+    }
+
+    fn build() -> String {
+        compile_flags_string(T::zlib_compile_flags())
+    }
+
+    fn uncompress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let dest_len = output.len();
+        let mut dest_len_ptr = 0;
+
+        // z_uintmax_t len, left;
+        let mut left;
+        let dest;
+        let buf: &mut [u8] = &mut [1]; /* for detection of incomplete stream when *destLen == 0 */
+
+        let mut len = input.len() as u64;
+        if dest_len != 0 {
+            left = dest_len as u64;
+            dest_len_ptr = 0;
+            dest = output.as_mut_ptr();
+        } else {
+            left = 1;
+            dest = buf.as_mut_ptr().cast();
+        }
+
+        let mut stream = MaybeUninit::zeroed();
+        let err = Self::inflate_init(stream.as_mut_ptr(), config);
+        let stream = unsafe { stream.assume_init_mut() };
+
+        if err != ReturnCode::Ok {
+            return (&mut [], ReturnCode::from(err));
+        }
+
+        Self::set_in(stream, input);
+        Self::set_out(stream, output);
+
+        Self::set_out_raw(stream, dest, 0);
+
+        let err = loop {
+            if *Self::avail_out_mut(stream) == 0 {
+                *Self::avail_out_mut(stream) = Ord::min(left, u32::MAX as u64) as u32;
+                left -= *Self::avail_out_mut(stream) as u64;
+            }
+
+            if *Self::avail_out_mut(stream) == 0 {
+                *Self::avail_in_mut(stream) = Ord::min(len, u32::MAX as u64) as u32;
+                len -= *Self::avail_in_mut(stream) as u64;
+            }
+
+            let err = Self::inflate(stream, Flush::NoFlush as _);
+            let err = ReturnCode::from(err);
+
+            if err != ReturnCode::Ok as _ {
+                break err;
+            }
+        };
+
+        if dest_len != 0 {
+            dest_len_ptr = Self::total_out(stream);
+        } else if Self::total_out(stream) != 0 && err == ReturnCode::BufError as _ {
+            left = 1;
+        }
+
+        Self::inflate_end(stream);
+
+        let ret = match err {
+            ReturnCode::StreamEnd => ReturnCode::Ok,
+            ReturnCode::NeedDict => ReturnCode::DataError,
+            ReturnCode::BufError if (left + *Self::avail_out_mut(stream) as u64) != 0 => {
+                ReturnCode::DataError
+            }
+            _ => err,
+        };
+
+        // SAFETY: we have now initialized these bytes
+        let output_slice = unsafe {
+            std::slice::from_raw_parts_mut(output.as_mut_ptr() as *mut u8, dest_len_ptr as usize)
+        };
+
+        (output_slice, ret)
+    }
+
+    fn compress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let mut stream = MaybeUninit::zeroed();
+        let err = Self::deflate_init(stream.as_mut_ptr(), config);
+
+        if err != ReturnCode::Ok {
+            return (&mut [], ReturnCode::from(err));
+        }
+
+        let stream = unsafe { stream.assume_init_mut() };
+
+        Self::set_in(stream, &input[..0]);
+        Self::set_out(stream, &output[..0]);
+
+        let max = core::ffi::c_uint::MAX as usize;
+
+        let mut left = output.len();
+        let mut source_len = input.len();
+
+        loop {
+            if *Self::avail_out_mut(stream) == 0 {
+                *Self::avail_out_mut(stream) = Ord::min(left, max) as _;
+                left -= *Self::avail_out_mut(stream) as usize;
+            }
+
+            if *Self::avail_in_mut(stream) == 0 {
+                *Self::avail_in_mut(stream) = Ord::min(source_len, max) as _;
+                source_len -= *Self::avail_in_mut(stream) as usize;
+            }
+
+            let flush = if source_len > 0 {
+                Flush::NoFlush
+            } else {
+                Flush::Finish
+            };
+
+            let err = Self::deflate(stream, flush);
+
+            if err != ReturnCode::Ok {
+                break;
+            }
+        }
+
+        let err = Self::deflate_end(stream);
+        let return_code: ReturnCode = ReturnCode::from(err);
+        // may DataError if there was insufficient output space
+        assert_eq!(ReturnCode::Ok, return_code);
+
+        // SAFETY: we have now initialized these bytes
+        let output_slice = unsafe {
+            std::slice::from_raw_parts_mut(output.as_mut_ptr() as *mut u8, Self::total_out(stream))
+        };
+
+        (output_slice, ReturnCode::Ok)
+    }
+
+    fn uncompress_chunked<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+        chunks: Chunks,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let mut stream = MaybeUninit::zeroed();
+        let err = Self::inflate_init(stream.as_mut_ptr(), config);
+        if err != ReturnCode::Ok {
+            return (&mut [], err);
+        }
+        let stream = unsafe { stream.assume_init_mut() };
+
+        let mut fed = 0;
+        let err = loop {
+            if *Self::avail_in_mut(stream) == 0 {
+                let end = Ord::min(fed + chunks.input, input.len());
+                Self::set_in(stream, &input[fed..end]);
+                fed = end;
+            }
+            let written = Self::total_out(stream);
+            let end = Ord::min(written + chunks.output, output.len());
+            Self::set_out(stream, &output[written..end]);
+
+            match Self::inflate(stream, Flush::NoFlush) {
+                ReturnCode::Ok => {}
+                err => break err,
+            }
+        };
+
+        let len = Self::total_out(stream);
+        Self::inflate_end(stream);
+
+        match err {
+            ReturnCode::StreamEnd => (&mut output[..len], ReturnCode::Ok),
+            ReturnCode::NeedDict => (&mut [], ReturnCode::DataError),
+            // no progress with output space left: the input ended early
+            ReturnCode::BufError if len < output.len() => (&mut [], ReturnCode::DataError),
+            err => (&mut [], err),
+        }
+    }
+
+    fn compress_chunked<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+        chunks: Chunks,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let mut stream = MaybeUninit::zeroed();
+        let err = Self::deflate_init(stream.as_mut_ptr(), config);
+        if err != ReturnCode::Ok {
+            return (&mut [], err);
+        }
+        let stream = unsafe { stream.assume_init_mut() };
+
+        let mut fed = 0;
+        let err = loop {
+            if *Self::avail_in_mut(stream) == 0 {
+                let end = Ord::min(fed + chunks.input, input.len());
+                Self::set_in(stream, &input[fed..end]);
+                fed = end;
+            }
+            let written = Self::total_out(stream);
+            let end = Ord::min(written + chunks.output, output.len());
+            Self::set_out(stream, &output[written..end]);
+
+            let flush = match fed == input.len() {
+                true => Flush::Finish,
+                false => Flush::NoFlush,
+            };
+            match Self::deflate(stream, flush) {
+                ReturnCode::Ok => {}
+                err => break err,
+            }
+        };
+
+        let len = Self::total_out(stream);
+        Self::deflate_end(stream);
+
+        match err {
+            ReturnCode::StreamEnd => (&mut output[..len], ReturnCode::Ok),
+            err => (&mut [], err),
+        }
+    }
+}
+
+#[cfg(feature = "og")]
+struct ZlibOg;
+
+#[cfg(feature = "og")]
+impl ZlibImplementation for ZlibOg {
+    type Stream = libz_sys::z_stream;
+
+    const NAME: &'static str = "zlib-og";
+
+    fn zlib_version() -> String {
+        version_string(unsafe { libz_sys::zlibVersion() })
+    }
+
+    fn zlib_compile_flags() -> Option<core::ffi::c_ulong> {
+        Some(unsafe { libz_sys::zlibCompileFlags() })
+    }
+
+    fn inflate_init(strm: *mut Self::Stream, config: InflateConfig) -> ReturnCode {
+        ReturnCode::from(unsafe {
+            libz_sys::inflateInit2_(
+                strm,
+                config.window_bits,
+                HEADER_VERSION.as_ptr().cast(),
+                core::mem::size_of::<Self::Stream>() as _,
+            )
+        })
+    }
+
+    fn inflate(strm: &mut Self::Stream, flush: Flush) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_sys::inflate(strm, flush as _) })
+    }
+
+    fn inflate_end(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_sys::inflateEnd(strm) })
+    }
+
+    fn inflate_reset(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_sys::inflateReset(strm) })
+    }
+
+    fn deflate_init(strm: *mut Self::Stream, config: DeflateConfig) -> ReturnCode {
+        ReturnCode::from(unsafe {
+            libz_sys::deflateInit2_(
+                strm,
+                config.level,
+                config.method as i32,
+                config.window_bits,
+                config.mem_level,
+                config.strategy as i32,
+                HEADER_VERSION.as_ptr().cast(),
+                core::mem::size_of::<Self::Stream>() as _,
+            )
+        })
+    }
+
+    fn deflate(strm: &mut Self::Stream, flush: Flush) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_sys::deflate(strm, flush as _) })
+    }
+
+    fn deflate_end(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_sys::deflateEnd(strm) })
+    }
+
+    fn deflate_reset(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_sys::deflateReset(strm) })
+    }
+
+    fn deflate_set_header(strm: &mut Self::Stream, head: &mut GzHeader) -> ReturnCode {
+        let head = (head as *mut GzHeader).cast();
+        ReturnCode::from(unsafe { libz_sys::deflateSetHeader(strm, head) })
+    }
+
+    fn deflate_set_dictionary(strm: &mut Self::Stream, dictionary: &[u8]) -> ReturnCode {
+        let (ptr, len) = (dictionary.as_ptr(), dictionary.len() as _);
+        ReturnCode::from(unsafe { libz_sys::deflateSetDictionary(strm, ptr, len) })
+    }
+
+    fn inflate_get_header(strm: &mut Self::Stream, head: &mut GzHeader) -> ReturnCode {
+        let head = (head as *mut GzHeader).cast();
+        ReturnCode::from(unsafe { libz_sys::inflateGetHeader(strm, head) })
+    }
+
+    fn inflate_prime(strm: &mut Self::Stream, bits: i32, value: i32) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_sys::inflatePrime(strm, bits, value) })
+    }
+
+    fn inflate_set_dictionary(strm: &mut Self::Stream, dictionary: &[u8]) -> ReturnCode {
+        let (ptr, len) = (dictionary.as_ptr(), dictionary.len() as _);
+        ReturnCode::from(unsafe { libz_sys::inflateSetDictionary(strm, ptr, len) })
+    }
+
+    fn set_in(strm: &mut Self::Stream, input: &[u8]) {
+        strm.avail_in = input.len() as _;
+        strm.next_in = input.as_ptr() as *mut _;
+    }
+
+    fn set_out_raw<T>(strm: &mut Self::Stream, ptr: *const T, len: usize) {
+        strm.avail_out = len as _;
+        strm.next_out = ptr as *mut _;
+    }
+
+    fn avail_out_mut(strm: &mut Self::Stream) -> &mut core::ffi::c_uint {
+        &mut strm.avail_out
+    }
+
+    fn avail_in_mut(strm: &mut Self::Stream) -> &mut core::ffi::c_uint {
+        &mut strm.avail_in
+    }
+
+    fn total_out(strm: &Self::Stream) -> usize {
+        strm.total_out as usize
+    }
+
+    fn data_type(strm: &Self::Stream) -> i32 {
+        strm.data_type
+    }
+
+    fn set_allocator(strm: *mut Self::Stream, zalloc: Alloc, zfree: Free, opaque: *mut c_void) {
+        unsafe {
+            core::ptr::addr_of_mut!((*strm).zalloc).write(zalloc);
+            core::ptr::addr_of_mut!((*strm).zfree).write(zfree);
+            core::ptr::addr_of_mut!((*strm).opaque).write(opaque);
+        }
+    }
+}
+
+#[cfg(feature = "ng")]
+struct ZlibNg;
+
+#[cfg(feature = "ng")]
+impl ZlibImplementation for ZlibNg {
+    type Stream = libz_ng_sys::z_stream;
+
+    const NAME: &'static str = "zlib-ng";
+
+    fn zlib_version() -> String {
+        version_string(unsafe { libz_ng_sys::zlibVersion() })
+    }
+
+    fn zlib_compile_flags() -> Option<core::ffi::c_ulong> {
+        Some(unsafe { libz_ng_sys::zlibCompileFlags() })
+    }
+
+    fn inflate_init(strm: *mut Self::Stream, config: InflateConfig) -> ReturnCode {
+        ReturnCode::from(unsafe {
+            libz_ng_sys::inflateInit2_(
+                strm,
+                config.window_bits,
+                "2.1.0.devel\0".as_ptr().cast(),
+                core::mem::size_of::<Self::Stream>() as _,
+            )
+        })
+    }
+
+    fn inflate(strm: &mut Self::Stream, flush: Flush) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_ng_sys::inflate(strm, flush as _) })
+    }
+
+    fn inflate_end(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_ng_sys::inflateEnd(strm) })
+    }
+
+    fn inflate_reset(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_ng_sys::inflateReset(strm) })
+    }
+
+    fn deflate_init(strm: *mut Self::Stream, config: DeflateConfig) -> ReturnCode {
+        ReturnCode::from(unsafe {
+            libz_ng_sys::deflateInit2_(
+                strm,
+                config.level,
+                config.method as i32,
+                config.window_bits,
+                config.mem_level,
+                config.strategy as i32,
+                "2.1.0.devel\0".as_ptr().cast(),
+                core::mem::size_of::<Self::Stream>() as _,
+            )
+        })
+    }
+
+    fn deflate(strm: &mut Self::Stream, flush: Flush) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_ng_sys::deflate(strm, flush as _) })
+    }
+
+    fn deflate_end(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_ng_sys::deflateEnd(strm) })
+    }
+
+    fn deflate_reset(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_ng_sys::deflateReset(strm) })
+    }
+
+    fn deflate_set_header(strm: &mut Self::Stream, head: &mut GzHeader) -> ReturnCode {
+        let head = (head as *mut GzHeader).cast();
+        ReturnCode::from(unsafe { libz_ng_sys::deflateSetHeader(strm, head) })
+    }
+
+    fn deflate_set_dictionary(strm: &mut Self::Stream, dictionary: &[u8]) -> ReturnCode {
+        let (ptr, len) = (dictionary.as_ptr(), dictionary.len() as _);
+        ReturnCode::from(unsafe { libz_ng_sys::deflateSetDictionary(strm, ptr, len) })
+    }
+
+    fn inflate_get_header(strm: &mut Self::Stream, head: &mut GzHeader) -> ReturnCode {
+        let head = (head as *mut GzHeader).cast();
+        ReturnCode::from(unsafe { libz_ng_sys::inflateGetHeader(strm, head) })
+    }
+
+    fn inflate_prime(strm: &mut Self::Stream, bits: i32, value: i32) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_ng_sys::inflatePrime(strm, bits, value) })
+    }
+
+    fn inflate_set_dictionary(strm: &mut Self::Stream, dictionary: &[u8]) -> ReturnCode {
+        let (ptr, len) = (dictionary.as_ptr(), dictionary.len() as _);
+        ReturnCode::from(unsafe { libz_ng_sys::inflateSetDictionary(strm, ptr, len) })
+    }
+
+    fn set_in(strm: &mut Self::Stream, input: &[u8]) {
+        strm.avail_in = input.len() as _;
+        strm.next_in = input.as_ptr() as *mut _;
+    }
+
+    fn set_out_raw<T>(strm: &mut Self::Stream, ptr: *const T, len: usize) {
+        strm.avail_out = len as _;
+        strm.next_out = ptr as *mut _;
+    }
+
+    fn avail_out_mut(strm: &mut Self::Stream) -> &mut core::ffi::c_uint {
+        &mut strm.avail_out
+    }
+
+    fn avail_in_mut(strm: &mut Self::Stream) -> &mut core::ffi::c_uint {
+        &mut strm.avail_in
+    }
+
+    fn total_out(strm: &Self::Stream) -> usize {
+        strm.total_out as usize
+    }
+
+    fn data_type(strm: &Self::Stream) -> i32 {
+        strm.data_type
+    }
+
+    fn set_allocator(strm: *mut Self::Stream, zalloc: Alloc, zfree: Free, opaque: *mut c_void) {
+        unsafe {
+            core::ptr::addr_of_mut!((*strm).zalloc).write(zalloc);
+            core::ptr::addr_of_mut!((*strm).zfree).write(zfree);
+            core::ptr::addr_of_mut!((*strm).opaque).write(opaque);
+        }
+    }
+}
+
+#[cfg(feature = "rs")]
+struct ZlibRs;
+
+#[cfg(feature = "rs")]
+impl ZlibImplementation for ZlibRs {
+    type Stream = libz_rs_sys::z_stream;
+
+    const NAME: &'static str = "zlib-rs";
+
+    fn zlib_version() -> String {
+        version_string(libz_rs_sys::zlibVersion())
+    }
+
+    fn zlib_compile_flags() -> Option<core::ffi::c_ulong> {
+        Some(libz_rs_sys::zlibCompileFlags())
+    }
+
+    fn inflate_init(strm: *mut Self::Stream, config: InflateConfig) -> ReturnCode {
+        ReturnCode::from(unsafe {
+            libz_rs_sys::inflateInit2_(
+                strm,
+                config.window_bits,
+                HEADER_VERSION.as_ptr().cast(),
+                core::mem::size_of::<Self::Stream>() as _,
+            )
+        })
+    }
+
+    fn inflate(strm: &mut Self::Stream, flush: Flush) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_rs_sys::inflate(strm, flush as _) })
+    }
+
+    fn inflate_end(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_rs_sys::inflateEnd(strm) })
+    }
+
+    fn inflate_reset(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_rs_sys::inflateReset(strm) })
+    }
+
+    fn deflate_init(strm: *mut Self::Stream, config: DeflateConfig) -> ReturnCode {
+        ReturnCode::from(unsafe {
+            libz_rs_sys::deflateInit2_(
+                strm,
+                config.level,
+                config.method as i32,
+                config.window_bits,
+                config.mem_level,
+                config.strategy as i32,
+                HEADER_VERSION.as_ptr().cast(),
+                core::mem::size_of::<Self::Stream>() as _,
+            )
+        })
+    }
+
+    fn deflate(strm: &mut Self::Stream, flush: Flush) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_rs_sys::deflate(strm, flush as _) })
+    }
+
+    fn deflate_end(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_rs_sys::deflateEnd(strm) })
+    }
+
+    fn deflate_reset(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_rs_sys::deflateReset(strm) })
+    }
+
+    fn deflate_set_header(strm: &mut Self::Stream, head: &mut GzHeader) -> ReturnCode {
+        let head = (head as *mut GzHeader).cast();
+        ReturnCode::from(unsafe { libz_rs_sys::deflateSetHeader(strm, head) })
+    }
+
+    fn deflate_set_dictionary(strm: &mut Self::Stream, dictionary: &[u8]) -> ReturnCode {
+        let (ptr, len) = (dictionary.as_ptr(), dictionary.len() as _);
+        ReturnCode::from(unsafe { libz_rs_sys::deflateSetDictionary(strm, ptr, len) })
+    }
+
+    fn inflate_get_header(strm: &mut Self::Stream, head: &mut GzHeader) -> ReturnCode {
+        let head = (head as *mut GzHeader).cast();
+        ReturnCode::from(unsafe { libz_rs_sys::inflateGetHeader(strm, head) })
+    }
+
+    fn inflate_prime(strm: &mut Self::Stream, bits: i32, value: i32) -> ReturnCode {
+        ReturnCode::from(unsafe { libz_rs_sys::inflatePrime(strm, bits, value) })
+    }
+
+    fn inflate_set_dictionary(strm: &mut Self::Stream, dictionary: &[u8]) -> ReturnCode {
+        let (ptr, len) = (dictionary.as_ptr(), dictionary.len() as _);
+        ReturnCode::from(unsafe { libz_rs_sys::inflateSetDictionary(strm, ptr, len) })
+    }
+
+    fn set_in(strm: &mut Self::Stream, input: &[u8]) {
+        strm.avail_in = input.len() as _;
+        strm.next_in = input.as_ptr() as *mut _;
+    }
+
+    fn set_out_raw<T>(strm: &mut Self::Stream, ptr: *const T, len: usize) {
+        strm.avail_out = len as _;
+        strm.next_out = ptr as *mut _;
+    }
+
+    fn avail_out_mut(strm: &mut Self::Stream) -> &mut core::ffi::c_uint {
+        &mut strm.avail_out
+    }
+
+    fn avail_in_mut(strm: &mut Self::Stream) -> &mut core::ffi::c_uint {
+        &mut strm.avail_in
+    }
+
+    fn total_out(strm: &Self::Stream) -> usize {
+        strm.total_out as usize
+    }
+
+    fn data_type(strm: &Self::Stream) -> i32 {
+        strm.data_type
+    }
+
+    fn set_allocator(strm: *mut Self::Stream, zalloc: Alloc, zfree: Free, opaque: *mut c_void) {
+        unsafe {
+            core::ptr::addr_of_mut!((*strm).zalloc).write(Some(zalloc));
+            core::ptr::addr_of_mut!((*strm).zfree).write(Some(zfree));
+            core::ptr::addr_of_mut!((*strm).opaque).write(opaque);
+        }
+    }
+}
+
+#[cfg(feature = "cloudflare")]
+struct ZlibCloudflare;
+
+#[cfg(feature = "cloudflare")]
+impl ZlibImplementation for ZlibCloudflare {
+    type Stream = cloudflare_zlib_sys::z_stream;
+
+    const NAME: &'static str = "zlib-cloudflare";
+
+    fn zlib_version() -> String {
+        version_string(unsafe { cloudflare_zlib_sys::zlibVersion() })
+    }
+
+    fn zlib_compile_flags() -> Option<core::ffi::c_ulong> {
+        Some(unsafe { cloudflare_zlib_sys::zlibCompileFlags() })
+    }
+
+    fn inflate_init(strm: *mut Self::Stream, config: InflateConfig) -> ReturnCode {
+        ReturnCode::from(unsafe {
+            cloudflare_zlib_sys::inflateInit2_(
+                strm,
+                config.window_bits,
+                HEADER_VERSION.as_ptr().cast(),
+                core::mem::size_of::<Self::Stream>() as _,
+            )
+        })
+    }
+
+    fn inflate(strm: &mut Self::Stream, flush: Flush) -> ReturnCode {
+        ReturnCode::from(unsafe { cloudflare_zlib_sys::inflate(strm, flush as _) })
+    }
+
+    fn inflate_end(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { cloudflare_zlib_sys::inflateEnd(strm) })
+    }
+
+    fn inflate_reset(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { cloudflare_zlib_sys::inflateReset(strm) })
+    }
+
+    fn deflate_init(strm: *mut Self::Stream, config: DeflateConfig) -> ReturnCode {
+        ReturnCode::from(unsafe {
+            cloudflare_zlib_sys::deflateInit2_(
+                strm,
+                config.level,
+                config.method as i32,
+                config.window_bits,
+                config.mem_level,
+                config.strategy as i32,
+                HEADER_VERSION.as_ptr().cast(),
+                core::mem::size_of::<Self::Stream>() as _,
+            )
+        })
+    }
+
+    fn deflate(strm: &mut Self::Stream, flush: Flush) -> ReturnCode {
+        ReturnCode::from(unsafe { cloudflare_zlib_sys::deflate(strm, flush as _) })
+    }
+
+    fn deflate_end(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { cloudflare_zlib_sys::deflateEnd(strm) })
+    }
+
+    fn deflate_reset(strm: &mut Self::Stream) -> ReturnCode {
+        ReturnCode::from(unsafe { cloudflare_zlib_sys::deflateReset(strm) })
+    }
+
+    fn deflate_set_header(strm: &mut Self::Stream, head: &mut GzHeader) -> ReturnCode {
+        let head = (head as *mut GzHeader).cast();
+        ReturnCode::from(unsafe { cloudflare_zlib_sys::deflateSetHeader(strm, head) })
+    }
+
+    fn deflate_set_dictionary(strm: &mut Self::Stream, dictionary: &[u8]) -> ReturnCode {
+        let (ptr, len) = (dictionary.as_ptr(), dictionary.len() as _);
+        ReturnCode::from(unsafe { cloudflare_zlib_sys::deflateSetDictionary(strm, ptr, len) })
+    }
+
+    fn inflate_get_header(strm: &mut Self::Stream, head: &mut GzHeader) -> ReturnCode {
+        let head = (head as *mut GzHeader).cast();
+        ReturnCode::from(unsafe { cloudflare_zlib_sys::inflateGetHeader(strm, head) })
+    }
+
+    fn inflate_prime(strm: &mut Self::Stream, bits: i32, value: i32) -> ReturnCode {
+        ReturnCode::from(unsafe { cloudflare_zlib_sys::inflatePrime(strm, bits, value) })
+    }
+
+    fn inflate_set_dictionary(strm: &mut Self::Stream, dictionary: &[u8]) -> ReturnCode {
+        let (ptr, len) = (dictionary.as_ptr(), dictionary.len() as _);
+        ReturnCode::from(unsafe { cloudflare_zlib_sys::inflateSetDictionary(strm, ptr, len) })
+    }
+
+    fn set_in(strm: &mut Self::Stream, input: &[u8]) {
+        strm.avail_in = input.len() as _;
+        strm.next_in = input.as_ptr() as *mut _;
+    }
+
+    fn set_out_raw<T>(strm: &mut Self::Stream, ptr: *const T, len: usize) {
+        strm.avail_out = len as _;
+        strm.next_out = ptr as *mut _;
+    }
+
+    fn avail_out_mut(strm: &mut Self::Stream) -> &mut core::ffi::c_uint {
+        &mut strm.avail_out
+    }
+
+    fn avail_in_mut(strm: &mut Self::Stream) -> &mut core::ffi::c_uint {
+        &mut strm.avail_in
+    }
+
+    fn total_out(strm: &Self::Stream) -> usize {
+        strm.total_out as usize
+    }
+
+    fn data_type(strm: &Self::Stream) -> i32 {
+        strm.data_type
+    }
+
+    fn set_allocator(strm: *mut Self::Stream, zalloc: Alloc, zfree: Free, opaque: *mut c_void) {
+        unsafe {
+            core::ptr::addr_of_mut!((*strm).zalloc).write(Some(zalloc));
+            core::ptr::addr_of_mut!((*strm).zfree).write(Some(zfree));
+            core::ptr::addr_of_mut!((*strm).opaque).write(opaque);
+        }
+    }
+}
+
+#[cfg(feature = "miniz")]
+struct MinizOxide;
+
+#[cfg(feature = "miniz")]
+impl DeflateImplementation for MinizOxide {
+    const NAME: &'static str = "miniz-oxide";
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        max_level: 10,
+        gzip: false,
+        min_window_bits: 15,
+        dictionary: false,
+        mem_level: false,
+        ..Capabilities::ZLIB
+    };
+
+    fn version() -> String {
+        env!("ZLIB_BENCH_VERSION_MINIZ_OXIDE").to_string()
+    }
+
+    fn uncompress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let mut flags =
+            miniz_oxide::inflate::core::inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+
+        // negative window bits mean raw deflate, without the zlib header
+        if config.window_bits > 0 {
+            flags |= miniz_oxide::inflate::core::inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER;
+        }
+
+        let mut output = unsafe {
+            core::slice::from_raw_parts_mut(output.as_mut_ptr().cast::<u8>(), output.len())
+        };
+
+        let mut decomp = Box::<miniz_oxide::inflate::core::DecompressorOxide>::default();
+
+        let mut out_pos = 0;
+        loop {
+            // Wrap the whole output slice so we know we have enough of the
+            // decompressed data for matches.
+            let (status, _in_consumed, out_consumed) =
+                miniz_oxide::inflate::core::decompress(&mut decomp, input, output, out_pos, flags);
+            out_pos += out_consumed;
+
+            match status {
+                miniz_oxide::inflate::TINFLStatus::Done => {
+                    output = &mut output[..out_pos];
+                    return (output, ReturnCode::Ok);
+                }
+
+                miniz_oxide::inflate::TINFLStatus::HasMoreOutput => {
+                    unreachable!()
+                }
+
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    fn compress_slice<'a>(
+        mut output: &'a mut [u8],
+        mut input: &[u8],
+        config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        // The comp flags function sets the zlib flag if the window_bits parameter is > 0.
+        let flags = miniz_oxide::deflate::core::create_comp_flags_from_zip_params(
+            config.level.into(),
+            config.window_bits as i32,
+            config.strategy as i32,
+        );
+        let mut compressor = miniz_oxide::deflate::core::CompressorOxide::new(flags);
+
+        let mut out_pos = 0;
+        loop {
+            let (status, bytes_in, bytes_out) = miniz_oxide::deflate::core::compress(
+                &mut compressor,
+                input,
+                &mut output[out_pos..],
+                miniz_oxide::deflate::core::TDEFLFlush::Finish,
+            );
+            out_pos += bytes_out;
+
+            match status {
+                miniz_oxide::deflate::core::TDEFLStatus::Done => {
+                    output = &mut output[..out_pos];
+                    break;
+                }
+                miniz_oxide::deflate::core::TDEFLStatus::Okay if bytes_in <= input.len() => {
+                    input = &input[bytes_in..];
+
+                    if true {
+                        unreachable!("we should provide enough space");
+                    }
+                }
+                // Not supposed to happen unless there is a bug.
+                _ => panic!("Bug! Unexpectedly failed to compress!"),
+            }
+        }
+
+        (output, ReturnCode::Ok)
+    }
+
+    fn uncompress_chunked<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+        chunks: Chunks,
+    ) -> (&'a mut [u8], ReturnCode) {
+        use miniz_oxide::inflate::core::inflate_flags;
+        use miniz_oxide::inflate::TINFLStatus;
+
+        let mut flags = inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF;
+        if config.window_bits > 0 {
+            flags |= inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER;
+        }
+
+        let mut decomp = Box::<miniz_oxide::inflate::core::DecompressorOxide>::default();
+
+        let (mut in_pos, mut out_pos) = (0, 0);
+        loop {
+            let in_end = Ord::min(in_pos + chunks.input, input.len());
+            let flags = match in_end < input.len() {
+                true => flags | inflate_flags::TINFL_FLAG_HAS_MORE_INPUT,
+                false => flags,
+            };
+
+            // the output written so far stays in view, matches can refer back into it
+            let out_end = Ord::min(out_pos + chunks.output, output.len());
+            let (status, in_consumed, out_consumed) = miniz_oxide::inflate::core::decompress(
+                &mut decomp,
+                &input[in_pos..in_end],
+                &mut output[..out_end],
+                out_pos,
+                flags,
+            );
+            in_pos += in_consumed;
+            out_pos += out_consumed;
+
+            match status {
+                TINFLStatus::Done => return (&mut output[..out_pos], ReturnCode::Ok),
+                TINFLStatus::NeedsMoreInput if in_pos < input.len() => {}
+                TINFLStatus::HasMoreOutput if out_pos < output.len() => {}
+                TINFLStatus::HasMoreOutput => return (&mut [], ReturnCode::BufError),
+                _ => return (&mut [], ReturnCode::DataError),
+            }
+        }
+    }
+
+    fn compress_chunked<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+        chunks: Chunks,
+    ) -> (&'a mut [u8], ReturnCode) {
+        use miniz_oxide::deflate::core::{TDEFLFlush, TDEFLStatus};
+
+        let flags = miniz_oxide::deflate::core::create_comp_flags_from_zip_params(
+            config.level,
+            config.window_bits,
+            config.strategy as i32,
+        );
+        let mut compressor = miniz_oxide::deflate::core::CompressorOxide::new(flags);
+
+        let (mut in_pos, mut out_pos) = (0, 0);
+        loop {
+            let in_end = Ord::min(in_pos + chunks.input, input.len());
+            let flush = match in_end == input.len() {
+                true => TDEFLFlush::Finish,
+                false => TDEFLFlush::None,
+            };
+
+            let out_end = Ord::min(out_pos + chunks.output, output.len());
+            let (status, bytes_in, bytes_out) = miniz_oxide::deflate::core::compress(
+                &mut compressor,
+                &input[in_pos..in_end],
+                &mut output[out_pos..out_end],
+                flush,
+            );
+            in_pos += bytes_in;
+            out_pos += bytes_out;
+
+            match status {
+                TDEFLStatus::Done => return (&mut output[..out_pos], ReturnCode::Ok),
+                TDEFLStatus::Okay if bytes_in > 0 || bytes_out > 0 => {}
+                // no progress: the output did not fit
+                TDEFLStatus::Okay => return (&mut [], ReturnCode::BufError),
+                _ => return (&mut [], ReturnCode::StreamError),
+            }
+        }
+    }
+}
+
+/// The original C version of miniz, that miniz_oxide was ported from
+#[cfg(feature = "miniz-c")]
+struct MinizC;
+
+#[cfg(feature = "miniz-c")]
+impl MinizC {
+    fn return_code(ret: core::ffi::c_int) -> ReturnCode {
+        match ret {
+            // `MZ_PARAM_ERROR`, for window bits other than 15 and -15
+            -10000 => ReturnCode::StreamError,
+            ret => ReturnCode::from(ret),
+        }
+    }
+}
+
+#[cfg(feature = "miniz-c")]
+impl DeflateImplementation for MinizC {
+    const NAME: &'static str = "miniz-c";
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        max_level: 10,
+        gzip: false,
+        min_window_bits: 15,
+        dictionary: false,
+        ..Capabilities::ZLIB
+    };
+
+    fn version() -> String {
+        env!("ZLIB_BENCH_VERSION_MINIZ_SYS").to_string()
+    }
+
+    // C, compiled by the `-sys` crate's build script with cargo's optimization level
+    fn build() -> String {
+        format!("C, opt-level {}", env!("ZLIB_BENCH_OPT_LEVEL"))
+    }
+
+    fn uncompress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let mut stream: miniz_sys::mz_stream = unsafe { core::mem::zeroed() };
+        stream.next_in = input.as_ptr();
+        stream.avail_in = input.len() as _;
+        stream.next_out = output.as_mut_ptr();
+        stream.avail_out = output.len() as _;
+
+        unsafe {
+            let err =
+                Self::return_code(miniz_sys::mz_inflateInit2(&mut stream, config.window_bits));
+            if err != ReturnCode::Ok {
+                return (&mut [], err);
+            }
+
+            let err = Self::return_code(miniz_sys::mz_inflate(&mut stream, Flush::Finish as _));
+            miniz_sys::mz_inflateEnd(&mut stream);
+
+            match err {
+                ReturnCode::StreamEnd => (&mut output[..stream.total_out as usize], ReturnCode::Ok),
+                err => (&mut [], err),
+            }
+        }
+    }
+
+    fn compress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let mut stream: miniz_sys::mz_stream = unsafe { core::mem::zeroed() };
+        stream.next_in = input.as_ptr();
+        stream.avail_in = input.len() as _;
+        stream.next_out = output.as_mut_ptr();
+        stream.avail_out = output.len() as _;
+
+        unsafe {
+            let err = Self::return_code(miniz_sys::mz_deflateInit2(
+                &mut stream,
+                config.level,
+                config.method as _,
+                config.window_bits,
+                config.mem_level,
+                config.strategy as _,
+            ));
+            if err != ReturnCode::Ok {
+                return (&mut [], err);
+            }
+
+            let err = Self::return_code(miniz_sys::mz_deflate(&mut stream, Flush::Finish as _));
+            miniz_sys::mz_deflateEnd(&mut stream);
+
+            match err {
+                ReturnCode::StreamEnd => (&mut output[..stream.total_out as usize], ReturnCode::Ok),
+                // the output did not fit
+                ReturnCode::Ok => (&mut [], ReturnCode::BufError),
+                err => (&mut [], err),
+            }
+        }
+    }
+
+    fn uncompress_chunked<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+        chunks: Chunks,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let mut stream: miniz_sys::mz_stream = unsafe { core::mem::zeroed() };
+
+        unsafe {
+            let err =
+                Self::return_code(miniz_sys::mz_inflateInit2(&mut stream, config.window_bits));
+            if err != ReturnCode::Ok {
+                return (&mut [], err);
+            }
+
+            let mut fed = 0;
+            let err = loop {
+                if stream.avail_in == 0 {
+                    let end = Ord::min(fed + chunks.input, input.len());
+                    stream.next_in = input[fed..].as_ptr();
+                    stream.avail_in = (end - fed) as _;
+                    fed = end;
+                }
+                let written = stream.total_out as usize;
+                let end = Ord::min(written + chunks.output, output.len());
+                stream.next_out = output[written..].as_mut_ptr();
+                stream.avail_out = (end - written) as _;
+
+                match Self::return_code(miniz_sys::mz_inflate(&mut stream, Flush::NoFlush as _)) {
+                    ReturnCode::Ok => {}
+                    err => break err,
+                }
+            };
+            miniz_sys::mz_inflateEnd(&mut stream);
+
+            let len = stream.total_out as usize;
+            match err {
+                ReturnCode::StreamEnd => (&mut output[..len], ReturnCode::Ok),
+                // no progress with output space left: the input ended early
+                ReturnCode::BufError if len < output.len() => (&mut [], ReturnCode::DataError),
+                err => (&mut [], err),
+            }
+        }
+    }
+
+    fn compress_chunked<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+        chunks: Chunks,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let mut stream: miniz_sys::mz_stream = unsafe { core::mem::zeroed() };
+
+        unsafe {
+            let err = Self::return_code(miniz_sys::mz_deflateInit2(
+                &mut stream,
+                config.level,
+                config.method as _,
+                config.window_bits,
+                config.mem_level,
+                config.strategy as _,
+            ));
+            if err != ReturnCode::Ok {
+                return (&mut [], err);
+            }
+
+            let mut fed = 0;
+            let err = loop {
+                if stream.avail_in == 0 {
+                    let end = Ord::min(fed + chunks.input, input.len());
+                    stream.next_in = input[fed..].as_ptr();
+                    stream.avail_in = (end - fed) as _;
+                    fed = end;
+                }
+                let written = stream.total_out as usize;
+                let end = Ord::min(written + chunks.output, output.len());
+                stream.next_out = output[written..].as_mut_ptr();
+                stream.avail_out = (end - written) as _;
+
+                let flush = match fed == input.len() {
+                    true => Flush::Finish,
+                    false => Flush::NoFlush,
+                };
+                match Self::return_code(miniz_sys::mz_deflate(&mut stream, flush as _)) {
+                    ReturnCode::Ok => {}
+                    err => break err,
+                }
+            };
+            miniz_sys::mz_deflateEnd(&mut stream);
+
+            match err {
+                ReturnCode::StreamEnd => (&mut output[..stream.total_out as usize], ReturnCode::Ok),
+                err => (&mut [], err),
+            }
+        }
+    }
+}
+
+/// libdeflate only compresses and decompresses whole buffers, there is no streaming API
+#[cfg(feature = "libdeflate")]
+struct Libdeflate;
+
+#[cfg(feature = "libdeflate")]
+impl DeflateImplementation for Libdeflate {
+    const NAME: &'static str = "libdeflate";
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        max_level: 12,
+        min_window_bits: 15,
+        dictionary: false,
+        streaming: false,
+        strategies: false,
+        mem_level: false,
+        ..Capabilities::ZLIB
+    };
+
+    fn version() -> String {
+        env!("ZLIB_BENCH_VERSION_LIBDEFLATER").to_string()
+    }
+
+    // C, compiled by the `-sys` crate's build script with cargo's optimization level
+    fn build() -> String {
+        format!("C, opt-level {}", env!("ZLIB_BENCH_OPT_LEVEL"))
+    }
+
+    fn uncompress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let mut decompressor = libdeflater::Decompressor::new();
+
+        // like zlib: negative window bits mean raw deflate, and 16 is added for gzip
+        let result = match config.window_bits {
+            ..=-1 => decompressor.deflate_decompress(input, output),
+            16.. => decompressor.gzip_decompress(input, output),
+            _ => decompressor.zlib_decompress(input, output),
+        };
+
+        match result {
+            Ok(len) => (&mut output[..len], ReturnCode::Ok),
+            Err(libdeflater::DecompressionError::BadData) => (&mut [], ReturnCode::DataError),
+            Err(libdeflater::DecompressionError::InsufficientSpace) => {
+                (&mut [], ReturnCode::BufError)
+            }
+        }
+    }
+
+    fn compress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        // libdeflate has levels 0 to 12, where 1 to 9 roughly match zlib's. Like zlib, -1 is the
+        // default level (6), and other levels are an error.
+        let level = match config.level {
+            -1 => libdeflater::CompressionLvl::default(),
+            level => match libdeflater::CompressionLvl::new(level) {
+                Ok(level) => level,
+                Err(_) => return (&mut [], ReturnCode::StreamError),
+            },
+        };
+        let mut compressor = libdeflater::Compressor::new(level);
+
+        let result = match config.window_bits {
+            ..=-1 => compressor.deflate_compress(input, output),
+            16.. => compressor.gzip_compress(input, output),
+            _ => compressor.zlib_compress(input, output),
+        };
+
+        match result {
+            Ok(len) => (&mut output[..len], ReturnCode::Ok),
+            Err(libdeflater::CompressionError::InsufficientSpace) => {
+                (&mut [], ReturnCode::BufError)
+            }
+        }
+    }
+}
+
+/// The implementation that flate2 was built with, selected with the `flate2-*` cargo features. Like
+/// flate2 itself, zlib-ng takes precedence over zlib-rs, then cloudflare and then zlib.
+#[cfg(any(feature = "flate2", feature = "gzp"))]
+const FLATE2: &str = if cfg!(feature = "flate2-zlib-ng") {
+    "flate2-ng"
+} else if cfg!(feature = "flate2-zlib-rs") {
+    "flate2-rs"
+} else if cfg!(feature = "flate2-cloudflare") {
+    "flate2-cloudflare"
+} else if cfg!(feature = "flate2-zlib") {
+    "flate2-og"
+} else {
+    "flate2-miniz"
+};
+
+/// One of the other implementations, through flate2's `Compress` and `Decompress`. Most rust code
+/// uses the implementations like this, so the difference with the direct implementation is the
+/// cost of the wrapper.
+#[cfg(feature = "flate2")]
+struct Flate2;
+
+#[cfg(feature = "flate2")]
+impl Flate2 {
+    /// Whether to use the zlib header: only the default window size is available with every backend
+    fn zlib_header(window_bits: i32) -> Option<bool> {
+        match window_bits {
+            -15 => Some(false),
+            15 => Some(true),
+            _ => None,
+        }
+    }
+
+    /// flate2 panics on levels that the backend rejects
+    fn compression(level: i32) -> Option<flate2::Compression> {
+        match level {
+            -1 => Some(flate2::Compression::default()),
+            0..=9 => Some(flate2::Compression::new(level as u32)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "flate2")]
+impl DeflateImplementation for Flate2 {
+    const NAME: &'static str = FLATE2;
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        gzip: false,
+        min_window_bits: 15,
+        dictionary: false,
+        strategies: false,
+        mem_level: false,
+        ..Capabilities::ZLIB
+    };
+
+    fn version() -> String {
+        env!("ZLIB_BENCH_VERSION_FLATE2").to_string()
+    }
+
+    fn uncompress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let Some(zlib_header) = Self::zlib_header(config.window_bits) else {
+            return (&mut [], ReturnCode::StreamError);
+        };
+
+        let mut decompress = flate2::Decompress::new(zlib_header);
+        match decompress.decompress(input, output, flate2::FlushDecompress::Finish) {
+            Ok(flate2::Status::StreamEnd) => {
+                let len = decompress.total_out() as usize;
+                (&mut output[..len], ReturnCode::Ok)
+            }
+            Ok(_) => (&mut [], ReturnCode::BufError),
+            Err(_) => (&mut [], ReturnCode::DataError),
+        }
+    }
+
+    fn compress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let (Some(zlib_header), Some(level)) = (
+            Self::zlib_header(config.window_bits),
+            Self::compression(config.level),
+        ) else {
+            return (&mut [], ReturnCode::StreamError);
+        };
+
+        let mut compress = flate2::Compress::new(level, zlib_header);
+        match compress.compress(input, output, flate2::FlushCompress::Finish) {
+            Ok(flate2::Status::StreamEnd) => {
+                let len = compress.total_out() as usize;
+                (&mut output[..len], ReturnCode::Ok)
+            }
+            Ok(_) => (&mut [], ReturnCode::BufError),
+            Err(_) => (&mut [], ReturnCode::StreamError),
+        }
+    }
+
+    fn uncompress_chunked<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+        chunks: Chunks,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let Some(zlib_header) = Self::zlib_header(config.window_bits) else {
+            return (&mut [], ReturnCode::StreamError);
+        };
+
+        let mut decompress = flate2::Decompress::new(zlib_header);
+        loop {
+            let (read, written) = (
+                decompress.total_in() as usize,
+                decompress.total_out() as usize,
+            );
+            let input = &input[read..Ord::min(read + chunks.input, input.len())];
+            let end = Ord::min(written + chunks.output, output.len());
+
+            let flush = flate2::FlushDecompress::None;
+            match decompress.decompress(input, &mut output[written..end], flush) {
+                Ok(flate2::Status::StreamEnd) => {
+                    let len = decompress.total_out() as usize;
+                    return (&mut output[..len], ReturnCode::Ok);
+                }
+                Ok(flate2::Status::Ok) => {}
+                // no progress with output space left: the input ended early
+                Ok(flate2::Status::BufError) if end < output.len() => {
+                    return (&mut [], ReturnCode::DataError)
+                }
+                Ok(flate2::Status::BufError) => return (&mut [], ReturnCode::BufError),
+                Err(_) => return (&mut [], ReturnCode::DataError),
+            }
+        }
+    }
+
+    fn compress_chunked<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+        chunks: Chunks,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let (Some(zlib_header), Some(level)) = (
+            Self::zlib_header(config.window_bits),
+            Self::compression(config.level),
+        ) else {
+            return (&mut [], ReturnCode::StreamError);
+        };
+
+        let mut compress = flate2::Compress::new(level, zlib_header);
+        loop {
+            let (read, written) = (compress.total_in() as usize, compress.total_out() as usize);
+            let in_end = Ord::min(read + chunks.input, input.len());
+            let end = Ord::min(written + chunks.output, output.len());
+
+            let flush = match in_end == input.len() {
+                true => flate2::FlushCompress::Finish,
+                false => flate2::FlushCompress::None,
+            };
+            match compress.compress(&input[read..in_end], &mut output[written..end], flush) {
+                Ok(flate2::Status::StreamEnd) => {
+                    let len = compress.total_out() as usize;
+                    return (&mut output[..len], ReturnCode::Ok);
+                }
+                Ok(flate2::Status::Ok) => {}
+                Ok(flate2::Status::BufError) => return (&mut [], ReturnCode::BufError),
+                Err(_) => return (&mut [], ReturnCode::StreamError),
+            }
+        }
+    }
+}
+
+/// Zopfli only compresses: it spends orders of magnitude more time than zlib to find the smallest
+/// deflate stream it can, so its output size is a ceiling for what the other implementations could
+/// achieve. Inflating is done by zlib-rs.
+#[cfg(feature = "zopfli")]
+struct Zopfli;
+
+#[cfg(feature = "zopfli")]
+impl DeflateImplementation for Zopfli {
+    const NAME: &'static str = "zopfli";
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        min_window_bits: 15,
+        dictionary: false,
+        streaming: false,
+        strategies: false,
+        mem_level: false,
+        ..Capabilities::ZLIB
+    };
+
+    fn version() -> String {
+        env!("ZLIB_BENCH_VERSION_ZOPFLI").to_string()
+    }
+
+    fn uncompress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        ZlibRs::uncompress_slice(output, input, config)
+    }
+
+    fn compress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        // zopfli has no levels, every valid zlib level uses its default options
+        if !matches!(config.level, -1..=9) {
+            return (&mut [], ReturnCode::StreamError);
+        }
+
+        let format = match config.window_bits {
+            ..=-1 => zopfli::Format::Deflate,
+            16.. => zopfli::Format::Gzip,
+            _ => zopfli::Format::Zlib,
+        };
+
+        let mut compressed = Vec::new();
+        zopfli::compress(zopfli::Options::default(), format, input, &mut compressed).unwrap();
+
+        match output.get_mut(..compressed.len()) {
+            Some(output) => {
+                output.copy_from_slice(&compressed);
+                (output, ReturnCode::Ok)
+            }
+            None => (&mut [], ReturnCode::BufError),
+        }
+    }
+}
+
+/// An `io::Write` into a fixed buffer that takes at most `chunk` bytes per call, so the
+/// implementations that write their output into a writer drain it in pieces
+#[cfg(any(feature = "classic", feature = "yazi"))]
+struct ChunkedWriter<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+    chunk: usize,
+}
+
+#[cfg(any(feature = "classic", feature = "yazi"))]
+impl std::io::Write for ChunkedWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = Ord::min(
+            Ord::min(buf.len(), self.chunk),
+            self.buffer.len() - self.len,
+        );
+        if n == 0 && !buf.is_empty() {
+            return Err(std::io::ErrorKind::WriteZero.into());
+        }
+
+        self.buffer[self.len..][..n].copy_from_slice(&buf[..n]);
+        self.len += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The first pure-rust implementations: the `inflate` crate decompresses, and the `deflate` crate
+/// compresses. Both predate miniz_oxide, and are kept for historical comparison.
+#[cfg(feature = "classic")]
+struct Classic;
+
+#[cfg(feature = "classic")]
+impl DeflateImplementation for Classic {
+    const NAME: &'static str = "inflate+deflate";
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        gzip: false,
+        min_window_bits: 15,
+        dictionary: false,
+        strategies: false,
+        mem_level: false,
+        ..Capabilities::ZLIB
+    };
+
+    fn version() -> String {
+        let inflate = env!("ZLIB_BENCH_VERSION_INFLATE");
+        let deflate = env!("ZLIB_BENCH_VERSION_DEFLATE");
+        format!("{inflate}+{deflate}")
+    }
+
+    fn uncompress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let result = match config.window_bits {
+            ..=-1 => inflate::inflate_bytes(input),
+            8..=15 => inflate::inflate_bytes_zlib(input),
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+
+        match result {
+            Ok(decompressed) => match output.get_mut(..decompressed.len()) {
+                Some(output) => {
+                    output.copy_from_slice(&decompressed);
+                    (output, ReturnCode::Ok)
+                }
+                None => (&mut [], ReturnCode::BufError),
+            },
+            Err(_) => (&mut [], ReturnCode::DataError),
+        }
+    }
+
+    fn compress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        // the deflate crate has three levels, and no stored level: huffman-only is the closest
+        let compression = match config.level {
+            0 => deflate::CompressionOptions::huffman_only(),
+            1..=3 => deflate::Compression::Fast.into(),
+            -1 | 4..=6 => deflate::Compression::Default.into(),
+            7..=9 => deflate::Compression::Best.into(),
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+
+        let compressed = match config.window_bits {
+            ..=-1 => deflate::deflate_bytes_conf(input, compression),
+            8..=15 => deflate::deflate_bytes_zlib_conf(input, compression),
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+
+        match output.get_mut(..compressed.len()) {
+            Some(output) => {
+                output.copy_from_slice(&compressed);
+                (output, ReturnCode::Ok)
+            }
+            None => (&mut [], ReturnCode::BufError),
+        }
+    }
+
+    fn uncompress_chunked<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+        chunks: Chunks,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let mut stream = match config.window_bits {
+            ..=-1 => inflate::InflateStream::new(),
+            8..=15 => inflate::InflateStream::from_zlib(),
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+
+        // the stream decodes into its own buffer, and hands out what it has: the output can't be
+        // drained in smaller pieces, so `chunks.output` is not used
+        let (mut read, mut written) = (0, 0);
+        loop {
+            let piece = &input[read..Ord::min(read + chunks.input, input.len())];
+            let (n, decoded) = match stream.update(piece) {
+                Ok(result) => result,
+                Err(_) => return (&mut [], ReturnCode::DataError),
+            };
+            read += n;
+
+            if n == 0 && decoded.is_empty() {
+                break;
+            }
+            match output.get_mut(written..written + decoded.len()) {
+                Some(output) => output.copy_from_slice(decoded),
+                None => return (&mut [], ReturnCode::BufError),
+            }
+            written += decoded.len();
+        }
+
+        (&mut output[..written], ReturnCode::Ok)
+    }
+
+    fn compress_chunked<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+        chunks: Chunks,
+    ) -> (&'a mut [u8], ReturnCode) {
+        use std::io::Write;
+
+        let compression = match config.level {
+            0 => deflate::CompressionOptions::huffman_only(),
+            1..=3 => deflate::Compression::Fast.into(),
+            -1 | 4..=6 => deflate::Compression::Default.into(),
+            7..=9 => deflate::Compression::Best.into(),
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+
+        let writer = ChunkedWriter {
+            buffer: output,
+            len: 0,
+            chunk: chunks.output,
+        };
+        let mut pieces = input.chunks(chunks.input);
+        let result = match config.window_bits {
+            ..=-1 => {
+                let mut encoder = deflate::write::DeflateEncoder::new(writer, compression);
+                (pieces.try_for_each(|piece| encoder.write_all(piece)))
+                    .and_then(|()| encoder.finish())
+            }
+            8..=15 => {
+                let mut encoder = deflate::write::ZlibEncoder::new(writer, compression);
+                (pieces.try_for_each(|piece| encoder.write_all(piece)))
+                    .and_then(|()| encoder.finish())
+            }
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+
+        match result {
+            Ok(writer) => (&mut writer.buffer[..writer.len], ReturnCode::Ok),
+            // the only error is the output that did not fit
+            Err(_) => (&mut [], ReturnCode::BufError),
+        }
+    }
+}
+
+#[cfg(feature = "yazi")]
+struct Yazi;
+
+#[cfg(feature = "yazi")]
+impl DeflateImplementation for Yazi {
+    const NAME: &'static str = "yazi";
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        gzip: false,
+        min_window_bits: 15,
+        dictionary: false,
+        strategies: false,
+        mem_level: false,
+        ..Capabilities::ZLIB
+    };
+
+    fn version() -> String {
+        env!("ZLIB_BENCH_VERSION_YAZI").to_string()
+    }
+
+    fn uncompress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let format = match config.window_bits {
+            ..=-1 => yazi::Format::Raw,
+            8..=15 => yazi::Format::Zlib,
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+
+        let mut decoder = yazi::Decoder::boxed();
+        decoder.set_format(format);
+        let mut stream = decoder.stream_into_buf(output);
+        let result = stream.write(input).and_then(|_| stream.finish());
+
+        match result {
+            Ok((len, checksum)) => {
+                let output = &mut output[..len as usize];
+
+                // yazi returns the checksum of the stream, but does not verify it
+                match checksum {
+                    Some(checksum) if checksum != adler::adler32_slice(output) => {
+                        (&mut [], ReturnCode::DataError)
+                    }
+                    _ => (output, ReturnCode::Ok),
+                }
+            }
+            Err(yazi::Error::Overflow) => (&mut [], ReturnCode::BufError),
+            Err(_) => (&mut [], ReturnCode::DataError),
+        }
+    }
+
+    fn compress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let level = match config.level {
+            -1 => yazi::CompressionLevel::Default,
+            0 => yazi::CompressionLevel::None,
+            1..=9 => yazi::CompressionLevel::Specific(config.level as u8),
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+        let format = match config.window_bits {
+            ..=-1 => yazi::Format::Raw,
+            8..=15 => yazi::Format::Zlib,
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+
+        let mut encoder = yazi::Encoder::boxed();
+        encoder.set_format(format);
+        encoder.set_level(level);
+        let mut stream = encoder.stream_into_buf(output);
+        let result = stream.write(input).and_then(|_| stream.finish());
+
+        match result {
+            Ok(len) => (&mut output[..len as usize], ReturnCode::Ok),
+            Err(yazi::Error::Overflow) => (&mut [], ReturnCode::BufError),
+            Err(_) => (&mut [], ReturnCode::StreamError),
+        }
+    }
+
+    fn uncompress_chunked<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+        chunks: Chunks,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let format = match config.window_bits {
+            ..=-1 => yazi::Format::Raw,
+            8..=15 => yazi::Format::Zlib,
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+
+        let mut writer = ChunkedWriter {
+            buffer: output,
+            len: 0,
+            chunk: chunks.output,
+        };
+        let mut decoder = yazi::Decoder::boxed();
+        decoder.set_format(format);
+        let mut stream = decoder.stream(&mut writer);
+        let result = (input.chunks(chunks.input))
+            .try_for_each(|piece| stream.write(piece))
+            .and_then(|()| stream.finish());
+
+        match result {
+            Ok((len, checksum)) => {
+                let output = &mut writer.buffer[..len as usize];
+
+                // yazi returns the checksum of the stream, but does not verify it
+                match checksum {
+                    Some(checksum) if checksum != adler::adler32_slice(output) => {
+                        (&mut [], ReturnCode::DataError)
+                    }
+                    _ => (output, ReturnCode::Ok),
+                }
+            }
+            // the only error of the writer is the output that did not fit
+            Err(yazi::Error::Io(_)) => (&mut [], ReturnCode::BufError),
+            Err(_) => (&mut [], ReturnCode::DataError),
+        }
+    }
+
+    fn compress_chunked<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+        chunks: Chunks,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let level = match config.level {
+            -1 => yazi::CompressionLevel::Default,
+            0 => yazi::CompressionLevel::None,
+            1..=9 => yazi::CompressionLevel::Specific(config.level as u8),
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+        let format = match config.window_bits {
+            ..=-1 => yazi::Format::Raw,
+            8..=15 => yazi::Format::Zlib,
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+
+        let mut writer = ChunkedWriter {
+            buffer: output,
+            len: 0,
+            chunk: chunks.output,
+        };
+        let mut encoder = yazi::Encoder::boxed();
+        encoder.set_format(format);
+        encoder.set_level(level);
+        let mut stream = encoder.stream(&mut writer);
+        let result = (input.chunks(chunks.input))
+            .try_for_each(|piece| stream.write(piece))
+            .and_then(|()| stream.finish());
+
+        match result {
+            Ok(len) => (&mut writer.buffer[..len as usize], ReturnCode::Ok),
+            Err(yazi::Error::Io(_)) => (&mut [], ReturnCode::BufError),
+            Err(_) => (&mut [], ReturnCode::StreamError),
+        }
+    }
+}
+
+/// zune-inflate only decompresses, `compress_slice` is a `StreamError`
+#[cfg(feature = "zune")]
+struct ZuneInflate;
+
+#[cfg(feature = "zune")]
+impl DeflateImplementation for ZuneInflate {
+    const NAME: &'static str = "zune-inflate";
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        deflate: false,
+        min_window_bits: 15,
+        dictionary: false,
+        streaming: false,
+        strategies: false,
+        mem_level: false,
+        ..Capabilities::ZLIB
+    };
+
+    fn version() -> String {
+        env!("ZLIB_BENCH_VERSION_ZUNE_INFLATE").to_string()
+    }
+
+    fn uncompress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        // the output is a vector, that is limited to the size of the buffer. It is preallocated (and
+        // zeroed) for a typical compression ratio rather than for the whole buffer, it grows if needed
+        let options = zune_inflate::DeflateOptions::default()
+            .set_limit(output.len())
+            .set_size_hint(output.len().min(4 * input.len()));
+        let mut decoder = zune_inflate::DeflateDecoder::new_with_options(input, options);
+
+        let result = match config.window_bits {
+            ..=-1 => decoder.decode_deflate(),
+            16.. => decoder.decode_gzip(),
+            _ => decoder.decode_zlib(),
+        };
+
+        match result {
+            Ok(decompressed) => {
+                let output = &mut output[..decompressed.len()];
+                output.copy_from_slice(&decompressed);
+                (output, ReturnCode::Ok)
+            }
+            Err(e) => match e.error {
+                zune_inflate::errors::DecodeErrorStatus::OutputLimitExceeded(..) => {
+                    (&mut [], ReturnCode::BufError)
+                }
+                _ => (&mut [], ReturnCode::DataError),
+            },
+        }
+    }
+
+    fn compress_slice<'a>(
+        _output: &'a mut [u8],
+        _input: &[u8],
+        _config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        (&mut [], ReturnCode::StreamError)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+    Inflate,
+    Deflate,
+}
+
+impl Mode {
+    /// The name of the mode on the command line
+    fn name(self) -> &'static str {
+        match self {
+            Mode::Inflate => "inflate",
+            Mode::Deflate => "deflate",
+        }
+    }
+}
+
+/// The command line interface. `src/main.rs` only calls this, so that a crate that registers its own
+/// backends (see `registry`) can run exactly the same commands.
+pub fn main() {
+    let mut it = std::env::args();
+
+    let _ = it.next().unwrap();
+
+    // the options that apply to every command come before it
+    let mut command = it.next().unwrap();
+    while command.starts_with("--") {
+        match command.as_str() {
+            "--pin-cpu" => {
+                let cpu = it.next().unwrap().parse().unwrap();
+                if let Err(e) = environment::pin_cpu(cpu) {
+                    eprintln!("--pin-cpu {cpu}: {e}");
+                    std::process::exit(1);
+                }
+            }
+            "--timer" => match it.next().as_deref() {
+                Some("instant") => {}
+                Some("cycles") => match timer::use_cycles() {
+                    Ok(ticks_per_ns) => {
+                        println!("# timer: cycle counter, {ticks_per_ns:.3} ticks/ns")
+                    }
+                    Err(e) => {
+                        eprintln!("--timer cycles: {e}");
+                        std::process::exit(1);
+                    }
+                },
+                other => panic!("invalid timer {other:?}"),
+            },
+            other => panic!("invalid option {other:?}"),
+        }
+        command = it.next().unwrap();
+    }
+
+    let mode = match command.as_str() {
+        "inflate" => Mode::Inflate,
+        "deflate" => Mode::Deflate,
+        "deflate-all" => {
+            let levels = level_args(&mut it);
+            let path = it.next().unwrap();
+            let options = Options::parse(it);
+            set_window_bits(options.window_bits.unwrap_or(15));
+            set_chunks(options.chunk_in, options.chunk_out);
+            set_input(options.bytes, options.target_size);
+            interrupt::install();
+
+            let strategies = options.strategies.unwrap_or(&[Strategy::Default]);
+            let mem_levels = options.mem_levels.unwrap_or(&[8]);
+            for (strategy, mem_level) in deflate_settings(strategies, mem_levels) {
+                set_strategy(strategy);
+                set_mem_level(mem_level);
+                if options.strategies.is_some() {
+                    println!("# strategy: {}", strategy.name());
+                }
+                if options.mem_levels.is_some() {
+                    println!("# mem level: {mem_level}");
+                }
+                for &level in &levels {
+                    if interrupt::interrupted() {
+                        return;
+                    }
+                    deflate_all(&path, level, &options);
+                }
+            }
+            return;
+        }
+        "stored-all" => {
+            let path = it.next().unwrap();
+            let options = Options::parse(it);
+            set_window_bits(options.window_bits.unwrap_or(15));
+            set_chunks(options.chunk_in, options.chunk_out);
+            set_input(options.bytes, options.target_size);
+
+            return stored_all(&path, &options);
+        }
+        "inflate-all" => {
+            let path = it.next().unwrap();
+            let options = Options::parse(it);
+            set_window_bits(options.window_bits.unwrap_or(15));
+            set_chunks(options.chunk_in, options.chunk_out);
+            let whole = options.bytes.is_none() && options.target_size.is_none();
+            assert!(whole, "{INFLATE_INPUT}");
+            interrupt::install();
+
+            return inflate_all(&path, &options);
+        }
+        "ab" => {
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+            let a = it.next().unwrap();
+            let b = it.next().unwrap();
+            let (runs, alpha) = comparison_args(it);
+
+            return ab::ab_all(mode, &path, level, &a, &b, runs, alpha);
+        }
+        "crossover" => {
+            // without a mode, compare the levels rather than the input sizes
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Some(Mode::Inflate), 0),
+                "deflate" => (Some(Mode::Deflate), it.next().unwrap().parse().unwrap()),
+                "levels" => (None, 0),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+            let a = it.next().unwrap();
+            let b = it.next().unwrap();
+
+            return crossover::crossover_all(mode, &path, level, &a, &b);
+        }
+        "roundtrip" => {
+            let level = it.next().unwrap().parse().unwrap();
+            let implementation = it.next().unwrap();
+            let path = it.next().unwrap();
+            let runs = Runs::parse(it);
+            set_window_bits(runs.window_bits);
+            set_chunks(runs.chunk_in, runs.chunk_out);
+            set_input(runs.bytes, runs.target_size);
+
+            let (&[strategy], &[mem_level]) = (runs.strategies, runs.mem_levels) else {
+                panic!("roundtrip takes a single --strategy and --mem-level");
+            };
+            set_strategy(strategy);
+            set_mem_level(mem_level);
+
+            return roundtrip::roundtrip_all(&implementation, &path, level, runs);
+        }
+        "matrix" => {
+            let (paths, options) = matrix::Options::parse(it);
+            interrupt::install();
+
+            return matrix::matrix_all(&paths, &options);
+        }
+        "compare" => {
+            let a = it.next().unwrap();
+            let b = it.next().unwrap();
+            let path = it.next().unwrap();
+
+            let mut configs = ab::Configs {
+                inflate: false,
+                levels: vec![1, 6, 9],
+            };
+            let mut rest = Vec::new();
+            while let Some(arg) = it.next() {
+                match arg.as_str() {
+                    "--inflate" => configs.inflate = true,
+                    "--levels" => configs.levels = parse_levels(&it.next().unwrap()),
+                    _ => rest.push(arg),
+                }
+            }
+            let (runs, alpha) = comparison_args(rest.into_iter());
+
+            return ab::compare_all(&a, &b, &path, &configs, runs, alpha);
+        }
+        #[cfg(any(
+            feature = "og",
+            feature = "ng",
+            feature = "rs",
+            feature = "cloudflare",
+            feature = "miniz",
+            feature = "libdeflate",
+            feature = "miniz-c"
+        ))]
+        "alignment" => {
+            let buffer = match it.next().unwrap().as_str() {
+                "input" => alignment::Buffer::Input,
+                "output" => alignment::Buffer::Output,
+                other => panic!("invalid buffer {other:?}"),
+            };
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+
+            return alignment::alignment_all(buffer, mode, &path, level);
+        }
+        #[cfg(not(any(
+            feature = "og",
+            feature = "ng",
+            feature = "rs",
+            feature = "cloudflare",
+            feature = "miniz",
+            feature = "libdeflate",
+            feature = "miniz-c"
+        )))]
+        "alignment" => panic!(
+            "alignment needs one of the features og, ng, rs, cloudflare, miniz, libdeflate or miniz-c"
+        ),
+        #[cfg(feature = "og")]
+        "output-chunks" => {
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+            let mut sizes: Vec<usize> = it.map(|size| size.parse().unwrap()).collect();
+            if sizes.is_empty() {
+                // just below and just above common internal chunk sizes
+                sizes = vec![1, 7, 255, 257, 4095, 4097, 65535, 65537, 1 << 20];
+            }
+
+            return chunks::output_chunks_all(mode, &path, level, &sizes);
+        }
+        #[cfg(not(feature = "og"))]
+        "output-chunks" => {
+            panic!("output-chunks checks its output with zlib, build with `--features og`")
+        }
+        "constrained-output" => {
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+            let mut sizes: Vec<usize> = it.map(|size| size.parse().unwrap()).collect();
+            if sizes.is_empty() {
+                // a common buffer size for writing to a socket or a file
+                sizes = vec![16 << 10];
+            }
+            assert!(
+                sizes.iter().all(|&size| size > 0),
+                "the output buffer can't be empty"
+            );
+
+            return constrained::constrained_output_all(mode, &path, level, &sizes);
+        }
+        "memory" => {
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+            let options = Options::parse(it);
+            set_window_bits(options.window_bits.unwrap_or(15));
+
+            // the footprint of every mem level next to each other is the point of `--mem-level all`
+            let strategies = options.strategies.unwrap_or(&[Strategy::Default]);
+            let mem_levels = options.mem_levels.unwrap_or(&[8]);
+            for (strategy, mem_level) in deflate_settings(strategies, mem_levels) {
+                set_strategy(strategy);
+                set_mem_level(mem_level);
+                if options.strategies.is_some() {
+                    println!("# strategy: {}", strategy.name());
+                }
+                if options.mem_levels.is_some() {
+                    println!("# mem level: {mem_level}");
+                }
+                memory::memory_all(mode, &path, level);
+            }
+            return;
+        }
+        "one-byte" => {
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+
+            return one_byte::one_byte_all(mode, &path, level);
+        }
+        "memcheck" => {
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+
+            let mut suppressions = Vec::new();
+            while let Some(arg) = it.next() {
+                match arg.as_str() {
+                    "--suppressions" => suppressions.push(it.next().unwrap()),
+                    other => panic!("invalid argument {other:?}"),
+                }
+            }
+
+            return memcheck::memcheck_all(mode, &path, level, &suppressions);
+        }
+        #[cfg(feature = "zopfli")]
+        "ratio" => {
+            let path = it.next().unwrap();
+            let level = match it.next() {
+                Some(level) => level.parse().unwrap(),
+                None => 9,
+            };
+
+            return ratio::ratio_all(&path, level);
+        }
+        #[cfg(not(feature = "zopfli"))]
+        "ratio" => panic!("ratio needs zopfli, build with `--features zopfli`"),
+        #[cfg(feature = "og")]
+        "records" => {
+            let level = it.next().unwrap().parse().unwrap();
+            let path = it.next().unwrap();
+            let record_size = match it.next() {
+                Some(size) => size.parse().unwrap(),
+                None => 512,
+            };
+
+            return records::records_all(&path, level, record_size);
+        }
+        #[cfg(not(feature = "og"))]
+        "records" => panic!("records checks its output with zlib, build with `--features og`"),
+        // the child process of `--isolate`
+        "measure" => return isolate::child(it),
+        "backends" => return backends_all(),
+        "list" => return list_all(),
+        "check-env" => {
+            let findings = environment::check();
+            environment::print(&findings);
+
+            return;
+        }
+        #[cfg(feature = "async")]
+        "async" => {
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+
+            return asynchronous::async_all(mode, &path, level);
+        }
+        #[cfg(not(feature = "async"))]
+        "async" => panic!("async-compression is not available, build with `--features async`"),
+        #[cfg(feature = "og")]
+        "bgzf" => {
+            let level = it.next().unwrap().parse().unwrap();
+            let path = it.next().unwrap();
+
+            return bgzf::bgzf_all(&path, level);
+        }
+        #[cfg(not(feature = "og"))]
+        "bgzf" => panic!("bgzf checks its output with zlib, build with `--features og`"),
+        "init-cost" => {
+            let level = it.next().unwrap().parse().unwrap();
+            let path = it.next().unwrap();
+            let mut sizes: Vec<usize> = it.map(|size| size.parse().unwrap()).collect();
+            if sizes.is_empty() {
+                // from where setting up the stream dominates to where it disappears in the noise
+                sizes = vec![64, 1 << 10, 16 << 10, 256 << 10, 4 << 20];
+            }
+
+            return init_cost::init_cost_all(&path, level, &sizes);
+        }
+        #[cfg(any(feature = "og", feature = "ng"))]
+        "linking" => {
+            let level = it.next().unwrap().parse().unwrap();
+            let path = it.next().unwrap();
+            let mut sizes: Vec<usize> = it.map(|size| size.parse().unwrap()).collect();
+            if sizes.is_empty() {
+                // the small payloads, where the cost of a call is not lost in the work
+                sizes = vec![64, 256, 1 << 10, 4 << 10, 16 << 10];
+            }
+
+            return linking::linking_all(&path, level, &sizes);
+        }
+        #[cfg(not(any(feature = "og", feature = "ng")))]
+        "linking" => panic!("linking needs one of the features og or ng"),
+        "messages" => {
+            let level = it.next().unwrap().parse().unwrap();
+            let path = it.next().unwrap();
+            let message_size = match it.next() {
+                Some(size) => size.parse().unwrap(),
+                None => 200,
+            };
+
+            return messages::messages_all(&path, level, message_size);
+        }
+        #[cfg(any(feature = "og", feature = "ng", feature = "rs", feature = "cloudflare"))]
+        "flush-dictionary" => {
+            let level = it.next().unwrap().parse().unwrap();
+            let path = it.next().unwrap();
+
+            return dictionary::flush_dictionary_all(&path, level);
+        }
+        #[cfg(not(any(feature = "og", feature = "ng", feature = "rs", feature = "cloudflare")))]
+        "flush-dictionary" => panic!(
+            "flush-dictionary needs one of the features og, ng, rs or cloudflare"
+        ),
+        "chromium" => {
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+            let library = it.next();
+
+            return chromium::chromium_all(mode, &path, level, library.as_deref());
+        }
+        #[cfg(any(
+            feature = "og",
+            feature = "ng",
+            feature = "rs",
+            feature = "cloudflare",
+            feature = "miniz",
+            feature = "libdeflate",
+            feature = "miniz-c"
+        ))]
+        "decoders" => {
+            let path = it.next().unwrap();
+            let mut levels: Vec<i32> = it.map(|level| level.parse().unwrap()).collect();
+            if levels.is_empty() {
+                levels = (0..=9).collect();
+            }
+
+            return decoders::decoders_all(&path, &levels);
+        }
+        #[cfg(not(any(
+            feature = "og",
+            feature = "ng",
+            feature = "rs",
+            feature = "cloudflare",
+            feature = "miniz",
+            feature = "libdeflate",
+            feature = "miniz-c"
+        )))]
+        "decoders" => panic!(
+            "decoders needs one of the features og, ng, rs, cloudflare, miniz, libdeflate or miniz-c"
+        ),
+        #[cfg(any(feature = "og", feature = "ng", feature = "rs", feature = "cloudflare"))]
+        "dict" => match it.next().as_deref() {
+            Some("build") => {
+                let path = it.next().unwrap();
+                let output = it.next().unwrap();
+                let sample_size = match it.next() {
+                    Some(size) => size.parse().unwrap(),
+                    None => 1024,
+                };
+                let level = match it.next() {
+                    Some(level) => level.parse().unwrap(),
+                    None => 6,
+                };
+
+                return dictionary::dict_build_all(&path, &output, sample_size, level);
+            }
+            other => panic!("invalid dict command {other:?}"),
+        },
+        #[cfg(not(any(feature = "og", feature = "ng", feature = "rs", feature = "cloudflare")))]
+        "dict" => panic!("dict build needs one of the features og, ng, rs or cloudflare"),
+        "generate" => {
+            let kind = it.next().unwrap();
+            let path = it.next().unwrap();
+            let size = match it.next() {
+                Some(mib) => mib.parse::<usize>().unwrap() << 20,
+                None => 16 << 20,
+            };
+
+            return generate::generate_all(&kind, &path, size);
+        }
+        "export-explorer" => {
+            let output = it.next().unwrap();
+            let inputs: Vec<String> = it.collect();
+
+            return explorer::export_explorer(&output, &inputs);
+        }
+        #[cfg(any(feature = "og", feature = "ng", feature = "rs", feature = "cloudflare"))]
+        "gzip-header" => {
+            let path = it.next().unwrap();
+
+            return gzip::gzip_header_all(&path);
+        }
+        #[cfg(not(any(feature = "og", feature = "ng", feature = "rs", feature = "cloudflare")))]
+        "gzip-header" => panic!("gzip-header needs one of the features og, ng, rs or cloudflare"),
+        #[cfg(feature = "gzp")]
+        "gzp" => {
+            let path = it.next().unwrap();
+            let mut level = 6;
+            let mut threads = Vec::new();
+            while let Some(arg) = it.next() {
+                match arg.as_str() {
+                    "--threads" => {
+                        let list = it.next().unwrap();
+                        threads = list.split(',').map(|t| t.parse().unwrap()).collect();
+                    }
+                    level_arg => level = level_arg.parse().unwrap(),
+                }
+            }
+            if threads.is_empty() {
+                // powers of two up to the number of cores
+                let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+                threads = (0..).map(|i| 1 << i).take_while(|&t| t <= cores).collect();
+            }
+
+            return parallel::gzp_all(&path, level, &threads);
+        }
+        #[cfg(not(feature = "gzp"))]
+        "gzp" => panic!("gzp is not available, build with `--features gzp`"),
+        #[cfg(any(feature = "og", feature = "ng", feature = "rs", feature = "cloudflare"))]
+        "index" => {
+            let path = it.next().unwrap();
+            let span = match it.next() {
+                Some(span) => span.parse().unwrap(),
+                None => 1 << 20,
+            };
+
+            return index::index_all(&path, span);
+        }
+        #[cfg(not(any(feature = "og", feature = "ng", feature = "rs", feature = "cloudflare")))]
+        "index" => panic!("index needs one of the features og, ng, rs or cloudflare"),
+        #[cfg(any(
+            feature = "og",
+            feature = "ng",
+            feature = "rs",
+            feature = "cloudflare",
+            feature = "miniz",
+            feature = "libdeflate",
+            feature = "miniz-c"
+        ))]
+        "level1" => {
+            let path = it.next().unwrap();
+            let level = match it.next() {
+                Some(level) => level.parse().unwrap(),
+                None => 1,
+            };
+
+            return level1::level1_all(&path, level);
+        }
+        #[cfg(not(any(
+            feature = "og",
+            feature = "ng",
+            feature = "rs",
+            feature = "cloudflare",
+            feature = "miniz",
+            feature = "libdeflate",
+            feature = "miniz-c"
+        )))]
+        "level1" => panic!(
+            "level1 needs one of the features og, ng, rs, cloudflare, miniz, libdeflate or miniz-c"
+        ),
+        #[cfg(any(
+            feature = "og",
+            feature = "ng",
+            feature = "rs",
+            feature = "cloudflare",
+            feature = "miniz",
+            feature = "libdeflate",
+            feature = "miniz-c"
+        ))]
+        "levels" => {
+            let path = it.next().unwrap();
+            let mut levels: Vec<i32> = it.map(|level| level.parse().unwrap()).collect();
+            if levels.is_empty() {
+                // the default level, and levels that zlib rejects
+                levels = vec![-1, -2, -10, 10];
+            }
+
+            return levels::levels_all(&path, &levels);
+        }
+        #[cfg(not(any(
+            feature = "og",
+            feature = "ng",
+            feature = "rs",
+            feature = "cloudflare",
+            feature = "miniz",
+            feature = "libdeflate",
+            feature = "miniz-c"
+        )))]
+        "levels" => panic!(
+            "levels needs one of the features og, ng, rs, cloudflare, miniz, libdeflate or miniz-c"
+        ),
+        "ng-versions" => {
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+            let mut versions: Vec<String> = it.collect();
+            if versions.is_empty() {
+                versions = ng_versions::DEFAULT_VERSIONS.map(String::from).to_vec();
+            }
+
+            return ng_versions::ng_versions_all(mode, &path, level, &versions);
+        }
+        "ng-compat" => {
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+            let mut libraries: Vec<String> = it.collect();
+            if libraries.is_empty() {
+                libraries = vec![ng_versions::DEFAULT_VERSIONS.last().unwrap().to_string()];
+            }
+
+            return ng_versions::ng_compat_all(mode, &path, level, &libraries);
+        }
+        #[cfg(feature = "rs")]
+        "ng-simd" => {
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+            let version = match it.next() {
+                Some(version) => version,
+                None => ng_versions::DEFAULT_VERSIONS.last().unwrap().to_string(),
+            };
+
+            return ng_versions::ng_simd_all(mode, &path, level, &version);
+        }
+        #[cfg(not(feature = "rs"))]
+        "ng-simd" => panic!("ng-simd compares with zlib-rs, build with `--features rs`"),
+        "orchestrate" => {
+            const USAGE: &str = "usage: orchestrate <hosts> inflate-all <path> [options], or orchestrate <hosts> deflate-all <level> <path> [options]";
+
+            let args: Vec<String> = it.collect();
+            let (hosts, path) = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+                [hosts, "inflate-all", path, ..] | [hosts, "deflate-all", _, path, ..] => (
+                    hosts.split(',').map(String::from).collect::<Vec<_>>(),
+                    path.to_string(),
+                ),
+                _ => {
+                    eprintln!("{USAGE}");
+                    std::process::exit(1);
+                }
+            };
+
+            return orchestrate::orchestrate_all(&hosts, &path, &args[1..]);
+        }
+        "qemu" => {
+            let arch = it.next().unwrap();
+            let path = it.next().unwrap();
+
+            return qemu::qemu_all(&arch, &path);
+        }
+        #[cfg(feature = "rs")]
+        "rs-features" => {
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+            let features: Vec<String> = it.collect();
+
+            return rs_features::rs_features_all(mode, &path, level, &features);
+        }
+        #[cfg(not(feature = "rs"))]
+        "rs-features" => panic!("rs-features compares with zlib-rs, build with `--features rs`"),
+        "run" => {
+            let sandbox = match it.next().as_deref() {
+                Some("--sandbox") => it.next().unwrap(),
+                other => panic!("expected --sandbox, got {other:?}"),
+            };
+            let args: Vec<String> = it.collect();
+
+            return sandbox::run(&sandbox, &args);
+        }
+        "serve" => {
+            let address = it.next().unwrap_or_else(|| "127.0.0.1:8080".to_string());
+
+            return serve::serve(&address);
+        }
+        "size" => {
+            let binary = match it.next() {
+                Some(binary) => binary,
+                None => std::env::current_exe().unwrap().display().to_string(),
+            };
+
+            return size::size_all(&binary);
+        }
+        "tools" => {
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+
+            return tools::tools_all(mode, &path, level);
+        }
+        #[cfg(feature = "wasm")]
+        "wasm" => {
+            let (mode, level) = match it.next().unwrap().as_str() {
+                "inflate" => (Mode::Inflate, 0),
+                "deflate" => (Mode::Deflate, it.next().unwrap().parse().unwrap()),
+                other => panic!("invalid mode {other:?}"),
+            };
+            let path = it.next().unwrap();
+            let module = it.next();
+
+            return wasm::wasm_all(mode, &path, level, module.as_deref());
+        }
+        #[cfg(not(feature = "wasm"))]
+        "wasm" => panic!("wasmtime is not available, build with `--features wasm`"),
+        #[cfg(feature = "rs")]
+        "watch" => {
+            let checkout = it.next().unwrap();
+            let path = it.next().unwrap();
+            let level = match it.next() {
+                Some(level) => level.parse().unwrap(),
+                None => 6,
+            };
+
+            return watch::watch_all(&checkout, &path, level);
+        }
+        #[cfg(not(feature = "rs"))]
+        "watch" => panic!("watch compares with zlib-rs, build with `--features rs`"),
+        other => panic!("invalid mode {other:?}"),
+    };
+
+    let levels = match mode {
+        Mode::Inflate => vec![0],
+        Mode::Deflate => level_args(&mut it),
+    };
+
+    let implementation = it.next().unwrap().to_string();
+    let path = it.next().unwrap();
+    let runs = Runs::parse(it);
+    set_window_bits(runs.window_bits);
+    set_chunks(runs.chunk_in, runs.chunk_out);
+    let whole = runs.bytes.is_none() && runs.target_size.is_none();
+    assert!(matches!(mode, Mode::Deflate) || whole, "{INFLATE_INPUT}");
+    set_input(runs.bytes, runs.target_size);
+
+    let (level, strategy, mem_level) = match (&levels[..], runs.strategies, runs.mem_levels) {
+        (&[level], &[strategy], &[mem_level]) => (level, strategy, mem_level),
+        _ => return sweep(&implementation, &path, &levels, runs),
+    };
+    set_strategy(strategy);
+    set_mem_level(mem_level);
+
+    if implementation == "all" {
+        return single_all(mode, &path, level, runs);
+    }
+
+    match implementation.as_str() {
+        #[cfg(feature = "og")]
+        "og" => single::<ZlibOg>(mode, &path, level, runs),
+        #[cfg(feature = "ng")]
+        "ng" => single::<ZlibNg>(mode, &path, level, runs),
+        #[cfg(feature = "rs")]
+        "rs" => single::<ZlibRs>(mode, &path, level, runs),
+        #[cfg(feature = "cloudflare")]
+        "cloudflare" => single::<ZlibCloudflare>(mode, &path, level, runs),
+        #[cfg(feature = "miniz")]
+        "miniz" => single::<MinizOxide>(mode, &path, level, runs),
+        #[cfg(feature = "libdeflate")]
+        "libdeflate" => single::<Libdeflate>(mode, &path, level, runs),
+        #[cfg(feature = "miniz-c")]
+        "miniz-c" => single::<MinizC>(mode, &path, level, runs),
+        #[cfg(feature = "flate2")]
+        "flate2" => single::<Flate2>(mode, &path, level, runs),
+        #[cfg(feature = "classic")]
+        "classic" => single::<Classic>(mode, &path, level, runs),
+        #[cfg(feature = "yazi")]
+        "yazi" => single::<Yazi>(mode, &path, level, runs),
+        "system" => single::<dylib::SystemZlib>(mode, &path, level, runs),
+        "og-local" => match og_local::load() {
+            Ok(library) => {
+                dylib::select(library);
+                single::<dylib::ZlibDylib>(mode, &path, level, runs)
+            }
+            Err(e) => panic!("og-local: {e}"),
+        },
+        #[cfg(feature = "zune")]
+        "zune" => single::<ZuneInflate>(mode, &path, level, runs),
+        "isal" => single::<isal::Isal>(mode, &path, level, runs),
+        #[cfg(feature = "zopfli")]
+        "zopfli" => single::<Zopfli>(mode, &path, level, runs),
+        #[cfg(target_os = "macos")]
+        "libcompression" => single::<libcompression::LibCompression>(mode, &path, level, runs),
+        other => match other.strip_prefix("dylib:") {
+            Some(library) => {
+                match dylib::Library::open_any(library) {
+                    Ok(library) => dylib::select(library),
+                    Err(e) => panic!("{library}: {e}"),
+                }
+                single::<dylib::ZlibDylib>(mode, &path, level, runs)
+            }
+            None => match registry::single(other, mode, &path, level, runs) {
+                Some(output_len) => output_len,
+                None => match FEATURES.iter().any(|(n, _)| *n == other) {
+                    true => panic!("{other} is not compiled in, build with `--features {other}`"),
+                    false => panic!("invalid implementation: {other:?}"),
+                },
+            },
+        },
+    };
+}
+
+/// Flags accepted after the positional arguments of the `*-all` commands
+#[derive(Debug, Default)]
+struct Options {
+    /// `--profile perf`: run every implementation under `perf record` after timing it
+    profile: bool,
+    /// `--strict-env`: refuse to run when the environment check finds a source of noise
+    strict_env: bool,
+    /// `--mem-bandwidth <GB/s>`: the machine's memory bandwidth, measured when not given
+    mem_bandwidth: Option<f64>,
+    /// `--guard front|back`: place the buffers flush against a guard page
+    guard: Option<Guard>,
+    /// `--samples <path>`: write the timing (and counters) of every iteration to a CSV file
+    samples: Option<String>,
+    /// `--jsonl <path>`: write a JSON line per completed implementation (`-` for stdout)
+    jsonl: Option<String>,
+    /// `--pushgateway <host:port>`: push every completed implementation to a Prometheus pushgateway
+    pushgateway: Option<String>,
+    /// `--discard-outliers`: leave outlier iterations out of the statistics, rather than only
+    /// counting them
+    discard_outliers: bool,
+    /// `--strategy <strategy>|all`: deflate with these strategies rather than the default one
+    strategies: Option<&'static [Strategy]>,
+    /// `--window-bits <n>`: the window bits (and so the wrapper) rather than 15, the zlib wrapper
+    window_bits: Option<i32>,
+    /// `--mem-level <n>|all`: deflate with these memory levels rather than 8
+    mem_levels: Option<&'static [i32]>,
+    /// `--max-time <duration>`: rather than 5 iterations, as many as fit in this time
+    max_time: Option<std::time::Duration>,
+    /// `--isolate`: measure every implementation in a fresh process
+    isolate: bool,
+    /// `--threads <n>`: measure how the throughput scales with `n` concurrent streams instead
+    threads: Option<usize>,
+    /// `--chunk-in <bytes>`: feed the input to the stream in pieces of this size
+    chunk_in: Option<usize>,
+    /// `--chunk-out <bytes>`: drain the output from the stream in pieces of this size
+    chunk_out: Option<usize>,
+    /// `--baseline <implementation>`: what the others are compared with over a corpus directory
+    baseline: Option<String>,
+    /// `--bytes <n>`: only use the first `n` bytes of the input
+    bytes: Option<usize>,
+    /// `--target-size <n>`: repeat the input until it is `n` bytes long
+    target_size: Option<usize>,
+    /// `--timeout <duration>`: give up on an implementation that takes longer, implies `--isolate`
+    timeout: Option<std::time::Duration>,
+    /// `--inflate-hash <hash>`: what the children of `--isolate` check the output against
+    inflate_hash: Option<u64>,
+}
+
+impl Options {
+    fn parse(mut it: impl Iterator<Item = String>) -> Self {
+        let mut options = Options::default();
+
+        while let Some(arg) = it.next() {
+            match arg.as_str() {
+                "--profile" => match it.next().as_deref() {
+                    Some("perf") => options.profile = true,
+                    other => panic!("invalid profiler {other:?}"),
+                },
+                "--strict-env" => options.strict_env = true,
+                "--mem-bandwidth" => {
+                    let value = it.next().unwrap();
+                    options.mem_bandwidth = Some(value.parse().unwrap());
+                }
+                "--guard" => options.guard = Some(Guard::parse(&it.next().unwrap())),
+                "--samples" => options.samples = Some(it.next().unwrap()),
+                "--jsonl" => options.jsonl = Some(it.next().unwrap()),
+                "--pushgateway" => options.pushgateway = Some(it.next().unwrap()),
+                "--discard-outliers" => options.discard_outliers = true,
+                "--strategy" => options.strategies = Some(Strategy::parse(&it.next().unwrap())),
+                "--window-bits" => options.window_bits = Some(it.next().unwrap().parse().unwrap()),
+                "--mem-level" => options.mem_levels = Some(parse_mem_levels(&it.next().unwrap())),
+                "--max-time" => options.max_time = Some(parse_duration(&it.next().unwrap())),
+                "--isolate" => options.isolate = true,
+                "--threads" => {
+                    let threads = it.next().unwrap().parse().unwrap();
+                    assert!(threads > 0, "--threads must be at least 1");
+                    options.threads = Some(threads);
+                }
+                "--chunk-in" => options.chunk_in = Some(parse_chunk(&it.next().unwrap())),
+                "--chunk-out" => options.chunk_out = Some(parse_chunk(&it.next().unwrap())),
+                "--baseline" => options.baseline = Some(it.next().unwrap()),
+                "--bytes" => options.bytes = Some(parse_bytes(&it.next().unwrap())),
+                "--target-size" => {
+                    options.target_size = Some(parse_bytes(&it.next().unwrap()));
+                }
+                "--inflate-hash" => {
+                    options.inflate_hash = Some(it.next().unwrap().parse().unwrap())
+                }
+                "--timeout" => {
+                    // only a child process can be stopped in the middle of a call
+                    options.timeout = Some(parse_duration(&it.next().unwrap()));
+                    options.isolate = true;
+                }
+                other => panic!("invalid argument {other:?}"),
+            }
+        }
+
+        options
+    }
+}
+
+/// With `--guard`, the output buffer must be exactly as large as the output for overruns to fault,
+/// so we first do an untimed run with plain heap buffers to find out how large that is.
+fn allocation(
+    options: &Options,
+    name: &str,
+    f: Function,
+    mode: Mode,
+    path: &str,
+    level: i32,
+) -> Allocation {
+    match options.guard {
+        None => Allocation::HEAP,
+        Some(guard) => {
+            let output_len = f(mode, path, level, Allocation::HEAP);
+
+            // a fault kills the process, so announce what is running
+            eprintln!("# guard pages ({guard:?}): {name}");
+
+            Allocation {
+                guard: Some(guard),
+                output_len,
+            }
+        }
+    }
+}
+
+/// Check (and record) the environment before a timing-sensitive run.
+fn check_environment(options: &Options) {
+    let findings = environment::check();
+    environment::print(&findings);
+
+    if options.strict_env && findings.iter().any(|f| f.noisy) {
+        eprintln!("refusing to run: the environment is noisy (see above)");
+        std::process::exit(1);
+    }
+}
+
+/// The zlib wrapper with the default window, what `helper` inflates
+fn inflate_config() -> InflateConfig {
+    InflateConfig {
+        window_bits: window_bits(),
+    }
+}
+
+/// zlib's defaults (by default with the zlib wrapper), what `helper` deflates with
+fn deflate_config(level: i32) -> DeflateConfig {
+    DeflateConfig {
+        level,
+        method: Method::Deflated,
+        window_bits: window_bits(),
+        mem_level: mem_level(),
+        strategy: strategy(),
+    }
+}
+
+/// Levels given as e.g. `6`, `1-9`, or `1,6,9` (or a mix, `0-3,9`)
+fn parse_levels(levels: &str) -> Vec<i32> {
+    let parse = |level: &str| match level.parse() {
+        Ok(level) => level,
+        Err(e) => panic!("invalid level {level:?}: {e}"),
+    };
+
+    let mut parsed = Vec::new();
+    for part in levels.split(',') {
+        // a leading `-` is the sign of a single (negative) level
+        let range = part.get(1..).and_then(|rest| rest.find('-'));
+        match range.map(|i| part.split_at(i + 1)) {
+            Some((start, end)) => parsed.extend(parse(start)..=parse(&end[1..])),
+            None => parsed.push(parse(part)),
+        }
+    }
+
+    parsed
+}
+
+/// The size of the pieces of `--chunk-in` and `--chunk-out`, in bytes
+fn parse_chunk(chunk: &str) -> usize {
+    match chunk.parse() {
+        Ok(0) | Err(_) => panic!("invalid chunk size {chunk:?}, e.g. 4096"),
+        Ok(chunk) => chunk,
+    }
+}
+
+/// The value of `--bytes` and `--target-size`, a number of bytes, optionally with a `K`, `M` or `G` suffix (powers of
+/// 1024)
+fn parse_bytes(bytes: &str) -> usize {
+    let (number, scale) = match bytes.char_indices().last() {
+        Some((i, 'K')) => (&bytes[..i], 1 << 10),
+        Some((i, 'M')) => (&bytes[..i], 1 << 20),
+        Some((i, 'G')) => (&bytes[..i], 1 << 30),
+        _ => (bytes, 1),
+    };
+
+    match number.parse::<usize>() {
+        Ok(n) if n > 0 => n * scale,
+        _ => panic!("invalid number of bytes {bytes:?}, e.g. 65536 or 64K"),
+    }
+}
+
+/// A duration given as e.g. `5s`, `500ms`, `2m`, or `1.5` (seconds)
+fn parse_duration(duration: &str) -> std::time::Duration {
+    let (number, scale) = if let Some(number) = duration.strip_suffix("ms") {
+        (number, 1e-3)
+    } else if let Some(number) = duration.strip_suffix('s') {
+        (number, 1.0)
+    } else if let Some(number) = duration.strip_suffix('m') {
+        (number, 60.0)
+    } else {
+        (duration, 1.0)
+    };
+
+    match number.parse::<f64>() {
+        Ok(value) if value >= 0.0 => std::time::Duration::from_secs_f64(value * scale),
+        _ => panic!("invalid duration {duration:?}, e.g. 5s or 500ms"),
+    }
+}
+
+/// The number of runs with `--max-time`, even when the first ones use up the time budget
+const MIN_RUNS: usize = 3;
+
+/// Whether to run again after `done` runs that took `elapsed`: `n` runs, or with `--max-time` as
+/// many as fit in that time (but at least `MIN_RUNS`)
+fn another_run(
+    done: usize,
+    n: usize,
+    max_time: Option<std::time::Duration>,
+    elapsed: std::time::Duration,
+) -> bool {
+    match max_time {
+        None => done < n,
+        Some(max_time) => done < MIN_RUNS || elapsed < max_time,
+    }
+}
+
+/// The indices `0..len`, starting at a different one every round, so that no implementation always
+/// runs first or last
+fn rotation(round: usize, len: usize) -> impl Iterator<Item = usize> {
+    (0..len).map(move |i| (round + i) % len)
+}
+
+/// Call `step` with the indices `0..len` in rounds (see `rotation`), until a round where it returns
+/// false (did not run) for all of them
+fn interleave(len: usize, mut step: impl FnMut(usize) -> bool) {
+    for round in 0.. {
+        let mut ran = false;
+        for i in rotation(round, len) {
+            ran |= step(i);
+        }
+
+        if !ran {
+            break;
+        }
+    }
+}
+
+/// The level of `deflate` and `deflate-all`, or several with `--levels <levels>` in its place
+fn level_args(it: &mut impl Iterator<Item = String>) -> Vec<i32> {
+    match it.next().unwrap().as_str() {
+        "--levels" => parse_levels(&it.next().unwrap()),
+        level => vec![level.parse().unwrap()],
+    }
+}
+
+/// The optional arguments of `ab` and `compare`: the number of runs (10 by default), and
+/// `--alpha <a>`, the significance level of the t-test (0.05 by default)
+fn comparison_args(mut it: impl Iterator<Item = String>) -> (usize, f64) {
+    let (mut runs, mut alpha) = (10, 0.05);
+
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--alpha" => alpha = it.next().unwrap().parse().unwrap(),
+            other => runs = other.parse().unwrap(),
+        }
+    }
+
+    assert!(runs >= 2, "the t-test needs at least 2 runs");
+
+    (runs, alpha)
+}
+
+/// `--warmup <n>`, `--runs <n>`, `--max-time <duration>`, `--discard-outliers`,
+/// `--strategy <strategy>|all`, `--window-bits <n>`, `--mem-level <n>|all`, `--chunk-in <bytes>`,
+/// `--chunk-out <bytes>`, `--bytes <n>` and `--target-size <n>` of the single-run commands
+#[derive(Debug, Clone, Copy)]
+struct Runs {
+    warmup: usize,
+    runs: usize,
+    /// rather than `runs`, run until this much time has passed
+    max_time: Option<std::time::Duration>,
+    discard_outliers: bool,
+    /// whether a timing flag was given; otherwise there is one untimed run, for external tools
+    timed: bool,
+    strategies: &'static [Strategy],
+    window_bits: i32,
+    mem_levels: &'static [i32],
+    chunk_in: Option<usize>,
+    chunk_out: Option<usize>,
+    bytes: Option<usize>,
+    target_size: Option<usize>,
+}
+
+impl Runs {
+    fn parse(mut it: impl Iterator<Item = String>) -> Self {
+        let mut runs = Runs {
+            warmup: 0,
+            runs: 1,
+            max_time: None,
+            discard_outliers: false,
+            timed: false,
+            strategies: &[Strategy::Default],
+            window_bits: 15,
+            mem_levels: &[8],
+            chunk_in: None,
+            chunk_out: None,
+            bytes: None,
+            target_size: None,
+        };
+
+        while let Some(arg) = it.next() {
+            let mut value = || it.next().unwrap();
+            match arg.as_str() {
+                "--warmup" => runs.warmup = value().parse().unwrap(),
+                "--runs" => runs.runs = value().parse().unwrap(),
+                "--max-time" => runs.max_time = Some(parse_duration(&value())),
+                "--discard-outliers" => runs.discard_outliers = true,
+                "--strategy" => runs.strategies = Strategy::parse(&value()),
+                "--window-bits" => runs.window_bits = value().parse().unwrap(),
+                "--mem-level" => runs.mem_levels = parse_mem_levels(&value()),
+                "--chunk-in" => runs.chunk_in = Some(parse_chunk(&value())),
+                "--chunk-out" => runs.chunk_out = Some(parse_chunk(&value())),
+                "--bytes" => runs.bytes = Some(parse_bytes(&value())),
+                "--target-size" => runs.target_size = Some(parse_bytes(&value())),
+                other => panic!("invalid argument {other:?}"),
+            }
+            let timing = ["--warmup", "--runs", "--max-time", "--discard-outliers"];
+            runs.timed |= timing.contains(&arg.as_str());
+        }
+
+        assert!(runs.runs > 0, "--runs must be at least 1");
+
+        runs
+    }
+}
+
+/// Run `repeat` once, or exit when the implementation does not support this mode or level
+fn single<T: DeflateImplementation>(mode: Mode, path: &str, level: i32, runs: Runs) -> usize {
+    if let Err(reason) = T::CAPABILITIES.check(mode, level) {
+        eprintln!("{}: {reason}", T::name());
+        std::process::exit(1);
+    }
+
+    repeat::<T>(mode, path, level, runs)
+}
+
+/// Run `helper` once, or with `--warmup`/`--runs` time every run. The input is read and the output
+/// allocated once, so a run is only the call that (de)compresses.
+fn repeat<T: DeflateImplementation>(mode: Mode, path: &str, level: i32, runs: Runs) -> usize {
+    if !runs.timed {
+        return helper::<T>(mode, path, level, Allocation::HEAP);
+    }
+
+    let Ok(input) = read_input(path) else {
+        panic!("error opening {path:?}")
+    };
+    let mut output = output_buffer();
+
+    let mut run = || time_call(call::<T>, mode, &input, &mut output, level);
+
+    for _ in 0..runs.warmup {
+        run();
+    }
+
+    let mb = input.len() as f64 / 1e6;
+    let mut deltas = Vec::with_capacity(runs.runs);
+    let mut output_len = 0;
+
+    println!("run, ms, MB/s");
+    let start = std::time::Instant::now();
+    for i in 0.. {
+        if !another_run(i, runs.runs, runs.max_time, start.elapsed()) {
+            break;
+        }
+
+        let (delta, len) = run();
+        println!(
+            "{i}, {:.3}, {:.2}",
+            delta.as_secs_f64() * 1e3,
+            mb / delta.as_secs_f64()
+        );
+
+        deltas.push(delta);
+        output_len = len;
+    }
+
+    let ms: Vec<_> = deltas.iter().map(|d| d.as_secs_f64() * 1e3).collect();
+    let mb_per_s: Vec<_> = deltas.iter().map(|d| mb / d.as_secs_f64()).collect();
+
+    println!(
+        "# {} runs after {} warmup runs, {output_len} output bytes",
+        deltas.len(),
+        runs.warmup
+    );
+    let outliers = stats::outliers(&ms);
+    let discard = runs.discard_outliers;
+    for (i, _) in outliers.iter().enumerate().filter(|(_, outlier)| **outlier) {
+        let action = if discard { "discarded" } else { "kept" };
+        println!("# run {i} is an outlier ({action})");
+    }
+
+    println!("unit, {}", stats::Summary::HEADER);
+    let summary = stats::Summary::of(&ms, &outliers, discard);
+    println!("ms, {}", summary.columns());
+    let summary = stats::Summary::of(&mb_per_s, &outliers, discard);
+    println!("MB/s, {}", summary.columns());
+
+    output_len
+}
+
+/// Run every one of `calls` as `--warmup` and `--runs` say, with their runs interleaved, and return
+/// the median time and the output size of each. Like in `repeat`, the input is read once and a run
+/// only times the call.
+fn median_runs(
+    calls: &[Call],
+    mode: Mode,
+    path: &str,
+    level: i32,
+    runs: Runs,
+) -> Vec<(f64, usize)> {
+    let Ok(input) = read_input(path) else {
+        panic!("error opening {path:?}")
+    };
+    let mut output = output_buffer();
+
+    for &call in calls {
+        for _ in 0..runs.warmup {
+            time_call(call, mode, &input, &mut output, level);
+        }
+    }
+
+    let mut deltas = vec![Vec::with_capacity(runs.runs); calls.len()];
+    let mut output_lens = vec![0; calls.len()];
+    interleave(calls.len(), |i| {
+        let elapsed = deltas[i].iter().sum();
+        if !another_run(deltas[i].len(), runs.runs, runs.max_time, elapsed) {
+            return false;
+        }
+
+        let (delta, output_len) = time_call(calls[i], mode, &input, &mut output, level);
+        deltas[i].push(delta);
+        output_lens[i] = output_len;
+        true
+    });
+
+    let medians = deltas
+        .into_iter()
+        .map(|mut deltas: Vec<std::time::Duration>| {
+            deltas.sort();
+            deltas[deltas.len() / 2].as_secs_f64()
+        });
+    medians.zip(output_lens).collect()
+}
+
+/// `all` as the implementation: run every implementation that the `*-all` commands measure, with
+/// their runs interleaved, and print them from fastest to slowest
+fn single_all(mode: Mode, path: &str, level: i32, runs: Runs) {
+    let mb = input_len(path) as f64 / 1e6;
+
+    let mut names = Vec::new();
+    let mut calls = Vec::new();
+    for (name, _, call, capabilities, _, _) in
+        FUNCTIONS.iter().copied().chain(registry::registered())
+    {
+        if let Err(reason) = capabilities.check(mode, level) {
+            println!("# {name}: skipped, {reason}");
+            continue;
+        }
+
+        names.push(name);
+        calls.push(call);
+    }
+
+    let medians = median_runs(&calls, mode, path, level, runs);
+    let mut rows: Vec<_> = (names.into_iter().zip(medians))
+        .map(|(name, (seconds, output_len))| (name, mb / seconds, output_len))
+        .collect();
+
+    rows.sort_by(|(_, a, _), (_, b, _)| b.total_cmp(a));
+    let Some(&(_, fastest, _)) = rows.first() else {
+        return;
+    };
+
+    println!("implementation, MB/s, % of fastest, output bytes");
+    for (name, mb_per_s, output_len) in rows {
+        let relative = 100.0 * mb_per_s / fastest;
+        println!("{name}, {mb_per_s:.2}, {relative:.1}, {output_len}");
+    }
+}
+
+/// `deflate --levels`, `--strategy all` or `--mem-level all`: the speed and ratio of an
+/// implementation (or `all`) at every level, strategy and memory level
+fn sweep(implementation: &str, path: &str, levels: &[i32], runs: Runs) {
+    let selected: Vec<_> = match implementation {
+        "all" => FUNCTIONS
+            .iter()
+            .copied()
+            .chain(registry::registered())
+            .collect(),
+        name => match implementations().find(|(n, _, _, _, _, _)| *n == name) {
+            Some(implementation) => vec![implementation],
+            None => panic!("invalid implementation: {name:?}"),
+        },
+    };
+
+    let bytes = input_len(path) as f64;
+
+    println!("implementation, level, strategy, mem level, MB/s, output bytes, ratio");
+    for (name, _, call, capabilities, _, _) in selected {
+        for (strategy, mem_level) in deflate_settings(runs.strategies, runs.mem_levels) {
+            set_strategy(strategy);
+            set_mem_level(mem_level);
+            let strategy = strategy.name();
+
+            for &level in levels {
+                if let Err(reason) = capabilities.check(Mode::Deflate, level) {
+                    let config =
+                        format!("level {level}, strategy {strategy}, mem level {mem_level}");
+                    println!("# {name} at {config}: skipped, {reason}");
+                    continue;
+                }
+
+                let (seconds, output_len) =
+                    median_runs(&[call], Mode::Deflate, path, level, runs)[0];
+                println!(
+                    "{name}, {level}, {strategy}, {mem_level}, {:.2}, {output_len}, {:.3}",
+                    bytes / 1e6 / seconds,
+                    bytes / output_len as f64,
+                );
+            }
+        }
+    }
+}
+
+/// Returns the size of the output in bytes
+fn helper<T: DeflateImplementation>(
+    mode: Mode,
+    path: &str,
+    level: i32,
+    allocation: Allocation,
+) -> usize {
+    let mut output = Buffer::new(allocation.output_len, allocation.guard);
+    let Ok(input) = read_input(path) else {
+        panic!("error opening {path:?}")
+    };
+    let input = Buffer::from_vec(input, allocation.guard);
+
+    // println!( "performing {mode:?} at level {level} using method {}", T::NAME);
+
+    match mode {
+        Mode::Inflate => {
+            let (output, res) = uncompress::<T>(&mut output, &input, inflate_config());
+            assert_eq!(res, ReturnCode::Ok);
+
+            assert_eq!(output_hash(output), inflate_hash());
+
+            output.len()
+        }
+        Mode::Deflate => {
+            let (output, res) = compress::<T>(&mut output, &input, deflate_config(level));
+            assert_eq!(res, ReturnCode::Ok);
+
+            output.len()
+        }
+    }
+}
+
+type Function = fn(Mode, &str, i32, Allocation) -> usize;
+
+/// Compress or decompress `input` into `output`, and return the size of the output. Unlike `helper`
+/// this is only the call, so that a run can be timed on an input that was read once, into an output
+/// buffer that is reused.
+fn call<T: DeflateImplementation>(
+    mode: Mode,
+    input: &[u8],
+    output: &mut [u8],
+    level: i32,
+) -> usize {
+    let (output, res) = match mode {
+        Mode::Inflate => uncompress::<T>(output, input, inflate_config()),
+        Mode::Deflate => compress::<T>(output, input, deflate_config(level)),
+    };
+    assert_eq!(res, ReturnCode::Ok);
+
+    output.len()
+}
+
+type Call = fn(Mode, &[u8], &mut [u8], i32) -> usize;
+
+/// The output buffer of `call`, allocated once and reused for every run
+fn output_buffer() -> Buffer {
+    Buffer::new(Allocation::HEAP.output_len, Allocation::HEAP.guard)
+}
+
+/// Time one `call`, returns the time and the size of the output
+fn time_call(
+    call: Call,
+    mode: Mode,
+    input: &[u8],
+    output: &mut [u8],
+    level: i32,
+) -> (std::time::Duration, usize) {
+    let start = timer::now();
+    let output_len = call(mode, input, output, level);
+
+    (start.elapsed(), output_len)
+}
+
+/// An implementation that can be run: its name, `helper`, `call`, what it supports, its version,
+/// and how it was built
+type Implementation = (
+    &'static str,
+    Function,
+    Call,
+    Capabilities,
+    fn() -> String,
+    fn() -> String,
+);
+
+/// Every implementation that the `*-all` commands measure (the ones that are compiled in)
+const FUNCTIONS: &[Implementation] = &[
+    #[cfg(feature = "og")]
+    (
+        "og",
+        helper::<ZlibOg> as _,
+        call::<ZlibOg>,
+        ZlibOg::CAPABILITIES,
+        ZlibOg::version,
+        ZlibOg::build,
+    ),
+    #[cfg(feature = "ng")]
+    (
+        "ng",
+        helper::<ZlibNg> as _,
+        call::<ZlibNg>,
+        ZlibNg::CAPABILITIES,
+        ZlibNg::version,
+        ZlibNg::build,
+    ),
+    #[cfg(feature = "rs")]
+    (
+        "rs",
+        helper::<ZlibRs> as _,
+        call::<ZlibRs>,
+        ZlibRs::CAPABILITIES,
+        ZlibRs::version,
+        ZlibRs::build,
+    ),
+    #[cfg(feature = "cloudflare")]
+    (
+        "cloudflare",
+        helper::<ZlibCloudflare> as _,
+        call::<ZlibCloudflare>,
+        ZlibCloudflare::CAPABILITIES,
+        ZlibCloudflare::version,
+        ZlibCloudflare::build,
+    ),
+    #[cfg(feature = "miniz")]
+    (
+        "miniz",
+        helper::<MinizOxide> as _,
+        call::<MinizOxide>,
+        MinizOxide::CAPABILITIES,
+        MinizOxide::version,
+        MinizOxide::build,
+    ),
+    #[cfg(feature = "libdeflate")]
+    (
+        "libdeflate",
+        helper::<Libdeflate> as _,
+        call::<Libdeflate>,
+        Libdeflate::CAPABILITIES,
+        Libdeflate::version,
+        Libdeflate::build,
+    ),
+    #[cfg(feature = "miniz-c")]
+    (
+        "miniz-c",
+        helper::<MinizC> as _,
+        call::<MinizC>,
+        MinizC::CAPABILITIES,
+        MinizC::version,
+        MinizC::build,
+    ),
+    #[cfg(feature = "flate2")]
+    (
+        FLATE2,
+        helper::<Flate2> as _,
+        call::<Flate2>,
+        Flate2::CAPABILITIES,
+        Flate2::version,
+        Flate2::build,
+    ),
+    #[cfg(feature = "classic")]
+    (
+        "classic",
+        helper::<Classic> as _,
+        call::<Classic>,
+        Classic::CAPABILITIES,
+        Classic::version,
+        Classic::build,
+    ),
+    #[cfg(feature = "yazi")]
+    (
+        "yazi",
+        helper::<Yazi> as _,
+        call::<Yazi>,
+        Yazi::CAPABILITIES,
+        Yazi::version,
+        Yazi::build,
+    ),
+    (
+        "system",
+        helper::<dylib::SystemZlib> as _,
+        call::<dylib::SystemZlib>,
+        dylib::SystemZlib::CAPABILITIES,
+        dylib::SystemZlib::version,
+        dylib::SystemZlib::build,
+    ),
+];
+
+/// The built-in implementations that are behind a cargo feature of the same name, and whether they
+/// are compiled in
+const FEATURES: [(&str, bool); 12] = [
+    ("og", cfg!(feature = "og")),
+    ("ng", cfg!(feature = "ng")),
+    ("rs", cfg!(feature = "rs")),
+    ("cloudflare", cfg!(feature = "cloudflare")),
+    ("miniz", cfg!(feature = "miniz")),
+    ("libdeflate", cfg!(feature = "libdeflate")),
+    ("miniz-c", cfg!(feature = "miniz-c")),
+    ("flate2", cfg!(feature = "flate2")),
+    ("classic", cfg!(feature = "classic")),
+    ("yazi", cfg!(feature = "yazi")),
+    ("zopfli", cfg!(feature = "zopfli")),
+    ("zune", cfg!(feature = "zune")),
+];
+
+/// The implementations that are only run on their own, not by the `*-all` commands
+const SINGLE: &[Implementation] = &[
+    #[cfg(feature = "zopfli")]
+    (
+        "zopfli",
+        helper::<Zopfli>,
+        call::<Zopfli>,
+        Zopfli::CAPABILITIES,
+        Zopfli::version,
+        Zopfli::build,
+    ),
+    #[cfg(feature = "zune")]
+    (
+        "zune",
+        helper::<ZuneInflate>,
+        call::<ZuneInflate>,
+        ZuneInflate::CAPABILITIES,
+        ZuneInflate::version,
+        ZuneInflate::build,
+    ),
+    (
+        "isal",
+        helper::<isal::Isal>,
+        call::<isal::Isal>,
+        isal::Isal::CAPABILITIES,
+        isal::Isal::version,
+        isal::Isal::build,
+    ),
+    #[cfg(target_os = "macos")]
+    (
+        "libcompression",
+        helper::<libcompression::LibCompression>,
+        call::<libcompression::LibCompression>,
+        libcompression::LibCompression::CAPABILITIES,
+        libcompression::LibCompression::version,
+        libcompression::LibCompression::build,
+    ),
+];
+
+/// Every implementation that is compiled in or registered
+fn implementations() -> impl Iterator<Item = Implementation> {
+    let built_in = FUNCTIONS.iter().chain(SINGLE).copied();
+    built_in.chain(registry::registered())
+}
+
+fn yes_no(supported: bool) -> &'static str {
+    match supported {
+        true => "yes",
+        false => "no",
+    }
+}
+
+/// Every implementation that can be run and its version, so that scripts can find out what is
+/// available
+fn list_all() {
+    println!("implementation, version, deflate, inflate");
+    for (name, _, _, capabilities, version, _) in implementations() {
+        println!(
+            "{name}, {}, {}, {}",
+            version(),
+            yes_no(capabilities.deflate),
+            yes_no(capabilities.inflate),
+        );
+    }
+}
+
+fn backends_all() {
+    // these load a library at runtime, and are always compiled in
+    println!(
+        "# always available: system, isal, dylib:<path>, og-local (with {})",
+        og_local::SOURCE_VARIABLE
+    );
+
+    println!("implementation, compiled in");
+    for (name, enabled) in FEATURES {
+        println!("{name}, {}", yes_no(enabled));
+    }
+
+    println!();
+    println!("implementation, deflate, inflate, max level, raw, zlib, gzip, dictionary, streaming, strategies, mem level");
+    for (name, _, _, c, _, _) in implementations() {
+        println!(
+            "{name}, {}, {}, {}, {}, {}, {}, {}, {}, {}, {}",
+            yes_no(c.deflate),
+            yes_no(c.inflate),
+            c.max_level,
+            yes_no(c.raw),
+            yes_no(c.zlib),
+            yes_no(c.gzip),
+            yes_no(c.dictionary),
+            yes_no(c.streaming),
+            yes_no(c.strategies),
+            yes_no(c.mem_level),
+        );
+    }
+}
+
+fn deflate_all(path: &str, level: i32, options: &Options) {
+    if std::path::Path::new(path).is_dir() {
+        return corpus::corpus_all(Mode::Deflate, path, level, options);
+    }
+    if let Some(threads) = options.threads {
+        return scaling::scaling_all(Mode::Deflate, path, level, threads, options);
+    }
+
+    run_all(Mode::Deflate, path, level, options, None);
+}
+
+fn inflate_all(path: &str, options: &Options) {
+    if std::path::Path::new(path).is_dir() {
+        return corpus::corpus_all(Mode::Inflate, path, 0, options);
+    }
+    if let Some(threads) = options.threads {
+        return scaling::scaling_all(Mode::Inflate, path, 0, threads, options);
+    }
+
+    run_all(Mode::Inflate, path, 0, options, None);
+}
+
+/// The (minor, major) page faults of this process so far
+#[cfg(unix)]
+fn page_faults() -> (u64, u64) {
+    let mut usage = MaybeUninit::<libc::rusage>::zeroed();
+    let err = unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) };
+    assert_eq!(err, 0);
+
+    let usage = unsafe { usage.assume_init() };
+    (usage.ru_minflt as u64, usage.ru_majflt as u64)
+}
+
+/// Without `getrusage`, no page faults are counted
+#[cfg(not(unix))]
+fn page_faults() -> (u64, u64) {
+    (0, 0)
+}
+
+/// Level 0 only wraps the input in stored blocks, so this measures framing overhead and copy speed.
+fn stored_all(path: &str, options: &Options) {
+    check_environment(options);
+
+    let n = 5;
+
+    let bytes = input_len(path);
+    let mb = bytes as f64 / 1_000_000.0;
+
+    // a stored block copies the input to the output, so memcpy is the upper bound
+    let bandwidth = match options.mem_bandwidth {
+        Some(bandwidth) => bandwidth,
+        None => environment::memory_bandwidth(),
+    };
+    let memcpy = bandwidth / 2.0 * 1000.0;
+    println!("# memcpy: {memcpy:.2} MB/s");
+
+    let mut runners = Vec::new();
+    let all = FUNCTIONS.iter().copied().chain(registry::registered());
+    for (name, f, _, capabilities, version, build) in all {
+        if let Err(reason) = capabilities.check(Mode::Deflate, 0) {
+            println!("# {name}: skipped, {reason}");
+            continue;
+        }
+
+        println!("# {name}: {}, {}", version(), build());
+
+        let allocation = allocation(options, name, f, Mode::Deflate, path, 0);
+        runners.push((name, f, allocation, std::time::Duration::ZERO, 0, 0));
+    }
+
+    // interleaved, like `run_all`
+    interleave(runners.len(), |i| {
+        let (_, f, allocation, elapsed, runs, output_len) = &mut runners[i];
+        if *runs == n {
+            return false;
+        }
+
+        let start = timer::now();
+        *output_len = f(Mode::Deflate, path, 0, *allocation);
+        *elapsed += start.elapsed();
+        *runs += 1;
+        true
+    });
+
+    println!("implementation, MB/s, % of memcpy, output bytes, overhead bytes, overhead %");
+    for (name, _, _, delta, _, output_len) in runners {
+        let throughput = n as f64 * mb / delta.as_secs_f64();
+        let overhead = output_len as i64 - bytes as i64;
+
+        println!(
+            "{name}, {throughput}, {:.1}, {output_len}, {overhead}, {:.4}",
+            100.0 * throughput / memcpy,
+            100.0 * overhead as f64 / bytes as f64,
+        );
+    }
+}
+
+/// The iterations of one implementation in `run_all`
+#[derive(Debug, Default)]
+struct Measurement {
+    deltas: Vec<std::time::Duration>,
+    samples: Vec<counters::Sample>,
+    output_len: usize,
+    /// the minor and major page faults of all iterations together
+    faults: (u64, u64),
+    /// the clock speed before every iteration, to notice throttling
+    clocks: Vec<f64>,
+}
+
+impl Measurement {
+    /// Run and time one more iteration. Only the call is timed and counted, on `buffers`. With guard
+    /// pages, the guarded copies are set up and faulted in before the timing starts.
+    fn iteration(
+        &mut self,
+        call: Call,
+        mode: Mode,
+        level: i32,
+        buffers: &mut Buffers,
+        allocation: Allocation,
+        counters: &Result<counters::Counters, String>,
+    ) {
+        let mut guarded = allocation.guard.map(|guard| {
+            let input = Buffer::from_vec(buffers.input.clone(), Some(guard));
+            let mut output = Buffer::new(allocation.output_len, Some(guard));
+            output.fill(0);
+            (input, output)
+        });
+        let (input, output): (&[u8], &mut [u8]) = match &mut guarded {
+            None => (&buffers.input, &mut buffers.output),
+            Some((input, output)) => (input, output),
+        };
+
+        self.clocks.push(environment::clock_speed());
+
+        let (minor_before, major_before) = page_faults();
+        if let Ok(counters) = counters {
+            counters.start();
+        }
+        let start = timer::now();
+        self.output_len = call(mode, input, output, level);
+        self.deltas.push(start.elapsed());
+        if let Ok(counters) = counters {
+            self.samples.push(counters.stop());
+        }
+        let (minor_after, major_after) = page_faults();
+
+        self.faults.0 += minor_after - minor_before;
+        self.faults.1 += major_after - major_before;
+
+        // checked once, outside of what is measured
+        if matches!(mode, Mode::Inflate) && self.deltas.len() == 1 {
+            assert_eq!(output_hash(&output[..self.output_len]), inflate_hash());
+        }
+    }
+}
+
+/// The input of `run_all`, read once, and an output buffer that every implementation reuses
+struct Buffers {
+    input: Vec<u8>,
+    output: Buffer,
+}
+
+impl Buffers {
+    /// The pages of the output are faulted in up front, so that the first implementation to run
+    /// does not pay for them
+    fn read(path: &str) -> Self {
+        let Ok(input) = read_input(path) else {
+            panic!("error opening {path:?}")
+        };
+        let mut output = output_buffer();
+        output.fill(0);
+
+        Buffers { input, output }
+    }
+}
+
+type Results = Vec<(
+    &'static str,
+    Vec<std::time::Duration>,
+    Vec<counters::Sample>,
+    usize,
+    (u64, u64),
+)>;
+
+/// One line per iteration, for analysis with external tools
+fn write_samples(path: &str, mode: Mode, level: i32, results: &Results) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let (file, new) = open_output(path)?;
+    let mut file = std::io::BufWriter::new(file);
+    if new {
+        writeln!(
+            file,
+            "implementation,mode,level,iteration,nanoseconds,output bytes,cycles,instructions"
+        )?;
+    }
+
+    let mode = mode.name();
+
+    for (name, deltas, samples, output_len, _) in results {
+        for (i, delta) in deltas.iter().enumerate() {
+            let (cycles, instructions) = match samples.get(i) {
+                Some(s) => (s.cycles.to_string(), s.instructions.to_string()),
+                None => (String::new(), String::new()),
+            };
+
+            writeln!(
+                file,
+                "{name},{mode},{level},{i},{},{output_len},{cycles},{instructions}",
+                delta.as_nanos()
+            )?;
+        }
+    }
+
+    file.flush()
+}
+
+/// Open the file of `--jsonl` or `--samples`, and return whether it is new. A command can call
+/// `run_all` many times (for every level, setting or file of a corpus): the first run truncates the
+/// file, and the later ones append to it.
+fn open_output(path: &str) -> std::io::Result<(std::fs::File, bool)> {
+    static OPENED: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+    let mut opened = OPENED.lock().unwrap();
+    let new = !opened.iter().any(|opened| opened == path);
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(new)
+        .append(!new)
+        .open(path)?;
+    if new {
+        opened.push(path.to_string());
+    }
+
+    Ok((file, new))
+}
+
+/// Measure every implementation on `path`, print the report, and return the results for further
+/// aggregation
+fn run_all(
+    mode: Mode,
+    path: &str,
+    level: i32,
+    options: &Options,
+    mut progress: Option<&mut progress::Progress>,
+) -> Results {
+    check_environment(options);
+
+    let n = 5;
+
+    let counters = counters::Counters::open();
+    if let Err(e) = &counters {
+        println!("# hardware counters: unavailable ({e})");
+    }
+
+    let image = std::env::var(sandbox::IMAGE_VARIABLE).ok();
+    if let Some(image) = &image {
+        println!("# sandbox image: {image}");
+    }
+
+    // the vendored zlib is `og`, this is the one that dynamically linked programs get
+    match dylib::system() {
+        Ok(library) => println!("# system zlib: {} ({})", library.version(), library.path()),
+        Err(e) => println!("# system zlib: unavailable ({e})"),
+    }
+
+    // a patched zlib from a local source tree, measured after the built-in implementations
+    let local = match og_local::configured().then(og_local::load) {
+        None => None,
+        Some(Ok(library)) => {
+            println!("# og-local: {} ({})", library.version(), library.path());
+            dylib::select(library);
+
+            let f: Function = helper::<dylib::ZlibDylib>;
+            Some((
+                "og-local",
+                f,
+                call::<dylib::ZlibDylib> as _,
+                Capabilities::ZLIB,
+                dylib::ZlibDylib::version as _,
+                dylib::ZlibDylib::build as _,
+            ))
+        }
+        Some(Err(e)) => {
+            println!("# og-local: unavailable ({e})");
+            None
+        }
+    };
+
+    let mut events = options
+        .jsonl
+        .as_deref()
+        .map(|path| jsonl::Events::create(path).unwrap());
+    let mode_name = mode.name();
+    if let Some(events) = &mut events {
+        let mut line = jsonl::Line::new("start")
+            .str("mode", mode_name)
+            .int("level", level)
+            .str("path", path)
+            .str("host", &environment::hostname());
+        line = match options.max_time {
+            None => line.int("iterations", n as u64),
+            Some(max_time) => line.float("max_time_s", max_time.as_secs_f64()),
+        };
+        if let Some(image) = &image {
+            line = line.str("image", image);
+        }
+        events.emit(line);
+    }
+
+    let mut runners = Vec::new();
+    let all = FUNCTIONS.iter().copied().chain(local);
+    for (name, f, call, capabilities, version, build) in all.chain(registry::registered()) {
+        if let Err(reason) = capabilities.check(mode, level) {
+            println!("# {name}: skipped, {reason}");
+            continue;
+        }
+
+        // what was actually linked, so that results can be compared across machines
+        println!("# {name}: {}, {}", version(), build());
+
+        // a child process allocates for itself, this one does not run the implementation at all
+        let allocation = match options.isolate {
+            false => allocation(options, name, f, mode, path, level),
+            true => Allocation::HEAP,
+        };
+
+        runners.push((name, call, allocation, Measurement::default()));
+    }
+
+    // what `progress` shows as running
+    let running = |name: &str| match mode {
+        Mode::Inflate => format!("{name}, {path}"),
+        Mode::Deflate => format!("{name}, {path}, level {level}"),
+    };
+
+    if options.isolate {
+        runners.retain_mut(|(name, _, _, measurement)| {
+            if interrupt::interrupted() {
+                return false;
+            }
+            if let Some(progress) = &mut progress {
+                progress.running(running(name));
+            }
+            match isolate::measure(name, mode, path, level, options, n) {
+                Ok(isolated) => *measurement = isolated,
+                Err(e) => println!("# {name}: failed in its own process, {e}"),
+            }
+            !measurement.deltas.is_empty()
+        });
+    } else {
+        let mut buffers = Buffers::read(path);
+
+        // the iterations of the implementations are interleaved, so that a change in the machine's
+        // state (its temperature, its clock speed) affects all of them rather than whichever ran
+        // last. The first iteration runs with cold caches, like a one-shot CLI invocation; later
+        // iterations benefit from warm caches.
+        interleave(runners.len(), |i| {
+            let (name, call, allocation, measurement) = &mut runners[i];
+
+            let elapsed = measurement.deltas.iter().sum();
+            if interrupt::interrupted()
+                || !another_run(measurement.deltas.len(), n, options.max_time, elapsed)
+            {
+                return false;
+            }
+
+            if let Some(progress) = &mut progress {
+                progress.running(running(name));
+            }
+            measurement.iteration(*call, mode, level, &mut buffers, *allocation, &counters);
+            true
+        });
+    }
+    if let Some(progress) = &mut progress {
+        progress.clear();
+    }
+
+    // Ctrl-C: report what was measured, the first iteration and at least one of the steady state
+    if interrupt::interrupted() {
+        println!("# interrupted: the results are partial");
+        runners.retain(|(name, _, _, measurement)| {
+            let complete = measurement.deltas.len() >= 2;
+            if !complete {
+                println!("# {name}: interrupted before a steady state iteration");
+            }
+            complete
+        });
+    }
+
+    let mut results: Results = Vec::new();
+    // the clock speed before every iteration, to notice throttling
+    let mut clocks = Vec::new();
+    for (name, _, _, m) in runners {
+        results.push((name, m.deltas, m.samples, m.output_len, m.faults));
+        clocks.push(m.clocks);
+    }
+
+    for (name, deltas, samples, output_len, faults) in &mut results {
+        let (name, output_len) = (*name, *output_len);
+        let n = deltas.len();
+
+        *faults = (faults.0 / n as u64, faults.1 / n as u64);
+
+        if let Some(events) = &mut events {
+            let total: std::time::Duration = deltas.iter().sum();
+            let bytes = input_len(path);
+
+            events.emit(
+                jsonl::Line::new("cell")
+                    .str("mode", mode_name)
+                    .int("level", level)
+                    .str("implementation", name)
+                    .float(
+                        "mb_per_s",
+                        n as f64 * bytes as f64 / 1e6 / total.as_secs_f64(),
+                    )
+                    .ints("nanoseconds", deltas.iter().map(|d| d.as_nanos() as u64))
+                    .int("output_bytes", output_len as u64)
+                    .int("minor_faults", faults.0)
+                    .int("major_faults", faults.1)
+                    .ints("cycles", samples.iter().map(|s| s.cycles))
+                    .ints("instructions", samples.iter().map(|s| s.instructions)),
+            );
+        }
+
+        if let Some(address) = &options.pushgateway {
+            let total: std::time::Duration = deltas.iter().sum();
+            let steady = (total - deltas[0]) / (n as u32 - 1);
+            let mb = input_len(path) as f64 / 1e6;
+
+            let cell = prometheus::Cell {
+                mode: mode_name,
+                level,
+                implementation: name,
+                mb_per_s: n as f64 * mb / total.as_secs_f64(),
+                steady_mb_per_s: mb / steady.as_secs_f64(),
+                output_bytes: output_len,
+                minor_faults: faults.0,
+            };
+
+            // a monitoring hiccup should not abort a long run
+            if let Err(e) = prometheus::push(address, &cell) {
+                eprintln!("# pushgateway: {e}");
+            }
+        }
+    }
+
+    if let Some(events) = &mut events {
+        events.emit(
+            jsonl::Line::new("end")
+                .str("mode", mode_name)
+                .int("level", level),
+        );
+    }
+
+    if let Some(samples) = &options.samples {
+        write_samples(samples, mode, level, &results).unwrap();
+    }
+
+    let bytes = input_len(path);
+    let mb = bytes as f64 / 1_000_000.0;
+
+    let (bandwidth, source) = match options.mem_bandwidth {
+        Some(bandwidth) => (bandwidth, "configured"),
+        None => (environment::memory_bandwidth(), "measured"),
+    };
+    println!("# memory bandwidth: {bandwidth:.2} GB/s ({source})");
+
+    // the median of every implementation ignores the odd interrupted measurement
+    let medians: Vec<f64> = (clocks.iter_mut())
+        .map(|clocks| {
+            clocks.sort_by(f64::total_cmp);
+            clocks[clocks.len() / 2]
+        })
+        .collect();
+    let highest = medians.iter().copied().fold(0.0, f64::max);
+
+    let mut throttled = false;
+    for ((name, ..), median) in results.iter().zip(medians) {
+        let relative = median / highest;
+        if relative < environment::THROTTLED {
+            println!(
+                "# {name}: throttled, the clock was at {:.0}% of the highest of this run",
+                100.0 * relative
+            );
+            throttled = true;
+        }
+    }
+    if !throttled {
+        println!(
+            "# clock: steady, every implementation ran within {:.0}% of the highest",
+            100.0 * (1.0 - environment::THROTTLED)
+        );
+    }
+
+    // of the steady state iterations, like the steady state column
+    let summaries: Vec<_> = results
+        .iter()
+        .map(|(name, deltas, _, _, _)| {
+            let steady = &deltas[1..];
+            let mb_per_s: Vec<_> = steady.iter().map(|d| mb / d.as_secs_f64()).collect();
+            let seconds: Vec<_> = steady.iter().map(|d| d.as_secs_f64()).collect();
+            let outliers = stats::outliers(&seconds);
+
+            let summary = stats::Summary::of(&mb_per_s, &outliers, options.discard_outliers);
+            (*name, summary)
+        })
+        .collect();
+
+    println!("implementation, MB/s, first iteration MB/s, steady state MB/s, memory GB/s, % of memory bandwidth, minor faults per iteration, major faults per iteration, cycles/byte, instructions/byte");
+    for (name, deltas, samples, output_len, (minor, major)) in &results {
+        let (n, output_len) = (deltas.len(), *output_len);
+        let total: std::time::Duration = deltas.iter().sum();
+        let first = deltas[0];
+        let steady = (total - first) / (n as u32 - 1);
+
+        // every input byte is read and every output byte written at least once
+        let touched = (bytes + output_len as u64) as f64 / 1e9 / steady.as_secs_f64();
+
+        // per uncompressed byte: the input of deflate, the output of inflate; steady state only
+        let (cycles, instructions) = match samples.get(1..) {
+            Some(steady) if !steady.is_empty() => {
+                let uncompressed = match mode {
+                    Mode::Deflate => bytes,
+                    Mode::Inflate => output_len as u64,
+                } as f64
+                    * steady.len() as f64;
+
+                let cycles: u64 = steady.iter().map(|s| s.cycles).sum();
+                let instructions: u64 = steady.iter().map(|s| s.instructions).sum();
+
+                (
+                    format!("{:.3}", cycles as f64 / uncompressed),
+                    format!("{:.3}", instructions as f64 / uncompressed),
+                )
+            }
+            _ => ("-".to_string(), "-".to_string()),
+        };
+
+        println!(
+            "{name}, {}, {}, {}, {touched:.3}, {:.1}, {minor}, {major}, {cycles}, {instructions}",
+            (n as f64 * mb) / total.as_secs_f64(),
+            mb / first.as_secs_f64(),
+            mb / steady.as_secs_f64(),
+            100.0 * touched / bandwidth,
+        );
+    }
+
+    println!();
+    match options.discard_outliers {
+        true => println!("# outliers: discarded"),
+        false => println!("# outliers: counted, pass --discard-outliers to leave them out"),
+    }
+    println!(
+        "implementation, input, level, unit, {}",
+        stats::Summary::HEADER
+    );
+    for (name, summary) in summaries {
+        println!("{name}, {path}, {level}, MB/s, {}", summary.columns());
+    }
+
+    if options.profile {
+        let level_arg = level.to_string();
+        let profiles: Vec<_> = FUNCTIONS
+            .iter()
+            .filter(|(_, _, _, capabilities, _, _)| capabilities.check(mode, level).is_ok())
+            .map(|(name, _, _, _, _, _)| {
+                let args: &[&str] = match mode {
+                    Mode::Inflate => &["inflate", name, path],
+                    Mode::Deflate => &["deflate", &level_arg, name, path],
+                };
+
+                (*name, profile::perf_record(name, args))
+            })
+            .collect();
+
+        profile::print_profiles(&profiles);
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn levels() {
+        assert_eq!(parse_levels("6"), [6]);
+        assert_eq!(parse_levels("1-3,6"), [1, 2, 3, 6]);
+        assert_eq!(parse_levels("0-3,9"), [0, 1, 2, 3, 9]);
+        assert_eq!(parse_levels("-1"), [-1]);
+        assert_eq!(parse_levels("-1-1"), [-1, 0, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid level")]
+    fn invalid_level() {
+        parse_levels("1,x");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid level")]
+    fn empty_level() {
+        parse_levels("1,,3");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid level")]
+    fn non_ascii_level() {
+        parse_levels("é-3");
+    }
+
+    #[test]
+    fn bytes() {
+        assert_eq!(parse_bytes("65536"), 65536);
+        assert_eq!(parse_bytes("64K"), 64 << 10);
+        assert_eq!(parse_bytes("1M"), 1 << 20);
+        assert_eq!(parse_bytes("2G"), 2 << 30);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid number of bytes")]
+    fn zero_bytes() {
+        parse_bytes("0");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid number of bytes")]
+    fn suffix_without_number() {
+        parse_bytes("K");
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("5s"), Duration::from_secs(5));
+        assert_eq!(parse_duration("500ms"), Duration::from_millis(500));
+        assert_eq!(parse_duration("2m"), Duration::from_secs(120));
+        assert_eq!(parse_duration("1.5"), Duration::from_millis(1500));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid duration")]
+    fn invalid_duration() {
+        parse_duration("abc");
+    }
+}
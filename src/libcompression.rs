@@ -0,0 +1,161 @@
+//! Apple's `libcompression`, with its `COMPRESSION_ZLIB` codec.
+//!
+//! This is the system deflate implementation on macOS (and tuned for Apple Silicon). It only has a
+//! whole-buffer API without any configuration: it produces and consumes raw deflate at a fixed
+//! level (comparable to zlib's 5). The zlib header and adler32 trailer are handled here.
+
+use std::ffi::c_void;
+
+use crate::{Capabilities, DeflateConfig, DeflateImplementation, InflateConfig, ReturnCode};
+
+/// `COMPRESSION_ZLIB` of `compression_algorithm`
+const COMPRESSION_ZLIB: u32 = 0x205;
+
+#[link(name = "compression")]
+extern "C" {
+    fn compression_encode_scratch_buffer_size(algorithm: u32) -> usize;
+    fn compression_encode_buffer(
+        dst_buffer: *mut u8,
+        dst_size: usize,
+        src_buffer: *const u8,
+        src_size: usize,
+        scratch_buffer: *mut c_void,
+        algorithm: u32,
+    ) -> usize;
+    fn compression_decode_scratch_buffer_size(algorithm: u32) -> usize;
+    fn compression_decode_buffer(
+        dst_buffer: *mut u8,
+        dst_size: usize,
+        src_buffer: *const u8,
+        src_size: usize,
+        scratch_buffer: *mut c_void,
+        algorithm: u32,
+    ) -> usize;
+}
+
+/// The zlib header: deflate with a 32K window, and the "default" compression level
+const ZLIB_HEADER: [u8; 2] = [0x78, 0x9c];
+
+pub struct LibCompression;
+
+impl DeflateImplementation for LibCompression {
+    const NAME: &'static str = "libcompression";
+
+    const CAPABILITIES: Capabilities = Capabilities {
+        // only the whole-buffer API is used, not `compression_stream`
+        streaming: false,
+        gzip: false,
+        dictionary: false,
+        strategies: false,
+        mem_level: false,
+        ..Capabilities::ZLIB
+    };
+
+    // part of the operating system, which is versioned as a whole
+    fn version() -> String {
+        "system".to_string()
+    }
+
+    fn build() -> String {
+        "system library".to_string()
+    }
+
+    fn uncompress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        let raw = match config.window_bits {
+            ..=-1 => input,
+            8..=15 if input.len() >= 6 => {
+                let (cmf, flg) = (input[0], input[1]);
+                let valid = cmf & 0x0f == 8 && (u16::from(cmf) << 8 | u16::from(flg)) % 31 == 0;
+                if !valid || flg & 0x20 != 0 {
+                    return (&mut [], ReturnCode::DataError);
+                }
+                &input[2..input.len() - 4]
+            }
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+
+        let mut scratch =
+            vec![0u8; unsafe { compression_decode_scratch_buffer_size(COMPRESSION_ZLIB) }];
+        let len = unsafe {
+            compression_decode_buffer(
+                output.as_mut_ptr(),
+                output.len(),
+                raw.as_ptr(),
+                raw.len(),
+                scratch.as_mut_ptr().cast(),
+                COMPRESSION_ZLIB,
+            )
+        };
+
+        // errors are reported as 0 bytes of output, and a truncated output can't be told apart from
+        // one that exactly fits the buffer
+        if len == 0 && !raw.is_empty() {
+            return (&mut [], ReturnCode::DataError);
+        }
+        if len == output.len() {
+            return (&mut [], ReturnCode::BufError);
+        }
+
+        let output = &mut output[..len];
+        if config.window_bits > 0 {
+            let trailer = &input[input.len() - 4..];
+            if adler::adler32_slice(output).to_be_bytes() != trailer {
+                return (&mut [], ReturnCode::DataError);
+            }
+        }
+
+        (output, ReturnCode::Ok)
+    }
+
+    fn compress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        // there are no levels, every valid zlib level uses the fixed one
+        if !matches!(config.level, -1..=9) {
+            return (&mut [], ReturnCode::StreamError);
+        }
+
+        let (header, trailer) = match config.window_bits {
+            ..=-1 => (0, 0),
+            8..=15 => (ZLIB_HEADER.len(), 4),
+            _ => return (&mut [], ReturnCode::StreamError),
+        };
+        if output.len() < header + trailer {
+            return (&mut [], ReturnCode::BufError);
+        }
+
+        let end = output.len() - trailer;
+        let body = &mut output[header..end];
+        let mut scratch =
+            vec![0u8; unsafe { compression_encode_scratch_buffer_size(COMPRESSION_ZLIB) }];
+        let len = unsafe {
+            compression_encode_buffer(
+                body.as_mut_ptr(),
+                body.len(),
+                input.as_ptr(),
+                input.len(),
+                scratch.as_mut_ptr().cast(),
+                COMPRESSION_ZLIB,
+            )
+        };
+
+        // 0 means that the output did not fit
+        if len == 0 {
+            return (&mut [], ReturnCode::BufError);
+        }
+
+        if header > 0 {
+            output[..header].copy_from_slice(&ZLIB_HEADER);
+            let adler = adler::adler32_slice(input).to_be_bytes();
+            output[header + len..][..trailer].copy_from_slice(&adler);
+        }
+
+        (&mut output[..header + len + trailer], ReturnCode::Ok)
+    }
+}
@@ -0,0 +1,142 @@
+//! The cost of calling zlib and zlib-ng through a shared library, next to linking them statically.
+//!
+//! The vendored builds are static archives that are linked into the benchmark, so every call into
+//! them is a direct call that the linker can resolve (and, with cross-language LTO, inline). A
+//! shared library is called through the PLT, and its code is compiled as position independent.
+//! This compares the two for the same library, with the timing of `init-cost`: for small payloads
+//! the calls themselves are a visible part of the total.
+//!
+//! The shared zlib is compiled from the source that `libz-sys` ships with (or from
+//! `ZLIB_BENCH_OG_SOURCE`), the shared zlib-ng is the release of the vendored version, fetched and
+//! built like `ng-versions` does.
+
+use crate::dylib::{self, Library, ZlibDylib};
+use crate::init_cost::{measure, Phases};
+use crate::ZlibImplementation;
+
+/// The directory with the zlib source of the `libz-sys` in cargo's registry
+#[cfg(feature = "og")]
+fn vendored_source() -> Result<String, String> {
+    use std::path::PathBuf;
+
+    let version = env!("ZLIB_BENCH_VERSION_LIBZ_SYS");
+    if version == "unknown" {
+        return Err(format!(
+            "the version of libz-sys is unknown, set {}",
+            crate::og_local::SOURCE_VARIABLE
+        ));
+    }
+
+    let home = match std::env::var_os("CARGO_HOME") {
+        Some(home) => PathBuf::from(home),
+        None => PathBuf::from(std::env::var_os("HOME").unwrap_or_default()).join(".cargo"),
+    };
+    let registry = home.join("registry/src");
+
+    let entries = std::fs::read_dir(&registry).map_err(|e| format!("{registry:?}: {e}"))?;
+    for entry in entries.flatten() {
+        let source = entry.path().join(format!("libz-sys-{version}/src/zlib"));
+        if source.is_dir() {
+            return Ok(source.to_string_lossy().into_owned());
+        }
+    }
+
+    Err(format!(
+        "libz-sys-{version} is not in {registry:?}, set {}",
+        crate::og_local::SOURCE_VARIABLE
+    ))
+}
+
+#[cfg(feature = "og")]
+fn og() -> Result<&'static Library, String> {
+    let source = match std::env::var(crate::og_local::SOURCE_VARIABLE) {
+        Ok(source) => source,
+        Err(_) => vendored_source()?,
+    };
+
+    crate::og_local::open(&source)
+}
+
+#[cfg(feature = "ng")]
+fn ng() -> Result<&'static Library, String> {
+    crate::ng_versions::load(&crate::ZlibNg::zlib_version())
+}
+
+type Version = fn() -> String;
+type Measure = fn(&[u8], i32) -> Result<(Phases, Phases), String>;
+type Load = fn() -> Result<&'static Library, String>;
+
+/// The name, the version and the measurement of the static build, and how to load the shared one
+const PAIRS: &[(&str, Version, Measure, Load)] = &[
+    #[cfg(feature = "og")]
+    (
+        "og",
+        crate::ZlibOg::zlib_version,
+        measure::<crate::ZlibOg>,
+        og,
+    ),
+    #[cfg(feature = "ng")]
+    (
+        "ng",
+        crate::ZlibNg::zlib_version,
+        measure::<crate::ZlibNg>,
+        ng,
+    ),
+];
+
+/// The time of one call: setting up the stream, the work and tearing it down again
+fn total((init, work, end): Phases) -> f64 {
+    (init + work + end).as_nanos() as f64
+}
+
+pub fn linking_all(path: &str, level: i32, sizes: &[usize]) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    println!("implementation, mode, payload bytes, static ns, shared ns, shared - static ns, shared - static %");
+    for (name, version, measure_static, load) in PAIRS {
+        let library = match load() {
+            Ok(library) => library,
+            Err(reason) => {
+                println!("# {name}: skipped, {reason}");
+                continue;
+            }
+        };
+        dylib::select(library);
+
+        let (version, shared_version) = (version(), library.version());
+        println!("# {name}: static {version}, shared {shared_version}");
+        if version != shared_version {
+            println!("# {name}: the versions differ, so the difference is not only the linking");
+        }
+
+        for &size in sizes {
+            let payload = &input[..Ord::min(size, input.len())];
+
+            let measured = measure_static(payload, level)
+                .and_then(|a| Ok((a, measure::<ZlibDylib>(payload, level)?)));
+            let ((deflate, inflate), (shared_deflate, shared_inflate)) = match measured {
+                Ok(phases) => phases,
+                Err(e) => {
+                    println!("# {name} with {} bytes: failed, {e}", payload.len());
+                    continue;
+                }
+            };
+
+            for (mode, a, b) in [
+                ("deflate", deflate, shared_deflate),
+                ("inflate", inflate, shared_inflate),
+            ] {
+                let (a, b) = (total(a), total(b));
+
+                println!(
+                    "{name}, {mode}, {}, {a}, {b}, {}, {:.1}",
+                    payload.len(),
+                    b - a,
+                    100.0 * (b - a) / a,
+                );
+            }
+        }
+    }
+}
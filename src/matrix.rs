@@ -0,0 +1,274 @@
+//! Every combination of implementations, compression levels and input files, in one run.
+//!
+//! The runs are interleaved: every round runs each combination once, in a random order, so that
+//! drift in the machine's state and effects of one combination on the next (the caches, the branch
+//! predictor) are spread over all of them rather than hitting whichever happened to run last. The
+//! order comes from a seed, which is printed, so that `--seed` can reproduce it. Every input is
+//! read once up front, and a run only times the call that compresses it, into an output buffer
+//! that all runs reuse.
+//!
+//! After the table, every input gets its Pareto frontier of speed against output size: the
+//! (implementation, level) points that no other point beats on both. Any other point is dominated,
+//! there is a configuration that is at least as fast and compresses at least as well.
+//!
+//! With `--timeout`, every call runs in a child process (see `isolate`) that is killed when it takes
+//! longer. That combination is then left out of the later rounds and of the table, rather than
+//! stalling the whole matrix.
+
+use std::time::Duration;
+
+use crate::progress::Progress;
+use crate::rng::Rng;
+use crate::{
+    implementations, interrupt, isolate, output_buffer, parse_duration, parse_levels, registry,
+    stats, time_call, Implementation, Mode, FUNCTIONS,
+};
+
+pub struct Options {
+    pub levels: Vec<i32>,
+    /// by default, the implementations that the `*-all` commands measure
+    pub implementations: Option<Vec<String>>,
+    pub runs: usize,
+    pub discard_outliers: bool,
+    /// the seed of the order of the runs, by default taken from the clock
+    pub seed: Option<u64>,
+    /// run every call in a child process, and give up on it after this long
+    pub timeout: Option<Duration>,
+}
+
+impl Options {
+    pub fn parse(mut it: impl Iterator<Item = String>) -> (Vec<String>, Self) {
+        let mut paths = Vec::new();
+        let mut options = Options {
+            levels: vec![1, 6, 9],
+            implementations: None,
+            runs: 5,
+            discard_outliers: false,
+            seed: None,
+            timeout: None,
+        };
+
+        while let Some(arg) = it.next() {
+            let mut list = || -> Vec<String> {
+                let list = it.next().unwrap();
+                list.split(',').map(str::to_string).collect()
+            };
+            match arg.as_str() {
+                "--levels" => options.levels = parse_levels(&it.next().unwrap()),
+                "--implementations" => options.implementations = Some(list()),
+                "--runs" => options.runs = it.next().unwrap().parse().unwrap(),
+                "--discard-outliers" => options.discard_outliers = true,
+                "--seed" => options.seed = Some(it.next().unwrap().parse().unwrap()),
+                "--timeout" => options.timeout = Some(parse_duration(&it.next().unwrap())),
+                other if other.starts_with("--") => panic!("invalid argument {other:?}"),
+                _ => paths.push(arg),
+            }
+        }
+
+        assert!(!paths.is_empty(), "no input files");
+        assert!(options.runs > 0, "--runs must be at least 1");
+
+        (paths, options)
+    }
+}
+
+/// One combination: (input, level, implementation), and its times in seconds and output size
+struct Cell<'a> {
+    path: &'a str,
+    input: &'a [u8],
+    level: i32,
+    implementation: Implementation,
+    seconds: Vec<f64>,
+    output_len: usize,
+    /// with `--timeout`, whether the combination was given up on
+    failed: bool,
+}
+
+pub fn matrix_all(paths: &[String], options: &Options) {
+    let find = |name: &String| match implementations().find(|(n, _, _, _, _, _)| n == name) {
+        Some(implementation) => implementation,
+        None => panic!("invalid implementation: {name:?}"),
+    };
+    let selected: Vec<Implementation> = match &options.implementations {
+        None => FUNCTIONS
+            .iter()
+            .copied()
+            .chain(registry::registered())
+            .collect(),
+        Some(names) => names.iter().map(find).collect(),
+    };
+
+    let mut configs = Vec::new();
+    for &level in &options.levels {
+        for &implementation in &selected {
+            let (name, _, _, capabilities, _, _) = implementation;
+            match capabilities.check(Mode::Deflate, level) {
+                Ok(()) => configs.push((level, implementation)),
+                Err(reason) => println!("# {name} at level {level}: skipped, {reason}"),
+            }
+        }
+    }
+
+    let inputs: Vec<_> = paths
+        .iter()
+        .map(|path| match std::fs::read(path) {
+            Ok(input) => input,
+            Err(_) => panic!("error opening {path:?}"),
+        })
+        .collect();
+
+    let mut cells = Vec::new();
+    for (path, input) in paths.iter().zip(&inputs) {
+        for &(level, implementation) in &configs {
+            cells.push(Cell {
+                path,
+                input,
+                level,
+                implementation,
+                seconds: Vec::with_capacity(options.runs),
+                output_len: 0,
+                failed: false,
+            });
+        }
+    }
+
+    let seed = options.seed.unwrap_or_else(|| {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH);
+        now.unwrap().as_nanos() as u64
+    });
+    println!("# seed: {seed}");
+
+    // in bytes of input, so that a call on a large input counts for more
+    let bytes: usize = cells.iter().map(|cell| cell.input.len()).sum();
+    let mut progress = Progress::new((options.runs * bytes) as f64);
+
+    // the settings of the child processes are the defaults, like those of the calls in this one
+    let isolated = crate::Options {
+        timeout: options.timeout,
+        ..Default::default()
+    };
+
+    let mut output = output_buffer();
+
+    let mut rng = Rng::new(seed);
+    let mut order: Vec<usize> = (0..cells.len()).collect();
+    'rounds: for round in 1..=options.runs {
+        rng.shuffle(&mut order);
+        for &i in &order {
+            if interrupt::interrupted() {
+                progress.clear();
+                println!(
+                    "# interrupted in round {round} of {}: the results are partial",
+                    options.runs
+                );
+                break 'rounds;
+            }
+
+            let cell = &mut cells[i];
+            let (name, _, call, _, _, _) = cell.implementation;
+            progress.advance(cell.input.len() as f64);
+            if cell.failed {
+                continue;
+            }
+            progress.running(format_args!(
+                "round {round}/{}, {name}, {}, level {}",
+                options.runs, cell.path, cell.level
+            ));
+
+            if options.timeout.is_none() {
+                let (delta, output_len) =
+                    time_call(call, Mode::Deflate, cell.input, &mut output, cell.level);
+                cell.output_len = output_len;
+                cell.seconds.push(delta.as_secs_f64());
+                continue;
+            }
+
+            match isolate::measure(name, Mode::Deflate, cell.path, cell.level, &isolated, 1) {
+                Ok(measurement) => {
+                    cell.output_len = measurement.output_len;
+                    cell.seconds.push(measurement.deltas[0].as_secs_f64());
+                }
+                Err(e) => {
+                    progress.clear();
+                    println!(
+                        "# {name} at level {} on {}: failed, {e}",
+                        cell.level, cell.path
+                    );
+                    cell.failed = true;
+                }
+            }
+        }
+    }
+    progress.clear();
+
+    println!(
+        "input, level, implementation, unit, {}, output bytes, ratio",
+        stats::Summary::HEADER
+    );
+    let mut points = Vec::with_capacity(cells.len());
+    // after Ctrl-C, some combinations may not have run at all
+    let measured = cells
+        .iter()
+        .filter(|cell| !cell.failed && !cell.seconds.is_empty());
+    for cell in measured {
+        let (name, _, _, _, _, _) = cell.implementation;
+        let mb = cell.input.len() as f64 / 1e6;
+
+        let mb_per_s: Vec<_> = cell.seconds.iter().map(|s| mb / s).collect();
+        let outliers = stats::outliers(&cell.seconds);
+        let summary = stats::Summary::of(&mb_per_s, &outliers, options.discard_outliers);
+
+        println!(
+            "{}, {}, {name}, MB/s, {}, {}, {:.3}",
+            cell.path,
+            cell.level,
+            summary.columns(),
+            cell.output_len,
+            cell.input.len() as f64 / cell.output_len as f64,
+        );
+
+        points.push((cell, summary.median));
+    }
+
+    for path in paths {
+        let points: Vec<_> = points
+            .iter()
+            .filter(|(cell, _)| cell.path == path)
+            .collect();
+        pareto(path, &points);
+    }
+}
+
+/// Whether `a` is at least as fast and as small as `b`, and better at one of them
+fn dominates((a, a_speed): &(&Cell, f64), (b, b_speed): &(&Cell, f64)) -> bool {
+    let as_good = a_speed >= b_speed && a.output_len <= b.output_len;
+    as_good && (a_speed > b_speed || a.output_len < b.output_len)
+}
+
+/// The points of one input from fastest to slowest, with the ones on the Pareto frontier of speed
+/// (the median MB/s) against output size marked, and the others with a point that dominates them
+fn pareto(path: &str, points: &[&(&Cell, f64)]) {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    println!();
+    println!("# pareto frontier of {path}");
+    println!("level, implementation, median MB/s, output bytes, frontier");
+    for point @ (cell, speed) in sorted.iter().copied() {
+        let (name, _, _, _, _, _) = cell.implementation;
+
+        // the fastest of the points that dominate this one
+        let frontier = match sorted.iter().find(|other| dominates(other, point)) {
+            None => "optimal".to_string(),
+            Some((other, _)) => {
+                let (other_name, _, _, _, _, _) = other.implementation;
+                format!("dominated by {other_name} at level {}", other.level)
+            }
+        };
+
+        println!(
+            "{}, {name}, {speed:.2}, {}, {frontier}",
+            cell.level, cell.output_len
+        );
+    }
+}
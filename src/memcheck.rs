@@ -0,0 +1,147 @@
+//! Run every implementation under valgrind's memcheck, and summarize the errors it reports.
+//!
+//! A new zlib fork should be screened for invalid accesses, uses of uninitialized memory and leaks
+//! before its numbers are trusted. We run this executable once per implementation (like the
+//! profiler does), let valgrind write its findings as XML, and count the errors by kind.
+
+use std::process::Command;
+
+use crate::{Mode, FUNCTIONS};
+
+#[derive(Debug, Default)]
+struct Summary {
+    invalid_reads: usize,
+    invalid_writes: usize,
+    uninitialized: usize,
+    other: usize,
+    /// bytes in `Leak_DefinitelyLost` and `Leak_IndirectlyLost` errors
+    lost_bytes: u64,
+    /// bytes in `Leak_PossiblyLost` errors
+    possibly_lost_bytes: u64,
+    /// the innermost function of the first error, to have somewhere to start looking
+    first: Option<String>,
+}
+
+impl Summary {
+    fn is_clean(&self) -> bool {
+        self.invalid_reads + self.invalid_writes + self.uninitialized + self.other == 0
+            && self.lost_bytes == 0
+            && self.possibly_lost_bytes == 0
+    }
+}
+
+/// The text of the first `<tag>` element in `xml`
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+
+    Some(xml[start..end].trim())
+}
+
+/// Summarize valgrind's `--xml=yes` output. We only need a couple of fields from every `<error>`,
+/// so a string search is good enough.
+fn parse(xml: &str) -> Summary {
+    let mut summary = Summary::default();
+
+    for error in xml.split("<error>").skip(1) {
+        let error = error.split("</error>").next().unwrap_or(error);
+        let kind = element(error, "kind").unwrap_or("");
+        let leaked = || {
+            element(error, "leakedbytes")
+                .and_then(|bytes| bytes.parse::<u64>().ok())
+                .unwrap_or(0)
+        };
+
+        match kind {
+            "InvalidRead" => summary.invalid_reads += 1,
+            "InvalidWrite" => summary.invalid_writes += 1,
+            "UninitCondition" | "UninitValue" | "SyscallParam" => summary.uninitialized += 1,
+            "Leak_DefinitelyLost" | "Leak_IndirectlyLost" => summary.lost_bytes += leaked(),
+            "Leak_PossiblyLost" => summary.possibly_lost_bytes += leaked(),
+            // still reachable memory is not an error
+            "Leak_StillReachable" => continue,
+            _ => summary.other += 1,
+        }
+
+        if summary.first.is_none() {
+            let function = element(error, "fn").unwrap_or("??");
+            summary.first = Some(format!("{kind} in {function}"));
+        }
+    }
+
+    summary
+}
+
+/// Run this executable with `args` under memcheck
+fn memcheck(name: &str, args: &[&str], suppressions: &[String]) -> Result<Summary, String> {
+    let xml = std::env::temp_dir().join(format!("zlib-bench-{name}.memcheck.xml"));
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+
+    let output = Command::new("valgrind")
+        .arg("--tool=memcheck")
+        .arg("--leak-check=full")
+        .arg("--xml=yes")
+        .arg(format!("--xml-file={}", xml.display()))
+        .args(suppressions.iter().map(|s| format!("--suppressions={s}")))
+        .arg("--")
+        .arg(exe)
+        .args(args)
+        .output()
+        .map_err(|e| format!("error running valgrind: {e}"))?;
+
+    let report = std::fs::read_to_string(&xml);
+    let _ = std::fs::remove_file(&xml);
+
+    // the benchmark itself failed (as opposed to valgrind finding errors, which does not change
+    // the exit code unless `--error-exitcode` is given)
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let last = stderr.lines().last().unwrap_or("").trim();
+        return Err(format!("exited with {} ({last})", output.status));
+    }
+
+    let report = report.map_err(|e| format!("error reading valgrind output: {e}"))?;
+
+    Ok(parse(&report))
+}
+
+fn print_summaries(summaries: &[(&str, Result<Summary, String>)]) {
+    println!("implementation, status, invalid reads, invalid writes, uninitialized, other errors, lost bytes, possibly lost bytes, first error");
+    for (name, summary) in summaries {
+        match summary {
+            Err(e) => println!("{name}, FAILED ({e}), 0, 0, 0, 0, 0, 0, -"),
+            Ok(s) => println!(
+                "{name}, {}, {}, {}, {}, {}, {}, {}, {}",
+                if s.is_clean() { "ok" } else { "errors" },
+                s.invalid_reads,
+                s.invalid_writes,
+                s.uninitialized,
+                s.other,
+                s.lost_bytes,
+                s.possibly_lost_bytes,
+                s.first.as_deref().unwrap_or("-"),
+            ),
+        }
+    }
+}
+
+pub fn memcheck_all(mode: Mode, path: &str, level: i32, suppressions: &[String]) {
+    let level_arg = level.to_string();
+    let summaries: Vec<_> = FUNCTIONS
+        .iter()
+        .filter(|(_, _, _, capabilities, _, _)| capabilities.check(mode, level).is_ok())
+        .map(|(name, _, _, _, _, _)| {
+            let args: &[&str] = match mode {
+                Mode::Inflate => &["inflate", name, path],
+                Mode::Deflate => &["deflate", &level_arg, name, path],
+            };
+
+            (*name, memcheck(name, args, suppressions))
+        })
+        .collect();
+
+    print_summaries(&summaries);
+}
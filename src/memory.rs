@@ -0,0 +1,158 @@
+//! How much memory a stream allocates, through `zalloc` and `zfree`.
+//!
+//! The zlib API lets the caller provide the allocator of a stream, and every allocation of the
+//! state, the window and the hash tables goes through it. Here that allocator counts: the number of
+//! allocations, the bytes allocated in total, and the peak of the bytes that were allocated at the
+//! same time, which is the memory footprint of one stream. That footprint is what `--mem-level` and
+//! `--window-bits` trade against speed and ratio, and it differs a lot between implementations, e.g.
+//! in how they pad and align their buffers. Only the backends with the zlib API take an allocator.
+
+use core::ffi::{c_uint, c_void};
+use core::mem::MaybeUninit;
+
+use crate::{
+    deflate_config, inflate_config, Capabilities, DeflateImplementation, Flush, Mode, ReturnCode,
+    ZlibImplementation,
+};
+
+pub type Alloc = unsafe extern "C" fn(*mut c_void, c_uint, c_uint) -> *mut c_void;
+pub type Free = unsafe extern "C" fn(*mut c_void, *mut c_void);
+
+/// Every allocation is prefixed with its size, `zfree` does not get it. This keeps the alignment of
+/// `malloc`.
+const HEADER: usize = 16;
+
+/// The allocations of one stream, its `opaque`
+#[derive(Debug, Default)]
+struct Tracker {
+    allocations: usize,
+    total: usize,
+    current: usize,
+    peak: usize,
+}
+
+unsafe extern "C" fn zalloc(opaque: *mut c_void, items: c_uint, size: c_uint) -> *mut c_void {
+    let tracker = &mut *opaque.cast::<Tracker>();
+
+    let Some(len) = (items as usize).checked_mul(size as usize) else {
+        return core::ptr::null_mut();
+    };
+    let ptr = libc::malloc(HEADER + len);
+    if ptr.is_null() {
+        return ptr;
+    }
+    ptr.cast::<usize>().write(len);
+
+    tracker.allocations += 1;
+    tracker.total += len;
+    tracker.current += len;
+    tracker.peak = Ord::max(tracker.peak, tracker.current);
+
+    ptr.cast::<u8>().add(HEADER).cast()
+}
+
+unsafe extern "C" fn zfree(opaque: *mut c_void, address: *mut c_void) {
+    let tracker = &mut *opaque.cast::<Tracker>();
+
+    if address.is_null() {
+        return;
+    }
+    let ptr = address.cast::<u8>().sub(HEADER);
+    tracker.current -= ptr.cast::<usize>().read();
+
+    libc::free(ptr.cast());
+}
+
+/// Compress or decompress `input` in one call on a stream that allocates through a `Tracker`
+fn measure<T: ZlibImplementation>(mode: Mode, input: &[u8], level: i32) -> Result<Tracker, String> {
+    let capacity = match mode {
+        Mode::Inflate => 1 << 28,
+        Mode::Deflate => 2 * input.len() + 1024,
+    };
+    let output = vec![0u8; capacity];
+
+    let mut tracker = Tracker::default();
+    let opaque = (&mut tracker as *mut Tracker).cast();
+
+    let mut stream = MaybeUninit::zeroed();
+    T::set_allocator(stream.as_mut_ptr(), zalloc, zfree, opaque);
+    let err = match mode {
+        Mode::Inflate => T::inflate_init(stream.as_mut_ptr(), inflate_config()),
+        Mode::Deflate => T::deflate_init(stream.as_mut_ptr(), deflate_config(level)),
+    };
+    if err != ReturnCode::Ok {
+        return Err(format!("init: {err:?}"));
+    }
+    let stream = unsafe { stream.assume_init_mut() };
+
+    T::set_in(stream, input);
+    T::set_out(stream, &output);
+    let err = match mode {
+        Mode::Inflate => T::inflate(stream, Flush::NoFlush),
+        Mode::Deflate => T::deflate(stream, Flush::Finish),
+    };
+
+    match mode {
+        Mode::Inflate => T::inflate_end(stream),
+        Mode::Deflate => T::deflate_end(stream),
+    };
+
+    match err {
+        ReturnCode::StreamEnd => Ok(tracker),
+        err => Err(format!("{err:?}")),
+    }
+}
+
+type Measure = fn(Mode, &[u8], i32) -> Result<Tracker, String>;
+
+const BACKENDS: &[(&str, Capabilities, Measure)] = &[
+    #[cfg(feature = "og")]
+    ("og", crate::ZlibOg::CAPABILITIES, measure::<crate::ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", crate::ZlibNg::CAPABILITIES, measure::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", crate::ZlibRs::CAPABILITIES, measure::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    (
+        "cloudflare",
+        crate::ZlibCloudflare::CAPABILITIES,
+        measure::<crate::ZlibCloudflare>,
+    ),
+    (
+        "system",
+        crate::dylib::SystemZlib::CAPABILITIES,
+        measure::<crate::dylib::SystemZlib>,
+    ),
+];
+
+pub fn memory_all(mode: Mode, path: &str, level: i32) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    println!("implementation, allocations, allocated bytes, peak bytes, peak KiB");
+    for (name, capabilities, measure) in BACKENDS {
+        if let Err(reason) = capabilities.available() {
+            println!("# {name}: skipped, {reason}");
+            continue;
+        }
+        let tracker = match measure(mode, &input, level) {
+            Ok(tracker) => tracker,
+            Err(e) => {
+                println!("# {name}: failed, {e}");
+                continue;
+            }
+        };
+
+        if tracker.current != 0 {
+            println!("# {name}: {} bytes were not freed", tracker.current);
+        }
+        println!(
+            "{name}, {}, {}, {}, {:.1}",
+            tracker.allocations,
+            tracker.total,
+            tracker.peak,
+            tracker.peak as f64 / 1024.0,
+        );
+    }
+}
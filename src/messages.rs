@@ -0,0 +1,143 @@
+//! Thousands of tiny messages, each compressed and decompressed on its own.
+//!
+//! An RPC framework compresses every request and response separately, and they are often only a
+//! couple of hundred bytes, a small JSON object or protobuf. At that size the work per byte hardly
+//! matters: the cost of setting up the state, building the huffman tables and emitting the headers
+//! dominates. Throughput over a large file says little about that, so this reports the latency of
+//! individual messages instead, as percentiles over every message of every iteration.
+
+use std::time::Duration;
+
+use crate::{
+    deflate_config, inflate_config, stats, timer, DeflateImplementation, Mode, ReturnCode,
+};
+
+/// The maximum number of messages
+const MESSAGES: usize = 10_000;
+
+/// Every message is compressed and decompressed this many times
+const ITERATIONS: usize = 5;
+
+/// The latencies of compressing and of decompressing every message, and the compressed bytes of
+/// one iteration
+type Latencies = (Vec<Duration>, Vec<Duration>, usize);
+
+fn latencies<T: DeflateImplementation>(
+    messages: &[&[u8]],
+    level: i32,
+) -> Result<Latencies, String> {
+    let capabilities = T::CAPABILITIES;
+    capabilities.check(Mode::Deflate, level)?;
+    capabilities.check(Mode::Inflate, level)?;
+
+    let max = messages.iter().map(|m| m.len()).max().unwrap_or(0);
+    let mut compressed = vec![0u8; 2 * max + 1024];
+    let mut decompressed = vec![0u8; max];
+
+    let n = ITERATIONS * messages.len();
+    let (mut deflate, mut inflate) = (Vec::with_capacity(n), Vec::with_capacity(n));
+    let mut compressed_len = 0;
+
+    for _ in 0..ITERATIONS {
+        compressed_len = 0;
+        for message in messages {
+            let start = timer::now();
+            let (output, err) = T::compress_slice(&mut compressed, message, deflate_config(level));
+            deflate.push(start.elapsed());
+            if err != ReturnCode::Ok {
+                return Err(format!("deflate: {err:?}"));
+            }
+            let len = output.len();
+            compressed_len += len;
+
+            let start = timer::now();
+            let (output, err) =
+                T::uncompress_slice(&mut decompressed, &compressed[..len], inflate_config());
+            inflate.push(start.elapsed());
+            if err != ReturnCode::Ok {
+                return Err(format!("inflate: {err:?}"));
+            }
+            if output != *message {
+                return Err("roundtrip mismatch".to_string());
+            }
+        }
+    }
+
+    Ok((deflate, inflate, compressed_len))
+}
+
+type Measure = fn(&[&[u8]], i32) -> Result<Latencies, String>;
+
+const BACKENDS: &[(&str, Measure)] = &[
+    #[cfg(feature = "og")]
+    ("og", latencies::<crate::ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", latencies::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", latencies::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    ("cloudflare", latencies::<crate::ZlibCloudflare>),
+    #[cfg(feature = "miniz")]
+    ("miniz", latencies::<crate::MinizOxide>),
+    #[cfg(feature = "libdeflate")]
+    ("libdeflate", latencies::<crate::Libdeflate>),
+    #[cfg(feature = "miniz-c")]
+    ("miniz-c", latencies::<crate::MinizC>),
+    #[cfg(feature = "flate2")]
+    ("flate2", latencies::<crate::Flate2>),
+    #[cfg(feature = "classic")]
+    ("classic", latencies::<crate::Classic>),
+    #[cfg(feature = "yazi")]
+    ("yazi", latencies::<crate::Yazi>),
+    ("system", latencies::<crate::dylib::SystemZlib>),
+];
+
+pub fn messages_all(path: &str, level: i32, message_size: usize) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    if message_size == 0 {
+        eprintln!("messages: the message size must be at least 1 byte");
+        std::process::exit(1);
+    }
+    if input.is_empty() {
+        eprintln!("messages: {path:?} is empty, there are no messages to measure");
+        std::process::exit(1);
+    }
+
+    let messages: Vec<&[u8]> = input.chunks(message_size).take(MESSAGES).collect();
+    let bytes: usize = messages.iter().map(|m| m.len()).sum();
+    println!(
+        "# {} messages of {message_size} bytes, {bytes} bytes total, {ITERATIONS} iterations",
+        messages.len()
+    );
+
+    println!("implementation, mode, messages/s, p50 us, p90 us, p99 us, p99.9 us, max us, compressed bytes per message");
+    for (name, measure) in BACKENDS {
+        let (deflate, inflate, compressed_len) = match measure(&messages, level) {
+            Ok(latencies) => latencies,
+            Err(reason) => {
+                println!("# {name}: skipped, {reason}");
+                continue;
+            }
+        };
+
+        for (mode, latencies) in [("deflate", deflate), ("inflate", inflate)] {
+            let total: Duration = latencies.iter().sum();
+            let mut us: Vec<_> = latencies.iter().map(|d| d.as_secs_f64() * 1e6).collect();
+            us.sort_by(f64::total_cmp);
+
+            println!(
+                "{name}, {mode}, {:.0}, {:.2}, {:.2}, {:.2}, {:.2}, {:.2}, {:.1}",
+                latencies.len() as f64 / total.as_secs_f64(),
+                stats::percentile(&us, 0.5),
+                stats::percentile(&us, 0.9),
+                stats::percentile(&us, 0.99),
+                stats::percentile(&us, 0.999),
+                stats::percentile(&us, 1.0),
+                compressed_len as f64 / messages.len() as f64,
+            );
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Benchmark several releases of zlib-ng side by side.
+//!
+//! zlib-ng has changed a lot between releases, so a comparison against "zlib-ng" means little
+//! without a version. Releases are fetched and built as shared libraries (this needs `git`, `cmake`
+//! and a C compiler), then loaded with `dlopen` and run in the same process as the vendored build.
+//!
+//! The same machinery builds one release with SIMD code paths disabled, to separate how much of the
+//! difference with zlib-rs comes from the available SIMD extensions. None of the C backends can
+//! switch their SIMD code off at runtime, so this is done with zlib-ng's build options.
+//!
+//! It also builds one release in compat mode, with zlib's API, to measure the cost of that layer.
+//! `libz-sys` can build zlib-ng in compat mode too, but only instead of the vendored zlib.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::dylib::{self, Abi, Library, ZlibDylib};
+use crate::guard::Allocation;
+use crate::{helper, Function, Mode};
+
+const REPOSITORY: &str = "https://github.com/zlib-ng/zlib-ng.git";
+
+/// The latest patch release of the recent minor releases
+pub const DEFAULT_VERSIONS: [&str; 3] = ["2.0.7", "2.1.8", "2.2.4"];
+
+/// Builds with fewer and fewer SIMD code paths: (name, cmake options)
+///
+/// The x86 options are ignored (with a warning from cmake) on other architectures.
+#[cfg(feature = "rs")]
+const SIMD_BUILDS: [(&str, &[&str]); 4] = [
+    ("default", &[]),
+    (
+        "no-avx512",
+        &[
+            "-DWITH_AVX512=OFF",
+            "-DWITH_AVX512VNNI=OFF",
+            "-DWITH_VPCLMULQDQ=OFF",
+        ],
+    ),
+    (
+        "no-avx2",
+        &[
+            "-DWITH_AVX512=OFF",
+            "-DWITH_AVX512VNNI=OFF",
+            "-DWITH_VPCLMULQDQ=OFF",
+            "-DWITH_AVX2=OFF",
+        ],
+    ),
+    // only portable C
+    ("generic", &["-DWITH_OPTIM=OFF"]),
+];
+
+/// The cmake option for a build with zlib's API (later options override earlier ones)
+const COMPAT: &str = "-DZLIB_COMPAT=ON";
+
+pub fn run(command: &mut Command) -> Result<(), String> {
+    match command.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("{command:?} failed ({status})")),
+        Err(e) => Err(format!("{command:?} failed ({e})")),
+    }
+}
+
+/// Fetch and build a release (once) in the build directory `build`, with extra cmake `options`, and
+/// return the path of the shared library
+fn build(version: &str, build: &str, options: &[&str]) -> Result<String, String> {
+    let dir = format!("{}/target/zlib-ng/{version}", env!("CARGO_MANIFEST_DIR"));
+    let name = match options.contains(&COMPAT) {
+        true => "libz.so",
+        false => "libz-ng.so",
+    };
+    let library = format!("{dir}/{build}/{name}");
+    if Path::new(&library).exists() {
+        return Ok(library);
+    }
+
+    let source = format!("{dir}/source");
+    if !Path::new(&source).exists() {
+        run(Command::new("git").args([
+            "clone",
+            "--quiet",
+            "--depth=1",
+            "--branch",
+            version,
+            REPOSITORY,
+            &source,
+        ]))?;
+    }
+
+    run(Command::new("cmake")
+        .args([
+            "-S",
+            &source,
+            "-B",
+            &format!("{dir}/{build}"),
+            "-DCMAKE_BUILD_TYPE=Release",
+            "-DBUILD_SHARED_LIBS=ON",
+            // the native `zng_` API like the vendored build, unless `options` has `COMPAT`
+            "-DZLIB_COMPAT=OFF",
+            "-DZLIB_ENABLE_TESTS=OFF",
+            "-DWITH_GTEST=OFF",
+        ])
+        .args(options))?;
+    run(Command::new("cmake").args(["--build", &format!("{dir}/{build}"), "--parallel"]))?;
+
+    Ok(library)
+}
+
+/// A version is either a release (that is fetched and built), or the path of a `libz-ng.so`
+pub fn load(version: &str) -> Result<&'static Library, String> {
+    let path = match version.contains('/') {
+        true => version.to_string(),
+        false => build(version, "build", &[])?,
+    };
+
+    Library::open(&path, Abi::ZlibNg)
+}
+
+/// Returns (MB/s, output bytes)
+pub fn measure(f: Function, mode: Mode, path: &str, level: i32) -> (f64, usize) {
+    let n = 5;
+
+    let mut output_len = 0;
+    let start = std::time::Instant::now();
+    for _ in 0..n {
+        output_len = f(mode, path, level, Allocation::HEAP);
+    }
+    let total = start.elapsed();
+
+    let bytes = std::fs::metadata(path).unwrap().len();
+    let mb_per_s = n as f64 * bytes as f64 / 1e6 / total.as_secs_f64();
+
+    (mb_per_s, output_len)
+}
+
+pub fn ng_versions_all(mode: Mode, path: &str, level: i32, versions: &[String]) {
+    // build everything up front, so that compiler output does not end up between the results
+    let mut libraries = Vec::new();
+    for version in versions {
+        match load(version) {
+            Ok(library) => {
+                println!("# {version}: {} ({})", library.version(), library.path());
+                libraries.push((version, library));
+            }
+            Err(e) => println!("# {version}: unavailable ({e})"),
+        }
+    }
+
+    println!("version, MB/s, output bytes");
+
+    #[cfg(feature = "ng")]
+    {
+        let (mb_per_s, output_len) = measure(helper::<crate::ZlibNg>, mode, path, level);
+        println!("vendored, {mb_per_s:.2}, {output_len}");
+    }
+
+    for (version, library) in libraries {
+        dylib::select(library);
+
+        let (mb_per_s, output_len) = measure(helper::<ZlibDylib>, mode, path, level);
+        println!("{version}, {mb_per_s:.2}, {output_len}");
+    }
+}
+
+#[cfg(feature = "rs")]
+pub fn ng_simd_all(mode: Mode, path: &str, level: i32, version: &str) {
+    let mut libraries = Vec::new();
+    for (name, options) in SIMD_BUILDS {
+        match build(version, &format!("build-{name}"), options)
+            .and_then(|p| Library::open(&p, Abi::ZlibNg))
+        {
+            Ok(library) => libraries.push((name, library)),
+            Err(e) => println!("# {name}: unavailable ({e})"),
+        }
+    }
+
+    let (rs_mb_per_s, rs_output_len) = measure(helper::<crate::ZlibRs>, mode, path, level);
+
+    println!("build, MB/s, % of rs, output bytes");
+    println!("rs, {rs_mb_per_s:.2}, 100.0, {rs_output_len}");
+
+    #[cfg(feature = "ng")]
+    {
+        let (mb_per_s, output_len) = measure(helper::<crate::ZlibNg>, mode, path, level);
+        let relative = 100.0 * mb_per_s / rs_mb_per_s;
+        println!("ng (vendored), {mb_per_s:.2}, {relative:.1}, {output_len}");
+    }
+
+    for (name, library) in libraries {
+        dylib::select(library);
+
+        let (mb_per_s, output_len) = measure(helper::<ZlibDylib>, mode, path, level);
+        let relative = 100.0 * mb_per_s / rs_mb_per_s;
+        println!("ng {version} {name}, {mb_per_s:.2}, {relative:.1}, {output_len}");
+    }
+}
+
+/// `libraries` is a release, or the paths of a native and a compat build
+pub fn ng_compat_all(mode: Mode, path: &str, level: i32, libraries: &[String]) {
+    let loaded = match libraries {
+        [version] => build(version, "build", &[])
+            .and_then(|native| Ok((native, build(version, "build-compat", &[COMPAT])?))),
+        [native, compat] => Ok((native.clone(), compat.clone())),
+        _ => panic!("expected a version, or a native and a compat library"),
+    }
+    .and_then(|(native, compat)| {
+        Ok((
+            Library::open(&native, Abi::ZlibNg)?,
+            Library::open(&compat, Abi::Zlib)?,
+        ))
+    });
+    let (native, compat) = match loaded {
+        Ok(libraries) => libraries,
+        Err(e) => panic!("zlib-ng unavailable: {e}"),
+    };
+    println!("# native: {} ({})", native.version(), native.path());
+    println!("# compat: {} ({})", compat.version(), compat.path());
+
+    println!("api, MB/s, % of native, output bytes");
+
+    dylib::select(native);
+    let (native_mb_per_s, native_output_len) = measure(helper::<ZlibDylib>, mode, path, level);
+    println!("native, {native_mb_per_s:.2}, 100.0, {native_output_len}");
+
+    dylib::select(compat);
+    let (mb_per_s, output_len) = measure(helper::<ZlibDylib>, mode, path, level);
+    let relative = 100.0 * mb_per_s / native_mb_per_s;
+    println!("compat, {mb_per_s:.2}, {relative:.1}, {output_len}");
+}
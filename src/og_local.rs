@@ -0,0 +1,81 @@
+//! Build a local zlib source tree, so that a patched zlib can be measured next to the vendored one
+//! without publishing a fork.
+//!
+//! `libz-sys` always builds the copy of zlib that it ships with. Instead, the directory in
+//! `ZLIB_BENCH_OG_SOURCE` is compiled as a shared library (this needs a C compiler), with the same
+//! files and defines that `libz-sys` uses, and loaded with `dlopen`. The library is rebuilt on every
+//! run, so edits to the source tree are always picked up.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::process::Command;
+
+use crate::dylib::{Abi, Library};
+use crate::ng_versions::run;
+
+/// The environment variable with the path of the zlib source tree
+pub const SOURCE_VARIABLE: &str = "ZLIB_BENCH_OG_SOURCE";
+
+/// The sources that `libz-sys` compiles
+const FILES: [&str; 15] = [
+    "adler32.c",
+    "compress.c",
+    "crc32.c",
+    "deflate.c",
+    "infback.c",
+    "inffast.c",
+    "inflate.c",
+    "inftrees.c",
+    "trees.c",
+    "uncompr.c",
+    "zutil.c",
+    "gzclose.c",
+    "gzlib.c",
+    "gzread.c",
+    "gzwrite.c",
+];
+
+/// Whether a source tree is configured
+pub fn configured() -> bool {
+    std::env::var_os(SOURCE_VARIABLE).is_some()
+}
+
+/// Compile `source` into a directory of its own, and return the path of the shared library
+fn build(source: &str) -> Result<String, String> {
+    let source = std::fs::canonicalize(source).map_err(|e| format!("{source}: {e}"))?;
+
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    let dir = format!(
+        "{}/target/og-local/{:016x}",
+        env!("CARGO_MANIFEST_DIR"),
+        hasher.finish()
+    );
+    std::fs::create_dir_all(&dir).map_err(|e| format!("{dir}: {e}"))?;
+
+    let library = format!(
+        "{dir}/{}z{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    );
+    run(
+        Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".into()))
+            .args(["-O3", "-fPIC", "-shared", "-DSTDC", "-D_LARGEFILE64_SOURCE"])
+            .args(["-o", &library])
+            .args(FILES.map(|file| source.join(file))),
+    )?;
+
+    Ok(library)
+}
+
+/// Compile the zlib source tree `source`, and load it
+pub fn open(source: &str) -> Result<&'static Library, String> {
+    Library::open(&build(source)?, Abi::Zlib)
+}
+
+pub fn load() -> Result<&'static Library, String> {
+    let Ok(source) = std::env::var(SOURCE_VARIABLE) else {
+        return Err(format!("set {SOURCE_VARIABLE} to a zlib source tree"));
+    };
+
+    open(&source)
+}
@@ -0,0 +1,166 @@
+//! The worst case for a stream: the input arrives one byte per call.
+//!
+//! Every call has to restore the state of the previous one, and the fast paths that need a few
+//! bytes of input at hand (bulk refills of the bit buffer, the decode loops that assume a whole
+//! symbol is there) never run. That measures the fixed cost of a call, and exercises the places
+//! where the state machine has to stop and resume at every possible point. Every implementation
+//! that streams is run through its `*_chunked` function with one byte of input per call, and the
+//! output is verified: inflated output against the whole-buffer output of the same implementation,
+//! deflated output by inflating it again.
+
+use std::time::Duration;
+
+use crate::{
+    deflate_config, inflate_config, timer, Capabilities, Chunks, DeflateImplementation, Mode,
+    ReturnCode,
+};
+
+/// The whole-buffer time, the one-byte-at-a-time time, and the uncompressed size
+type Measured = (Duration, Duration, usize);
+
+fn measure<T: DeflateImplementation>(
+    mode: Mode,
+    input: &[u8],
+    level: i32,
+) -> Result<Measured, String> {
+    if !T::CAPABILITIES.streaming {
+        return Err("streaming in chunks is unsupported".to_string());
+    }
+
+    let capacity = match mode {
+        Mode::Inflate => 1 << 28,
+        Mode::Deflate => 2 * input.len() + 1024,
+    };
+    let mut whole = vec![0u8; capacity];
+    let mut output = vec![0u8; capacity];
+
+    let start = timer::now();
+    let (whole, err) = match mode {
+        Mode::Inflate => T::uncompress_slice(&mut whole, input, inflate_config()),
+        Mode::Deflate => T::compress_slice(&mut whole, input, deflate_config(level)),
+    };
+    let whole_time = start.elapsed();
+    if err != ReturnCode::Ok {
+        return Err(format!("whole buffer: {err:?}"));
+    }
+
+    let chunks = Chunks {
+        input: 1,
+        output: core::ffi::c_uint::MAX as usize,
+    };
+    let start = timer::now();
+    let (output, err) = match mode {
+        Mode::Inflate => T::uncompress_chunked(&mut output, input, inflate_config(), chunks),
+        Mode::Deflate => T::compress_chunked(&mut output, input, deflate_config(level), chunks),
+    };
+    let one_byte_time = start.elapsed();
+    if err != ReturnCode::Ok {
+        return Err(format!("one byte at a time: {err:?}"));
+    }
+
+    let uncompressed = match mode {
+        Mode::Inflate if output != whole => {
+            return Err("the output differs from the whole-buffer output".to_string())
+        }
+        Mode::Inflate => output.len(),
+        Mode::Deflate => {
+            let mut decompressed = vec![0u8; input.len()];
+            let (decompressed, err) =
+                T::uncompress_slice(&mut decompressed, output, inflate_config());
+            if err != ReturnCode::Ok || decompressed != input {
+                return Err("the output does not decompress to the input".to_string());
+            }
+            input.len()
+        }
+    };
+
+    Ok((whole_time, one_byte_time, uncompressed))
+}
+
+type Measure = fn(Mode, &[u8], i32) -> Result<Measured, String>;
+
+const BACKENDS: &[(&str, Capabilities, Measure)] = &[
+    #[cfg(feature = "og")]
+    ("og", crate::ZlibOg::CAPABILITIES, measure::<crate::ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", crate::ZlibNg::CAPABILITIES, measure::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", crate::ZlibRs::CAPABILITIES, measure::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    (
+        "cloudflare",
+        crate::ZlibCloudflare::CAPABILITIES,
+        measure::<crate::ZlibCloudflare>,
+    ),
+    #[cfg(feature = "miniz")]
+    (
+        "miniz",
+        crate::MinizOxide::CAPABILITIES,
+        measure::<crate::MinizOxide>,
+    ),
+    #[cfg(feature = "miniz-c")]
+    (
+        "miniz-c",
+        crate::MinizC::CAPABILITIES,
+        measure::<crate::MinizC>,
+    ),
+    #[cfg(feature = "flate2")]
+    (
+        crate::FLATE2,
+        crate::Flate2::CAPABILITIES,
+        measure::<crate::Flate2>,
+    ),
+    #[cfg(feature = "classic")]
+    (
+        "classic",
+        crate::Classic::CAPABILITIES,
+        measure::<crate::Classic>,
+    ),
+    #[cfg(feature = "yazi")]
+    ("yazi", crate::Yazi::CAPABILITIES, measure::<crate::Yazi>),
+    (
+        "system",
+        crate::dylib::SystemZlib::CAPABILITIES,
+        measure::<crate::dylib::SystemZlib>,
+    ),
+];
+
+pub fn one_byte_all(mode: Mode, path: &str, level: i32) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    let mut failed = 0;
+
+    // every input byte is a call
+    println!("implementation, calls, MB/s (whole buffer), MB/s, ns per call, x slower");
+    for (name, capabilities, measure) in BACKENDS {
+        if let Err(reason) = capabilities.available() {
+            println!("# {name}: skipped, {reason}");
+            continue;
+        }
+        let (whole, one_byte, uncompressed) = match measure(mode, &input, level) {
+            Ok(measured) => measured,
+            Err(e) => {
+                println!("# {name}: FAILED, {e}");
+                failed += 1;
+                continue;
+            }
+        };
+
+        let mb = uncompressed as f64 / 1e6;
+        println!(
+            "{name}, {}, {:.2}, {:.2}, {:.1}, {:.1}",
+            input.len(),
+            mb / whole.as_secs_f64(),
+            mb / one_byte.as_secs_f64(),
+            one_byte.as_nanos() as f64 / input.len() as f64,
+            one_byte.as_secs_f64() / whole.as_secs_f64(),
+        );
+    }
+
+    if failed > 0 {
+        eprintln!("{failed} implementation(s) did not converge one byte at a time");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,204 @@
+//! Run the same benchmark on several machines over ssh, and merge the results.
+//!
+//! Which implementation is fastest depends a lot on the hardware: the available SIMD extensions,
+//! cache sizes, and how expensive unaligned loads and branch mispredictions are. `orchestrate`
+//! copies the harness (built for the baseline CPU of the remote architecture, not with the
+//! `target-cpu=native` of this machine) and the input to every host, runs the command there with `--jsonl`, and prints one table with a column per host.
+//!
+//! Hosts are anything that `ssh` and `scp` accept (e.g. `user@host`, or an alias from
+//! `~/.ssh/config`), and must accept a key without prompting.
+
+use std::process::Command;
+
+use crate::jsonl;
+use crate::qemu;
+
+/// Everything is copied into (and run from) this directory, relative to the remote home directory
+const REMOTE_DIR: &str = "zlib-bench";
+
+/// Options that the orchestrator sets itself, or that would write files on the remote host
+const FORBIDDEN: [&str; 2] = ["--jsonl", "--samples"];
+
+struct Machine {
+    host: String,
+    arch: String,
+    cpu: String,
+}
+
+/// A completed cell: (mode, level, implementation, MB/s)
+type Cell = (String, i32, String, f64);
+
+/// Quote `s` for the remote shell
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+fn ssh(host: &str, command: &str) -> Result<String, String> {
+    let output = Command::new("ssh")
+        .args(["-o", "BatchMode=yes", host, command])
+        .output()
+        .map_err(|e| format!("running ssh: {e}"))?;
+
+    match output.status.success() {
+        true => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+        false => Err(format!(
+            "`{command}` failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+fn scp(host: &str, files: &[&str]) -> Result<(), String> {
+    let status = Command::new("scp")
+        .args(["-q", "-o", "BatchMode=yes"])
+        .args(files)
+        .arg(format!("{host}:{REMOTE_DIR}/"))
+        .status()
+        .map_err(|e| format!("running scp: {e}"))?;
+
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("scp failed ({status})")),
+    }
+}
+
+fn describe(host: &str) -> Result<Machine, String> {
+    let output = ssh(
+        host,
+        "uname -m; grep -m1 -E '^(model name|Model|cpu model)' /proc/cpuinfo",
+    )?;
+    let mut lines = output.lines();
+
+    let arch = lines.next().unwrap_or("").trim().to_string();
+    let cpu = match lines.next().and_then(|line| line.split_once(':')) {
+        Some((_, cpu)) => cpu.trim().to_string(),
+        None => "-".to_string(),
+    };
+
+    Ok(Machine {
+        host: host.to_string(),
+        arch,
+        cpu,
+    })
+}
+
+/// The executable to run on a machine of the given architecture. This executable is built for the
+/// CPU of this machine, so even a machine of the same architecture gets a portable build.
+fn executable(arch: &str) -> Result<String, String> {
+    if arch == std::env::consts::ARCH {
+        return qemu::build_portable();
+    }
+
+    match qemu::TARGETS.iter().find(|(name, _, _)| *name == arch) {
+        Some((_, triple, gnu)) => qemu::build(triple, gnu),
+        None => Err(format!("no cross-compilation target for {arch}")),
+    }
+}
+
+/// Copy everything over, run the command, and return the completed cells
+fn run(machine: &Machine, path: &str, args: &[String]) -> Result<Vec<Cell>, String> {
+    let exe = executable(&machine.arch)?;
+
+    ssh(&machine.host, &format!("mkdir -p {REMOTE_DIR}"))?;
+    scp(&machine.host, &[&exe, path])?;
+
+    // the input is copied to the remote directory under its file name
+    let file_name = std::path::Path::new(path)
+        .file_name()
+        .unwrap()
+        .to_string_lossy();
+    let exe_name = std::path::Path::new(&exe)
+        .file_name()
+        .unwrap()
+        .to_string_lossy();
+
+    let args: Vec<_> = args
+        .iter()
+        .map(|arg| match arg == path {
+            true => quote(&file_name),
+            false => quote(arg),
+        })
+        .collect();
+
+    let events = ssh(
+        &machine.host,
+        &format!(
+            "cd {REMOTE_DIR} && ./{} {} --jsonl events.jsonl >&2 && cat events.jsonl",
+            quote(&exe_name),
+            args.join(" ")
+        ),
+    )?;
+
+    let mut cells = Vec::new();
+    for line in events.lines() {
+        if jsonl::field(line, "event").as_deref() != Some("cell") {
+            continue;
+        }
+
+        let field = |key| jsonl::field(line, key).ok_or(format!("no {key:?} in {line}"));
+        cells.push((
+            field("mode")?,
+            field("level")?.parse().map_err(|_| "invalid level")?,
+            field("implementation")?,
+            field("mb_per_s")?.parse().unwrap_or(f64::NAN),
+        ));
+    }
+
+    Ok(cells)
+}
+
+/// `args` is a complete `inflate-all` or `deflate-all` command line, that mentions `path`
+pub fn orchestrate_all(hosts: &[String], path: &str, args: &[String]) {
+    if let Some(arg) = args.iter().find(|arg| FORBIDDEN.contains(&arg.as_str())) {
+        panic!("option {arg:?} is not supported by orchestrate");
+    }
+
+    let mut machines = Vec::new();
+    for host in hosts {
+        match describe(host) {
+            Ok(machine) => {
+                println!("# {}: {}, {}", machine.host, machine.arch, machine.cpu);
+                machines.push(machine);
+            }
+            Err(e) => println!("# {host}: unreachable ({e})"),
+        }
+    }
+
+    // one host at a time: the runs would not disturb each other, but the uploads and the output would
+    let mut results = Vec::new();
+    for machine in &machines {
+        eprintln!("# running on {}", machine.host);
+        match run(machine, path, args) {
+            Ok(cells) => results.push((machine, cells)),
+            Err(e) => println!("# {}: failed ({e})", machine.host),
+        }
+    }
+
+    let columns: Vec<_> = results
+        .iter()
+        .map(|(machine, _)| format!("{} MB/s", machine.host))
+        .collect();
+    println!("mode, level, implementation, {}", columns.join(", "));
+
+    // the cells of the first machine that completed determine the rows
+    let Some((_, first)) = results.first() else {
+        return;
+    };
+    for (mode, level, implementation, _) in first {
+        let row: Vec<_> = results
+            .iter()
+            .map(|(_, cells)| {
+                let cell = cells
+                    .iter()
+                    .find(|(m, l, i, _)| (m, l, i) == (mode, level, implementation));
+                match cell {
+                    Some((_, _, _, mb_per_s)) => format!("{mb_per_s:.2}"),
+                    None => "-".to_string(),
+                }
+            })
+            .collect();
+
+        println!("{mode}, {level}, {implementation}, {}", row.join(", "));
+    }
+}
@@ -0,0 +1,108 @@
+//! Multi-threaded gzip compression with `gzp`.
+//!
+//! gzp splits the input in blocks that are compressed on several threads (each block primed with
+//! the end of the previous one as its dictionary), and writes one ordinary gzip stream. It
+//! compresses with flate2, so the `flate2-*` features select the implementation; the scaling is
+//! compared with a single thread of zlib-ng.
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use gzp::deflate::Gzip;
+use gzp::par::compress::{ParCompress, ParCompressBuilder};
+use gzp::ZWriter;
+
+use crate::{DeflateConfig, DeflateImplementation, Method, ReturnCode, Strategy, ZlibNg, FLATE2};
+
+/// gzp needs to own its writer, so the output is shared with it
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Returns the time taken and the size of the output
+fn compress_gzp(input: &[u8], level: i32, threads: usize) -> Result<(Duration, usize), String> {
+    let output = Shared::default();
+
+    let start = Instant::now();
+    let mut writer: ParCompress<Gzip> = ParCompressBuilder::new()
+        .num_threads(threads)
+        .map_err(|e| e.to_string())?
+        .compression_level(gzp::Compression::new(level as u32))
+        .from_writer(output.clone());
+    writer.write_all(input).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    let elapsed = start.elapsed();
+
+    let len = output.0.lock().unwrap().len();
+    Ok((elapsed, len))
+}
+
+/// Returns the time taken and the size of the output
+fn compress_ng(input: &[u8], level: i32) -> (Duration, usize) {
+    let config = DeflateConfig {
+        level,
+        method: Method::Deflated,
+        // gzip, like gzp
+        window_bits: 31,
+        mem_level: 8,
+        strategy: Strategy::Default,
+    };
+
+    let mut output = vec![0u8; 2 * input.len() + 1024];
+    let start = Instant::now();
+    let (compressed, err) = ZlibNg::compress_slice(&mut output, input, config);
+    let elapsed = start.elapsed();
+    assert_eq!(err, ReturnCode::Ok);
+
+    (elapsed, compressed.len())
+}
+
+pub fn gzp_all(path: &str, level: i32, threads: &[usize]) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+    if !(0..=9).contains(&level) {
+        panic!("invalid level {level}, gzp supports 0 to 9");
+    }
+
+    // the best of a couple of runs
+    let n = 3;
+    let mb = input.len() as f64 / 1e6;
+
+    let (ng, ng_len) = (0..n).map(|_| compress_ng(&input, level)).min().unwrap();
+    let ng_mb_per_s = mb / ng.as_secs_f64();
+
+    println!("# gzp compresses with {FLATE2}");
+    println!("implementation, threads, MB/s, speedup over ng, output bytes");
+    println!("ng, 1, {ng_mb_per_s:.2}, 1.00, {ng_len}");
+
+    for &threads in threads {
+        let mut best = None;
+        for _ in 0..n {
+            match compress_gzp(&input, level, threads) {
+                Ok(run) => best = Some(best.map_or(run, |best| Ord::min(best, run))),
+                Err(e) => {
+                    println!("gzp, {threads}, FAILED ({e})");
+                    break;
+                }
+            }
+        }
+
+        if let Some((elapsed, len)) = best {
+            let mb_per_s = mb / elapsed.as_secs_f64();
+            let speedup = mb_per_s / ng_mb_per_s;
+            println!("gzp, {threads}, {mb_per_s:.2}, {speedup:.2}, {len}");
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Profile each implementation with `perf record` and summarize where the time went.
+
+use std::process::Command;
+
+/// The number of functions shown per implementation.
+const TOP: usize = 5;
+
+/// The hottest functions as (percentage of samples, symbol) pairs, or why profiling failed
+pub type Profile = Result<Vec<(f64, String)>, String>;
+
+/// Run this executable with `args` under `perf record`, and return the hottest functions.
+pub fn perf_record(name: &str, args: &[&str]) -> Profile {
+    let data = std::env::temp_dir().join(format!("zlib-bench-{name}.perf.data"));
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+
+    let output = Command::new("perf")
+        .arg("record")
+        .arg("--quiet")
+        .arg("--output")
+        .arg(&data)
+        .arg("--")
+        .arg(exe)
+        .args(args)
+        .output()
+        .map_err(|e| format!("error running perf: {e}"))?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    let output = Command::new("perf")
+        .arg("report")
+        .arg("--input")
+        .arg(&data)
+        .args(["--stdio", "--no-children", "--sort", "symbol"])
+        .output()
+        .map_err(|e| format!("error running perf: {e}"))?;
+
+    let _ = std::fs::remove_file(&data);
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    // lines look like `    35.21%  [.] inflate_fast`
+    let report = String::from_utf8_lossy(&output.stdout);
+    let functions = report
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (percentage, symbol) = line.split_once('%')?;
+            let symbol = symbol.trim();
+            let symbol = symbol.split_once("] ").map_or(symbol, |(_, s)| s);
+
+            Some((percentage.trim().parse().ok()?, symbol.to_string()))
+        })
+        .take(TOP)
+        .collect();
+
+    Ok(functions)
+}
+
+pub fn print_profiles(profiles: &[(&str, Profile)]) {
+    println!();
+    println!("implementation, samples %, function");
+    for (name, profile) in profiles {
+        match profile {
+            Ok(functions) => {
+                for (percentage, symbol) in functions {
+                    println!("{name}, {percentage:.2}, {symbol}");
+                }
+            }
+            Err(e) => println!("{name}, 0, perf failed: {e}"),
+        }
+    }
+}
@@ -0,0 +1,84 @@
+//! A progress line on stderr for the long runs: how far along the run is, an estimate of the time
+//! that is left, and what runs right now.
+//!
+//! A matrix or a corpus easily takes many minutes, and without output there is no telling a slow
+//! run from an implementation that hangs inside FFI. On a terminal the line is redrawn in place
+//! before every call, and erased before the report is printed. Otherwise (a log file, CI) it is
+//! written as a line of its own, at most every 10 seconds. Stdout, with the results, is untouched.
+
+use std::fmt::Display;
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+/// How often the line is written when stderr is not a terminal
+const INTERVAL: Duration = Duration::from_secs(10);
+
+pub struct Progress {
+    /// the work of the whole run, in the unit of the caller (calls, bytes)
+    total: f64,
+    done: f64,
+    start: Instant,
+    terminal: bool,
+    /// when the line was last written, to throttle it when stderr is not a terminal
+    written: Option<Instant>,
+}
+
+impl Progress {
+    pub fn new(total: f64) -> Self {
+        Self {
+            total,
+            done: 0.0,
+            start: Instant::now(),
+            terminal: std::io::stderr().is_terminal(),
+            written: None,
+        }
+    }
+
+    /// Count `work` as done
+    pub fn advance(&mut self, work: f64) {
+        self.done += work;
+    }
+
+    /// Show that `what` starts running now
+    pub fn running(&mut self, what: impl Display) {
+        if !self.terminal && self.written.is_some_and(|at| at.elapsed() < INTERVAL) {
+            return;
+        }
+        self.written = Some(Instant::now());
+
+        let fraction = match self.total > 0.0 {
+            true => f64::min(self.done / self.total, 1.0),
+            false => 0.0,
+        };
+        // assumes that the rest goes at the speed so far
+        let eta = match self.done > 0.0 {
+            true => format_eta(self.start.elapsed().mul_f64((1.0 - fraction) / fraction)),
+            false => "?".to_string(),
+        };
+        let line = format!("# {:.0}%, eta {eta}: {what}", 100.0 * fraction);
+
+        let mut stderr = std::io::stderr().lock();
+        let _ = match self.terminal {
+            true => write!(stderr, "\r\x1b[2K{line}"),
+            false => writeln!(stderr, "{line}"),
+        };
+        let _ = stderr.flush();
+    }
+
+    /// Erase the line, before anything else is printed
+    pub fn clear(&mut self) {
+        if self.terminal {
+            let _ = write!(std::io::stderr(), "\r\x1b[2K");
+        }
+    }
+}
+
+/// e.g. `1h02m`, `3m05s` or `12s`
+fn format_eta(eta: Duration) -> String {
+    let seconds = eta.as_secs();
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m{s:02}s"),
+        (h, m, _) => format!("{h}h{m:02}m"),
+    }
+}
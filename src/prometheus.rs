@@ -0,0 +1,71 @@
+//! Push results to a Prometheus pushgateway.
+//!
+//! Teams that run the benchmark continuously on dedicated machines already alert on metrics in
+//! Prometheus. Every completed cell is pushed as a group of gauges, grouped by mode, level and
+//! implementation, so that a regression in one cell does not overwrite the others. The pushgateway
+//! API is plain HTTP, so a `TcpStream` is all we need.
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+pub struct Cell<'a> {
+    pub mode: &'a str,
+    pub level: i32,
+    pub implementation: &'a str,
+    pub mb_per_s: f64,
+    pub steady_mb_per_s: f64,
+    pub output_bytes: usize,
+    pub minor_faults: u64,
+}
+
+fn body(cell: &Cell) -> String {
+    let gauges = [
+        ("zlib_bench_throughput_mb_per_second", cell.mb_per_s),
+        (
+            "zlib_bench_steady_throughput_mb_per_second",
+            cell.steady_mb_per_s,
+        ),
+        ("zlib_bench_output_bytes", cell.output_bytes as f64),
+        (
+            "zlib_bench_minor_faults_per_iteration",
+            cell.minor_faults as f64,
+        ),
+    ];
+
+    let mut body = String::new();
+    for (name, value) in gauges {
+        body.push_str(&format!("# TYPE {name} gauge\n{name} {value}\n"));
+    }
+    body
+}
+
+/// Push one cell to the pushgateway at `address` (`host:port`, optionally prefixed with `http://`)
+pub fn push(address: &str, cell: &Cell) -> Result<(), String> {
+    let host = address.trim_start_matches("http://").trim_end_matches('/');
+    let path = format!(
+        "/metrics/job/zlib_bench/mode/{}/level/{}/implementation/{}",
+        cell.mode, cell.level, cell.implementation
+    );
+    let body = body(cell);
+
+    let mut stream = TcpStream::connect(host).map_err(|e| format!("connecting to {host}: {e}"))?;
+    let request = format!(
+        "PUT {path} HTTP/1.1\r\nHost: {host}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream
+        .write_all(request.as_bytes())
+        .map_err(|e| e.to_string())?;
+
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .map_err(|e| e.to_string())?;
+
+    // e.g. `HTTP/1.1 200 OK`
+    let status = response.lines().next().unwrap_or("");
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(format!("pushgateway responded with {status:?}")),
+    }
+}
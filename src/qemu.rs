@@ -0,0 +1,102 @@
+//! Cross-compile the harness and run it under qemu-user.
+//!
+//! Timings under emulation are meaningless, but the inflate mode verifies the hash of its output,
+//! so this catches miscompilations and endianness bugs on architectures we can't run natively.
+
+use std::process::Command;
+
+use crate::FUNCTIONS;
+
+/// (name used on the command line, rust target triple, gnu prefix of the cross toolchain)
+pub const TARGETS: [(&str, &str, &str); 3] = [
+    ("aarch64", "aarch64-unknown-linux-gnu", "aarch64-linux-gnu"),
+    ("s390x", "s390x-unknown-linux-gnu", "s390x-linux-gnu"),
+    ("riscv64", "riscv64gc-unknown-linux-gnu", "riscv64-linux-gnu"),
+];
+
+pub fn qemu_all(arch: &str, path: &str) {
+    let targets: Vec<_> = match arch {
+        "all" => TARGETS.to_vec(),
+        _ => match TARGETS.iter().find(|(name, _, _)| *name == arch) {
+            Some(target) => vec![*target],
+            None => panic!("invalid architecture {arch:?}"),
+        },
+    };
+
+    println!("architecture, implementation, status, seconds");
+    for (name, triple, gnu) in targets {
+        let exe = match build(triple, gnu) {
+            Ok(exe) => exe,
+            Err(e) => {
+                println!("{name}, *, build failed ({e}), 0");
+                continue;
+            }
+        };
+
+        for (implementation, _, _, _, _, _) in FUNCTIONS {
+            let start = std::time::Instant::now();
+            let status = Command::new(format!("qemu-{name}"))
+                .env("QEMU_LD_PREFIX", format!("/usr/{gnu}"))
+                .arg(&exe)
+                .args(["inflate", implementation, path])
+                .output();
+            let delta = start.elapsed();
+
+            let status = match status {
+                Ok(output) if output.status.success() => "ok".to_string(),
+                Ok(output) => format!("FAILED ({})", output.status),
+                Err(e) => format!("FAILED ({e})"),
+            };
+
+            println!("{name}, {implementation}, {status}, {}", delta.as_secs_f64());
+        }
+    }
+}
+
+/// Cross-compile the harness, and return the path of the executable
+pub fn build(triple: &str, gnu: &str) -> Result<String, String> {
+    let env_triple = triple.to_uppercase().replace('-', "_");
+
+    let mut command = cargo_build(&["--target", triple]);
+
+    // respect a toolchain configuration that is already present in the environment
+    let linker = format!("CARGO_TARGET_{env_triple}_LINKER");
+    if std::env::var_os(&linker).is_none() {
+        command.env(linker, format!("{gnu}-gcc"));
+    }
+    let cc = format!("CC_{}", triple.replace('-', "_"));
+    if std::env::var_os(&cc).is_none() {
+        command.env(cc, format!("{gnu}-gcc"));
+    }
+
+    run(command, &format!("target/{triple}/release/zlib-bench"))
+}
+
+/// Build the harness for the baseline CPU of this architecture, for other machines that may not
+/// have the extensions of this one, and return the path of the executable
+pub fn build_portable() -> Result<String, String> {
+    let command = cargo_build(&["--target-dir", "target/portable"]);
+    run(command, "target/portable/release/zlib-bench")
+}
+
+/// `cargo build --release` with `args`
+fn cargo_build(args: &[&str]) -> Command {
+    let mut command = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()));
+    command
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["build", "--release"])
+        .args(args)
+        // `.cargo/config.toml` sets `target-cpu=native`, which is wrong for any other machine
+        .env("RUSTFLAGS", "-C symbol-mangling-version=v0");
+
+    command
+}
+
+/// Run a `cargo_build` command, and return the path of `exe`, relative to the manifest directory
+fn run(mut command: Command, exe: &str) -> Result<String, String> {
+    match command.status() {
+        Ok(status) if status.success() => Ok(format!("{}/{exe}", env!("CARGO_MANIFEST_DIR"))),
+        Ok(status) => Err(status.to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
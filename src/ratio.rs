@@ -0,0 +1,68 @@
+//! How much smaller could the output be?
+//!
+//! Compression ratio is only part of the picture, but it needs a reference point. Zopfli searches
+//! far harder than any of the other implementations, and is much too slow to time next to them, so
+//! here only the output sizes are compared: every implementation at one level against zopfli.
+
+use crate::{DeflateConfig, DeflateImplementation, Method, ReturnCode, Strategy, Zopfli};
+
+/// Returns the compressed size
+fn compress<T: DeflateImplementation>(input: &[u8], level: i32) -> usize {
+    let config = DeflateConfig {
+        level,
+        method: Method::Deflated,
+        window_bits: 15,
+        mem_level: 8,
+        strategy: Strategy::Default,
+    };
+
+    let mut output = vec![0u8; 2 * input.len() + 1024];
+    let (compressed, err) = T::compress_slice(&mut output, input, config);
+    assert_eq!(err, ReturnCode::Ok, "{}", T::name());
+
+    compressed.len()
+}
+
+type Compress = fn(&[u8], i32) -> usize;
+
+const BACKENDS: &[(&str, Compress)] = &[
+    #[cfg(feature = "og")]
+    ("og", compress::<crate::ZlibOg>),
+    #[cfg(feature = "ng")]
+    ("ng", compress::<crate::ZlibNg>),
+    #[cfg(feature = "rs")]
+    ("rs", compress::<crate::ZlibRs>),
+    #[cfg(feature = "cloudflare")]
+    ("cloudflare", compress::<crate::ZlibCloudflare>),
+    #[cfg(feature = "miniz")]
+    ("miniz", compress::<crate::MinizOxide>),
+    #[cfg(feature = "libdeflate")]
+    ("libdeflate", compress::<crate::Libdeflate>),
+    #[cfg(feature = "miniz-c")]
+    ("miniz-c", compress::<crate::MinizC>),
+];
+
+pub fn ratio_all(path: &str, level: i32) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    // zopfli first, the other implementations are compared with it
+    let start = std::time::Instant::now();
+    let ceiling = compress::<Zopfli>(&input, level);
+    println!("# zopfli took {:.1}s", start.elapsed().as_secs_f64());
+
+    println!("implementation, compressed bytes, ratio, % larger than zopfli");
+    println!(
+        "zopfli, {ceiling}, {:.3}, 0.00",
+        input.len() as f64 / ceiling as f64
+    );
+    for (name, compress) in BACKENDS {
+        let len = compress(&input, level);
+        println!(
+            "{name}, {len}, {:.3}, {:.2}",
+            input.len() as f64 / len as f64,
+            100.0 * (len as f64 - ceiling as f64) / ceiling as f64
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Many small records through one reused stream.
+//!
+//! Object stores and metadata services compress lots of small values independently. Setting up a
+//! deflate stream (and its 256K+ of state) per value is wasteful, so they keep one stream around and
+//! `deflateReset` it between values. At these sizes the per-call overhead and the cost of the reset
+//! matter as much as the compression itself, and the latency of individual records is what callers
+//! notice. The reading side does the same with `inflateReset`, and both are compared with a fresh
+//! stream per record to show what the reuse saves.
+
+use core::mem::MaybeUninit;
+use std::time::Duration;
+
+use crate::{
+    DeflateConfig, DeflateImplementation, Flush, InflateConfig, Method, ReturnCode, Strategy,
+    ZlibImplementation, ZlibOg,
+};
+
+/// The maximum number of records
+const RECORDS: usize = 50_000;
+
+fn config(level: i32) -> DeflateConfig {
+    DeflateConfig {
+        level,
+        method: Method::Deflated,
+        window_bits: 15,
+        mem_level: 8,
+        strategy: Strategy::Default,
+    }
+}
+
+/// Compress every record with one stream that is reset in between.
+///
+/// Returns the compressed records, and the time taken for each record.
+fn reused<T: ZlibImplementation>(
+    records: &[&[u8]],
+    level: i32,
+) -> Result<(Vec<Vec<u8>>, Vec<Duration>), ReturnCode> {
+    let mut stream = MaybeUninit::zeroed();
+    let err = T::deflate_init(stream.as_mut_ptr(), config(level));
+    if err != ReturnCode::Ok {
+        return Err(err);
+    }
+    let stream = unsafe { stream.assume_init_mut() };
+
+    let output = vec![0u8; 2 * records.iter().map(|r| r.len()).max().unwrap_or(0) + 1024];
+    let mut compressed = Vec::with_capacity(records.len());
+    let mut latencies = Vec::with_capacity(records.len());
+
+    let result = (|| {
+        for record in records {
+            let start = crate::timer::now();
+
+            let err = T::deflate_reset(stream);
+            if err != ReturnCode::Ok {
+                return Err(err);
+            }
+
+            T::set_in(stream, record);
+            T::set_out(stream, &output);
+
+            let err = T::deflate(stream, Flush::Finish);
+            if err != ReturnCode::StreamEnd {
+                return Err(err);
+            }
+
+            latencies.push(start.elapsed());
+
+            let len = T::total_out(stream);
+            compressed.push(output[..len].to_vec());
+        }
+
+        Ok(())
+    })();
+
+    T::deflate_end(stream);
+    result?;
+
+    Ok((compressed, latencies))
+}
+
+/// Compress every record with a fresh stream, for comparison
+fn fresh<T: ZlibImplementation>(records: &[&[u8]], level: i32) -> Duration {
+    let mut output = vec![0u8; 2 * records.iter().map(|r| r.len()).max().unwrap_or(0) + 1024];
+
+    let start = std::time::Instant::now();
+    for record in records {
+        let (_, err) = T::compress_slice(&mut output, record, config(level));
+        assert_eq!(err, ReturnCode::Ok);
+    }
+    start.elapsed()
+}
+
+/// Decompress every compressed record with one stream that is reset in between
+fn inflate_reused<T: ZlibImplementation>(compressed: &[Vec<u8>], max: usize) -> Duration {
+    let mut stream = MaybeUninit::zeroed();
+    let config = InflateConfig { window_bits: 15 };
+    assert_eq!(T::inflate_init(stream.as_mut_ptr(), config), ReturnCode::Ok);
+    let stream = unsafe { stream.assume_init_mut() };
+
+    let output = vec![0u8; max];
+
+    let start = std::time::Instant::now();
+    for record in compressed {
+        assert_eq!(T::inflate_reset(stream), ReturnCode::Ok);
+
+        T::set_in(stream, record);
+        T::set_out(stream, &output);
+
+        assert_eq!(T::inflate(stream, Flush::Finish), ReturnCode::StreamEnd);
+    }
+    let elapsed = start.elapsed();
+
+    T::inflate_end(stream);
+
+    elapsed
+}
+
+/// Decompress every compressed record with a fresh stream, for comparison
+fn inflate_fresh<T: ZlibImplementation>(compressed: &[Vec<u8>], max: usize) -> Duration {
+    let mut output = vec![0u8; max];
+    let config = InflateConfig { window_bits: 15 };
+
+    let start = std::time::Instant::now();
+    for record in compressed {
+        let (_, err) = T::uncompress_slice(&mut output, record, config);
+        assert_eq!(err, ReturnCode::Ok);
+    }
+    start.elapsed()
+}
+
+type Reused = fn(&[&[u8]], i32) -> Result<(Vec<Vec<u8>>, Vec<Duration>), ReturnCode>;
+type Fresh = fn(&[&[u8]], i32) -> Duration;
+type Inflate = fn(&[Vec<u8>], usize) -> Duration;
+
+/// The deflate with a reused and a fresh stream, then the same for inflate
+const BACKENDS: &[(&str, Reused, Fresh, Inflate, Inflate)] = &[
+    (
+        "og",
+        reused::<ZlibOg>,
+        fresh::<ZlibOg>,
+        inflate_reused::<ZlibOg>,
+        inflate_fresh::<ZlibOg>,
+    ),
+    #[cfg(feature = "ng")]
+    (
+        "ng",
+        reused::<crate::ZlibNg>,
+        fresh::<crate::ZlibNg>,
+        inflate_reused::<crate::ZlibNg>,
+        inflate_fresh::<crate::ZlibNg>,
+    ),
+    #[cfg(feature = "rs")]
+    (
+        "rs",
+        reused::<crate::ZlibRs>,
+        fresh::<crate::ZlibRs>,
+        inflate_reused::<crate::ZlibRs>,
+        inflate_fresh::<crate::ZlibRs>,
+    ),
+    #[cfg(feature = "cloudflare")]
+    (
+        "cloudflare",
+        reused::<crate::ZlibCloudflare>,
+        fresh::<crate::ZlibCloudflare>,
+        inflate_reused::<crate::ZlibCloudflare>,
+        inflate_fresh::<crate::ZlibCloudflare>,
+    ),
+];
+
+/// How much of the time with a fresh stream per record reusing one stream saves, in percent
+fn saved(reused: Duration, fresh: Duration) -> f64 {
+    100.0 * (1.0 - reused.as_secs_f64() / fresh.as_secs_f64())
+}
+
+/// The latency at `percentile` (0.0 ..= 1.0) of sorted latencies, in microseconds
+fn percentile(sorted: &[Duration], percentile: f64) -> f64 {
+    let i = ((sorted.len() - 1) as f64 * percentile).round() as usize;
+    sorted[i].as_secs_f64() * 1e6
+}
+
+pub fn records_all(path: &str, level: i32, record_size: usize) {
+    let Ok(input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    if record_size == 0 {
+        eprintln!("records: the record size must be at least 1 byte");
+        std::process::exit(1);
+    }
+    if input.is_empty() {
+        eprintln!("records: {path:?} is empty, there are no records to measure");
+        std::process::exit(1);
+    }
+
+    let records: Vec<&[u8]> = input.chunks(record_size).take(RECORDS).collect();
+    let bytes: usize = records.iter().map(|r| r.len()).sum();
+    println!(
+        "# {} records of {record_size} bytes, {bytes} bytes total",
+        records.len()
+    );
+
+    println!("implementation, records/s, records/s (fresh stream), compressed bytes, p50 us, p99 us, p99.9 us, max us, stddev us, % saved by reuse, inflate records/s, inflate records/s (fresh stream), inflate % saved by reuse");
+    for (name, reused, fresh, inflate_reused, inflate_fresh) in BACKENDS {
+        let (compressed, mut latencies) = match reused(&records, level) {
+            Ok(result) => result,
+            Err(err) => {
+                println!("{name}, FAILED ({err:?}), 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0");
+                continue;
+            }
+        };
+
+        for (record, compressed) in records.iter().zip(&compressed) {
+            let mut output = vec![0u8; record.len()];
+            let config = InflateConfig { window_bits: 15 };
+            let (output, err) = ZlibOg::uncompress_slice(&mut output, compressed, config);
+            assert_eq!(err, ReturnCode::Ok, "{name}: invalid record");
+            assert!(output == *record, "{name}: roundtrip mismatch");
+        }
+
+        let fresh_time = fresh(&records, level);
+
+        let max = records.iter().map(|r| r.len()).max().unwrap_or(0);
+        let inflate_time = inflate_reused(&compressed, max);
+        let inflate_fresh_time = inflate_fresh(&compressed, max);
+
+        let total: Duration = latencies.iter().sum();
+        let n = latencies.len() as f64;
+        let mean = total.as_secs_f64() * 1e6 / n;
+        let variance = latencies
+            .iter()
+            .map(|d| (d.as_secs_f64() * 1e6 - mean).powi(2))
+            .sum::<f64>()
+            / n;
+
+        latencies.sort();
+
+        println!(
+            "{name}, {:.0}, {:.0}, {}, {:.2}, {:.2}, {:.2}, {:.2}, {:.2}, {:.1}, {:.0}, {:.0}, {:.1}",
+            n / total.as_secs_f64(),
+            n / fresh_time.as_secs_f64(),
+            compressed.iter().map(|c| c.len()).sum::<usize>(),
+            percentile(&latencies, 0.5),
+            percentile(&latencies, 0.99),
+            percentile(&latencies, 0.999),
+            percentile(&latencies, 1.0),
+            variance.sqrt(),
+            saved(total, fresh_time),
+            n / inflate_time.as_secs_f64(),
+            n / inflate_fresh_time.as_secs_f64(),
+            saved(inflate_time, inflate_fresh_time),
+        );
+    }
+}
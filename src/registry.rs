@@ -0,0 +1,267 @@
+//! Backends that other crates add at runtime, without patching this crate.
+//!
+//! A crate that depends on `zlib-bench` implements `Backend`, registers it, and then runs the
+//! command line interface:
+//!
+//! ```ignore
+//! fn main() {
+//!     zlib_bench::registry::register_backend("mine", Box::new(Mine));
+//!     zlib_bench::main()
+//! }
+//! ```
+//!
+//! A registered backend can be run by name like a built-in one, and is measured by `deflate-all`,
+//! `inflate-all` and `stored-all` after the built-in backends.
+
+use std::sync::{Mutex, OnceLock};
+
+use crate::{
+    call, helper, repeat, roundtrip, Call, Capabilities, DeflateConfig, DeflateImplementation,
+    Function, Implementation, InflateConfig, Mode, ReturnCode, Runs, FUNCTIONS,
+};
+
+/// A deflate implementation that works on whole buffers, like the built-in backends
+pub trait Backend: Send + Sync {
+    /// Decompress all of `input` into `output`, and return the part of `output` that was written
+    fn uncompress_slice<'a>(
+        &self,
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode);
+
+    /// Compress all of `input` into `output`, and return the part of `output` that was written
+    fn compress_slice<'a>(
+        &self,
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode);
+
+    /// What the backend supports, configurations outside of it are skipped. A backend only gets
+    /// whole buffers, so it never streams, whatever `streaming` says.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::ZLIB
+    }
+
+    /// The version that the `list` command reports
+    fn version(&self) -> String {
+        "unknown".to_string()
+    }
+
+    /// How the backend was built, reported before it is measured
+    fn build(&self) -> String {
+        "unknown".to_string()
+    }
+}
+
+/// What `backend` supports, without streaming: `Backend` has no chunked API
+fn capabilities(backend: &dyn Backend) -> Capabilities {
+    Capabilities {
+        streaming: false,
+        ..backend.capabilities()
+    }
+}
+
+/// The commands take plain function pointers, so every registered backend gets one of these slots
+const SLOTS: usize = 8;
+
+type Registry = Vec<(&'static str, &'static dyn Backend)>;
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Vec::new());
+
+/// The registry once the first backend was looked up. From then on it doesn't change, so that a
+/// compress or uncompress call doesn't have to take a lock.
+static FROZEN: OnceLock<Registry> = OnceLock::new();
+
+fn frozen() -> &'static Registry {
+    FROZEN.get_or_init(|| REGISTRY.lock().unwrap().clone())
+}
+
+/// Add a backend that is run under `name`. Registered backends live until the process exits.
+pub fn register_backend(name: &str, backend: Box<dyn Backend>) {
+    let mut registry = REGISTRY.lock().unwrap();
+
+    if FROZEN.get().is_some() {
+        panic!("backends must be registered before the benchmarks run");
+    }
+
+    let mut taken = FUNCTIONS
+        .iter()
+        .map(|(n, _, _, _, _, _)| n)
+        .chain(registry.iter().map(|(n, _)| n));
+    if taken.any(|n| *n == name) {
+        panic!("a backend named {name:?} already exists");
+    }
+    if name == "all" {
+        panic!("\"all\" selects every backend, and can't be the name of one");
+    }
+    if registry.len() == SLOTS {
+        panic!("at most {SLOTS} backends can be registered");
+    }
+
+    registry.push((String::leak(name.to_string()), Box::leak(backend)));
+}
+
+/// The `helper`, version and build functions of a slot
+type SlotFunctions = (Function, Call, fn() -> String, fn() -> String);
+
+/// The registered backends, in the order in which they were registered
+pub(crate) fn registered() -> Vec<Implementation> {
+    const SLOT_FUNCTIONS: [SlotFunctions; SLOTS] = [
+        (
+            helper::<Slot<0>>,
+            call::<Slot<0>>,
+            Slot::<0>::version,
+            Slot::<0>::build,
+        ),
+        (
+            helper::<Slot<1>>,
+            call::<Slot<1>>,
+            Slot::<1>::version,
+            Slot::<1>::build,
+        ),
+        (
+            helper::<Slot<2>>,
+            call::<Slot<2>>,
+            Slot::<2>::version,
+            Slot::<2>::build,
+        ),
+        (
+            helper::<Slot<3>>,
+            call::<Slot<3>>,
+            Slot::<3>::version,
+            Slot::<3>::build,
+        ),
+        (
+            helper::<Slot<4>>,
+            call::<Slot<4>>,
+            Slot::<4>::version,
+            Slot::<4>::build,
+        ),
+        (
+            helper::<Slot<5>>,
+            call::<Slot<5>>,
+            Slot::<5>::version,
+            Slot::<5>::build,
+        ),
+        (
+            helper::<Slot<6>>,
+            call::<Slot<6>>,
+            Slot::<6>::version,
+            Slot::<6>::build,
+        ),
+        (
+            helper::<Slot<7>>,
+            call::<Slot<7>>,
+            Slot::<7>::version,
+            Slot::<7>::build,
+        ),
+    ];
+
+    let backends = frozen().iter().zip(SLOT_FUNCTIONS);
+    backends
+        .map(|(&(name, backend), (f, call, version, build))| {
+            (name, f, call, capabilities(backend), version, build)
+        })
+        .collect()
+}
+
+/// `repeat` for a slot
+type SlotRepeat = fn(Mode, &str, i32, Runs) -> usize;
+
+/// Run the registered backend `name` on its own, or return `None` when there is no such backend
+pub(crate) fn single(name: &str, mode: Mode, path: &str, level: i32, runs: Runs) -> Option<usize> {
+    const SLOT_REPEAT: [SlotRepeat; SLOTS] = [
+        repeat::<Slot<0>>,
+        repeat::<Slot<1>>,
+        repeat::<Slot<2>>,
+        repeat::<Slot<3>>,
+        repeat::<Slot<4>>,
+        repeat::<Slot<5>>,
+        repeat::<Slot<6>>,
+        repeat::<Slot<7>>,
+    ];
+
+    let index = frozen().iter().position(|(n, _)| *n == name)?;
+    let backend = frozen()[index].1;
+
+    if let Err(reason) = capabilities(backend).check(mode, level) {
+        eprintln!("{name}: {reason}");
+        std::process::exit(1);
+    }
+
+    Some(SLOT_REPEAT[index](mode, path, level, runs))
+}
+
+/// `roundtrip::roundtrip` for a slot
+type SlotRoundtrip = fn(&str, i32, Runs);
+
+/// Compress and decompress with the registered backend `name`, or return `None` when there is no
+/// such backend
+pub(crate) fn roundtrip(name: &str, path: &str, level: i32, runs: Runs) -> Option<()> {
+    const SLOT_ROUNDTRIP: [SlotRoundtrip; SLOTS] = [
+        roundtrip::roundtrip::<Slot<0>>,
+        roundtrip::roundtrip::<Slot<1>>,
+        roundtrip::roundtrip::<Slot<2>>,
+        roundtrip::roundtrip::<Slot<3>>,
+        roundtrip::roundtrip::<Slot<4>>,
+        roundtrip::roundtrip::<Slot<5>>,
+        roundtrip::roundtrip::<Slot<6>>,
+        roundtrip::roundtrip::<Slot<7>>,
+    ];
+
+    let index = frozen().iter().position(|(n, _)| *n == name)?;
+    let backend = frozen()[index].1;
+
+    let capabilities = capabilities(backend);
+    let supported = capabilities.check(Mode::Deflate, level);
+    if let Err(reason) = supported.and_then(|()| capabilities.check(Mode::Inflate, level)) {
+        eprintln!("{name}: {reason}");
+        std::process::exit(1);
+    }
+
+    SLOT_ROUNDTRIP[index](path, level, runs);
+    Some(())
+}
+
+/// The backend that was registered as the `I`th
+struct Slot<const I: usize>;
+
+impl<const I: usize> Slot<I> {
+    fn backend() -> &'static dyn Backend {
+        frozen()[I].1
+    }
+}
+
+impl<const I: usize> DeflateImplementation for Slot<I> {
+    const NAME: &'static str = "registered";
+
+    fn name() -> &'static str {
+        frozen()[I].0
+    }
+
+    fn version() -> String {
+        Self::backend().version()
+    }
+
+    fn build() -> String {
+        Self::backend().build()
+    }
+
+    fn uncompress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: InflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        Self::backend().uncompress_slice(output, input, config)
+    }
+
+    fn compress_slice<'a>(
+        output: &'a mut [u8],
+        input: &[u8],
+        config: DeflateConfig,
+    ) -> (&'a mut [u8], ReturnCode) {
+        Self::backend().compress_slice(output, input, config)
+    }
+}
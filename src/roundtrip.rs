@@ -0,0 +1,171 @@
+//! Compress the input and decompress the result again, with the same implementation, in one run.
+//!
+//! Both phases are timed separately, and the decompressed output is compared with the input after
+//! every run (outside of the timing), so there is no need to stage a compressed file on disk
+//! between a `deflate` and an `inflate` invocation. Both speeds are in MB of uncompressed data per
+//! second, like those of `deflate` and `inflate`.
+
+use crate::guard::{Allocation, Buffer};
+use crate::{
+    another_run, compress, deflate_config, dylib, inflate_config, og_local, read_input, registry,
+    stats, timer, uncompress, DeflateImplementation, Mode, ReturnCode, Runs, FEATURES,
+};
+
+/// Run `roundtrip`, or exit when the implementation can't both compress at this level and
+/// decompress
+pub fn single<T: DeflateImplementation>(path: &str, level: i32, runs: Runs) {
+    let supported = T::CAPABILITIES.check(Mode::Deflate, level);
+    if let Err(reason) = supported.and_then(|()| T::CAPABILITIES.check(Mode::Inflate, level)) {
+        eprintln!("{}: {reason}", T::name());
+        std::process::exit(1);
+    }
+
+    roundtrip::<T>(path, level, runs)
+}
+
+pub fn roundtrip<T: DeflateImplementation>(path: &str, level: i32, runs: Runs) {
+    let Ok(input) = read_input(path) else {
+        panic!("error opening {path:?}")
+    };
+    let mut compressed = Buffer::new(Allocation::HEAP.output_len, None);
+    let mut decompressed = vec![0u8; input.len()];
+
+    let mut run = || {
+        let start = timer::now();
+        let (compressed, res) = compress::<T>(&mut compressed, &input, deflate_config(level));
+        let deflate = start.elapsed();
+        assert_eq!(res, ReturnCode::Ok);
+
+        let start = timer::now();
+        let (decompressed, res) = uncompress::<T>(&mut decompressed, compressed, inflate_config());
+        let inflate = start.elapsed();
+        assert_eq!(res, ReturnCode::Ok);
+
+        if *decompressed != *input {
+            let mut pairs = decompressed.iter().zip(&input);
+            let offset = pairs.position(|(a, b)| a != b);
+            let offset = offset.unwrap_or(decompressed.len().min(input.len()));
+            eprintln!(
+                "{}: the decompressed output differs from the input at byte {offset}",
+                T::name()
+            );
+            std::process::exit(1);
+        }
+
+        (deflate, inflate, compressed.len())
+    };
+
+    for _ in 0..runs.warmup {
+        run();
+    }
+
+    let mb = input.len() as f64 / 1e6;
+    let mut deltas = Vec::with_capacity(runs.runs);
+    let mut compressed_len = 0;
+
+    println!("run, deflate ms, deflate MB/s, inflate ms, inflate MB/s");
+    let start = std::time::Instant::now();
+    for i in 0.. {
+        if !another_run(i, runs.runs, runs.max_time, start.elapsed()) {
+            break;
+        }
+
+        let (deflate, inflate, len) = run();
+        println!(
+            "{i}, {:.3}, {:.2}, {:.3}, {:.2}",
+            deflate.as_secs_f64() * 1e3,
+            mb / deflate.as_secs_f64(),
+            inflate.as_secs_f64() * 1e3,
+            mb / inflate.as_secs_f64(),
+        );
+
+        deltas.push((deflate, inflate));
+        compressed_len = len;
+    }
+
+    println!(
+        "# {} runs after {} warmup runs, {compressed_len} compressed bytes, ratio {:.3}",
+        deltas.len(),
+        runs.warmup,
+        input.len() as f64 / compressed_len as f64,
+    );
+
+    let deflate: Vec<_> = deltas.iter().map(|(d, _)| d.as_secs_f64() * 1e3).collect();
+    let inflate: Vec<_> = deltas.iter().map(|(_, d)| d.as_secs_f64() * 1e3).collect();
+    let discard = runs.discard_outliers;
+
+    let phases = [("deflate", deflate), ("inflate", inflate)];
+    let outliers = phases.each_ref().map(|(_, ms)| stats::outliers(ms));
+    for ((phase, _), outliers) in phases.iter().zip(&outliers) {
+        for (i, _) in outliers.iter().enumerate().filter(|(_, outlier)| **outlier) {
+            let action = if discard { "discarded" } else { "kept" };
+            println!("# {phase} of run {i} is an outlier ({action})");
+        }
+    }
+
+    println!("phase, unit, {}", stats::Summary::HEADER);
+    for ((phase, ms), outliers) in phases.iter().zip(&outliers) {
+        let mb_per_s: Vec<_> = ms.iter().map(|ms| mb / (ms / 1e3)).collect();
+
+        let summary = stats::Summary::of(ms, outliers, discard);
+        println!("{phase}, ms, {}", summary.columns());
+        let summary = stats::Summary::of(&mb_per_s, outliers, discard);
+        println!("{phase}, MB/s, {}", summary.columns());
+    }
+}
+
+/// The `roundtrip` command: `roundtrip <level> <implementation> <path>`
+pub fn roundtrip_all(implementation: &str, path: &str, level: i32, runs: Runs) {
+    match implementation {
+        #[cfg(feature = "og")]
+        "og" => single::<crate::ZlibOg>(path, level, runs),
+        #[cfg(feature = "ng")]
+        "ng" => single::<crate::ZlibNg>(path, level, runs),
+        #[cfg(feature = "rs")]
+        "rs" => single::<crate::ZlibRs>(path, level, runs),
+        #[cfg(feature = "cloudflare")]
+        "cloudflare" => single::<crate::ZlibCloudflare>(path, level, runs),
+        #[cfg(feature = "miniz")]
+        "miniz" => single::<crate::MinizOxide>(path, level, runs),
+        #[cfg(feature = "libdeflate")]
+        "libdeflate" => single::<crate::Libdeflate>(path, level, runs),
+        #[cfg(feature = "miniz-c")]
+        "miniz-c" => single::<crate::MinizC>(path, level, runs),
+        #[cfg(feature = "flate2")]
+        "flate2" => single::<crate::Flate2>(path, level, runs),
+        #[cfg(feature = "classic")]
+        "classic" => single::<crate::Classic>(path, level, runs),
+        #[cfg(feature = "yazi")]
+        "yazi" => single::<crate::Yazi>(path, level, runs),
+        "system" => single::<dylib::SystemZlib>(path, level, runs),
+        "og-local" => match og_local::load() {
+            Ok(library) => {
+                dylib::select(library);
+                single::<dylib::ZlibDylib>(path, level, runs)
+            }
+            Err(e) => panic!("og-local: {e}"),
+        },
+        "isal" => single::<crate::isal::Isal>(path, level, runs),
+        #[cfg(target_os = "macos")]
+        "libcompression" => single::<crate::libcompression::LibCompression>(path, level, runs),
+        other => match other.strip_prefix("dylib:") {
+            Some(library) => {
+                match dylib::Library::open_any(library) {
+                    Ok(library) => dylib::select(library),
+                    Err(e) => panic!("{library}: {e}"),
+                }
+                single::<dylib::ZlibDylib>(path, level, runs)
+            }
+            None => {
+                if registry::roundtrip(other, path, level, runs).is_none() {
+                    match FEATURES.iter().any(|(n, _)| *n == other) {
+                        true => {
+                            panic!("{other} is not compiled in, build with `--features {other}`")
+                        }
+                        false => panic!("invalid implementation: {other:?}"),
+                    }
+                }
+            }
+        },
+    }
+}
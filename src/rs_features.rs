@@ -0,0 +1,96 @@
+//! Benchmark zlib-rs built with different cargo features side by side.
+//!
+//! zlib-rs can allocate with the rust allocator or with `malloc`, and can be built with or without
+//! `std`. Within one cargo build the features of a crate are unified, so the statically linked
+//! zlib-rs can only be built one way. Instead, the C API (`libz-rs-sys-cdylib`) of the same branch
+//! is built as a shared library once per feature set, and every build is loaded with `dlopen`.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::dylib::{self, Abi, Library, ZlibDylib};
+use crate::ng_versions::{measure, run};
+use crate::{helper, Mode, ZlibRs};
+
+/// The repository and branch of the `libz-rs-sys` dependency in `Cargo.toml`
+const REPOSITORY: &str = "https://github.com/memorysafety/zlib-rs.git";
+const BRANCH: &str = "allocator-feature-flag";
+
+/// Feature sets of `libz-rs-sys-cdylib`: (name, features). Builds use `--no-default-features`, so
+/// only these features are enabled.
+pub const DEFAULT_FEATURES: [(&str, &str); 4] = [
+    ("rust-allocator", "rust-allocator"),
+    ("c-allocator", "c-allocator"),
+    ("rust-allocator+std", "rust-allocator,libz-rs-sys/std"),
+    ("c-allocator+std", "c-allocator,libz-rs-sys/std"),
+];
+
+/// Fetch the branch (once), build the C API with `features` in its own target directory, and
+/// return the path of the shared library
+fn build(name: &str, features: &str) -> Result<String, String> {
+    let dir = format!("{}/target/zlib-rs", env!("CARGO_MANIFEST_DIR"));
+
+    let source = format!("{dir}/source");
+    if !Path::new(&source).exists() {
+        run(Command::new("git").args([
+            "clone",
+            "--quiet",
+            "--depth=1",
+            "--branch",
+            BRANCH,
+            REPOSITORY,
+            &source,
+        ]))?;
+    }
+
+    let target = format!("{dir}/{name}");
+    run(
+        Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()))
+            .current_dir(&source)
+            .args(["build", "--release", "--quiet"])
+            .args(["--manifest-path", "libz-rs-sys-cdylib/Cargo.toml"])
+            .args(["--target-dir", &target])
+            .args(["--no-default-features", "--features", features])
+            // like `.cargo/config.toml` does for the statically linked zlib-rs
+            .env("RUSTFLAGS", "-C target-cpu=native"),
+    )?;
+
+    Ok(format!("{target}/release/libz_rs.so"))
+}
+
+/// `features` are given on the command line as `name=features`, or just `features`
+fn parse(features: &str) -> (&str, &str) {
+    features.split_once('=').unwrap_or((features, features))
+}
+
+pub fn rs_features_all(mode: Mode, path: &str, level: i32, features: &[String]) {
+    let features: Vec<_> = match features {
+        [] => DEFAULT_FEATURES.to_vec(),
+        features => features.iter().map(|f| parse(f)).collect(),
+    };
+
+    // build everything up front, so that compiler output does not end up between the results
+    let mut libraries = Vec::new();
+    for (name, features) in features {
+        match build(name, features).and_then(|p| Library::open(&p, Abi::Zlib)) {
+            Ok(library) => {
+                println!("# {name}: --features {features} ({})", library.path());
+                libraries.push((name, library));
+            }
+            Err(e) => println!("# {name}: unavailable ({e})"),
+        }
+    }
+
+    let (rs_mb_per_s, rs_output_len) = measure(helper::<ZlibRs>, mode, path, level);
+
+    println!("features, MB/s, % of vendored, output bytes");
+    println!("vendored (rust-allocator), {rs_mb_per_s:.2}, 100.0, {rs_output_len}");
+
+    for (name, library) in libraries {
+        dylib::select(library);
+
+        let (mb_per_s, output_len) = measure(helper::<ZlibDylib>, mode, path, level);
+        let relative = 100.0 * mb_per_s / rs_mb_per_s;
+        println!("{name}, {mb_per_s:.2}, {relative:.1}, {output_len}");
+    }
+}
@@ -0,0 +1,108 @@
+//! Run a benchmark inside a pinned docker image.
+//!
+//! Published numbers are hard to reproduce when the toolchain and the versions of the backends are
+//! unknown. `run --sandbox docker` builds the image described by the `Dockerfile` in the repository
+//! (a fixed rust toolchain, and the backend versions from the `Cargo.lock` that is committed), runs
+//! the command in it, and records the registry digest of the base image in the results. The id of
+//! the image that is built locally means nothing elsewhere, the digest (with the commit of this
+//! repository) is what others need to build the same image.
+
+use std::process::Command;
+
+/// The name of the image that is built
+const TAG: &str = "zlib-bench:sandbox";
+
+/// The environment variable that tells the harness which image it is running in
+pub const IMAGE_VARIABLE: &str = "ZLIB_BENCH_IMAGE";
+
+/// The base image of a `Dockerfile`, from its `FROM` line
+fn base_image(dockerfile: &str) -> Option<&str> {
+    let from = dockerfile
+        .lines()
+        .find_map(|line| line.strip_prefix("FROM "))?;
+    from.split_whitespace().next()
+}
+
+/// The digest of `image` in the registry that it was pulled from, e.g. `rust@sha256:...`
+fn registry_digest(image: &str) -> Result<String, String> {
+    let output = Command::new("docker")
+        .args([
+            "image",
+            "inspect",
+            "--format",
+            "{{index .RepoDigests 0}}",
+            image,
+        ])
+        .output()
+        .map_err(|e| format!("running docker: {e}"))?;
+
+    match output.status.success() {
+        true => Ok(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        false => Err(format!(
+            "docker image inspect {image} failed ({})",
+            output.status
+        )),
+    }
+}
+
+/// Build the image, and return the registry digest of its base image
+fn build() -> Result<String, String> {
+    let manifest_dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+    if !manifest_dir.join("Cargo.lock").exists() {
+        return Err(
+            "no Cargo.lock to pin the backend versions, run `cargo generate-lockfile` and commit it"
+                .into(),
+        );
+    }
+    let dockerfile = std::fs::read_to_string(manifest_dir.join("Dockerfile"))
+        .map_err(|e| format!("reading the Dockerfile: {e}"))?;
+    let Some(base) = base_image(&dockerfile) else {
+        return Err("the Dockerfile has no FROM line".into());
+    };
+
+    let status = Command::new("docker")
+        .args(["build", "--tag", TAG])
+        .arg(manifest_dir)
+        .status()
+        .map_err(|e| format!("running docker: {e}"))?;
+    if !status.success() {
+        return Err(format!("docker build failed ({status})"));
+    }
+
+    registry_digest(base)
+}
+
+/// Run the harness with `args` in the sandbox, and exit with its exit code
+pub fn run(sandbox: &str, args: &[String]) {
+    if sandbox != "docker" {
+        panic!("invalid sandbox {sandbox:?}");
+    }
+
+    let image = match build() {
+        Ok(image) => image,
+        Err(e) => {
+            eprintln!("error building the sandbox: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    // the working directory is mounted as the working directory of the container, so relative paths to
+    // inputs and outputs work as usual
+    let cwd = std::env::current_dir().unwrap();
+    let status = Command::new("docker")
+        .args(["run", "--rm", "--init"])
+        .arg("--volume")
+        .arg(format!("{}:/work", cwd.display()))
+        .args(["--env", &format!("{IMAGE_VARIABLE}={image}")])
+        .arg(TAG)
+        .args(args)
+        .status();
+
+    match status {
+        Ok(status) => std::process::exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            eprintln!("error running docker: {e}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! `--threads <n>`: how the throughput of every implementation scales with independent streams.
+//!
+//! Every thread (de)compresses its own copy of the input with its own stream, into its own output
+//! buffer, so there is no shared state between them in this crate. Only the calls are timed. Whatever keeps the aggregate throughput from growing linearly
+//! is in the implementation or the machine: a lock or an atomic in a global allocator or table,
+//! and a working set that no longer fits in the shared caches when several streams run at once.
+
+use std::sync::Barrier;
+use std::time::{Duration, Instant};
+
+use crate::{
+    another_run, output_buffer, read_input, registry, time_call, Call, Mode, Options, FUNCTIONS,
+};
+
+/// The runs of every thread, unless `--max-time` gives a budget
+const ITERATIONS: usize = 5;
+
+/// Run `call` on `threads` threads at once, and return the throughput in MB/s of all threads
+/// together: the sum of the throughput of every thread, over the time of its calls
+fn concurrent(
+    call: Call,
+    mode: Mode,
+    input: &[u8],
+    level: i32,
+    threads: usize,
+    max_time: Option<Duration>,
+) -> f64 {
+    // the threads start together, once all of them have their buffers
+    let barrier = Barrier::new(threads);
+    let mb = input.len() as f64 / 1e6;
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let input = input.to_vec();
+                    let mut output = output_buffer();
+                    // loads libraries and faults in the output, outside of the measurement
+                    time_call(call, mode, &input, &mut output, level);
+                    barrier.wait();
+
+                    let start = Instant::now();
+                    let mut busy = Duration::ZERO;
+                    let mut runs = 0;
+                    while another_run(runs, ITERATIONS, max_time, start.elapsed()) {
+                        busy += time_call(call, mode, &input, &mut output, level).0;
+                        runs += 1;
+                    }
+                    runs as f64 * mb / busy.as_secs_f64()
+                })
+            })
+            .collect();
+
+        handles.into_iter().map(|h| h.join().unwrap()).sum()
+    })
+}
+
+pub fn scaling_all(mode: Mode, path: &str, level: i32, threads: usize, options: &Options) {
+    let Ok(input) = read_input(path) else {
+        panic!("error opening {path:?}")
+    };
+
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    if threads > cores {
+        println!(
+            "# {threads} threads on {cores} cores: the threads share cores, expect no scaling"
+        );
+    }
+
+    println!(
+        "implementation, MB/s (1 thread), MB/s ({threads} threads), speedup, scaling efficiency %"
+    );
+    for (name, _, call, capabilities, _, _) in
+        FUNCTIONS.iter().copied().chain(registry::registered())
+    {
+        if let Err(reason) = capabilities.check(mode, level) {
+            println!("# {name}: skipped, {reason}");
+            continue;
+        }
+
+        let single = concurrent(call, mode, &input, level, 1, options.max_time);
+        let aggregate = concurrent(call, mode, &input, level, threads, options.max_time);
+
+        let speedup = aggregate / single;
+        println!(
+            "{name}, {single:.2}, {aggregate:.2}, {speedup:.2}, {:.1}",
+            100.0 * speedup / threads as f64
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! Run benchmarks on request, over a small HTTP API.
+//!
+//! Benchmarks should run on a quiet, dedicated machine, but are usually started from a developer's
+//! laptop. `serve` listens for jobs, runs them one at a time (so they don't disturb each other) as a
+//! child process of this executable, and responds with the results as JSON.
+//!
+//! ```text
+//! POST /run
+//! ["deflate-all", "6", "silesia-small.tar"]
+//! ```
+//!
+//! There is no authentication, so only listen on a trusted network. Jobs are limited to the
+//! benchmark commands, and to options that don't write files on the server or run other programs.
+//! The headers of a request are at most `MAX_HEADER` bytes and its body at most `MAX_BODY` bytes,
+//! and a client that doesn't send the whole request within `TIMEOUT` is dropped.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::jsonl::escape;
+
+/// The commands that a job may run
+const COMMANDS: [&str; 4] = ["deflate-all", "inflate-all", "stored-all", "check-env"];
+
+/// Options that write to the server's file system, or to somewhere else, and options that run other
+/// programs: `perf` (which also writes its recordings), or this executable in child processes
+const FORBIDDEN: [&str; 6] = [
+    "--samples",
+    "--jsonl",
+    "--pushgateway",
+    "--profile",
+    "--isolate",
+    "--timeout",
+];
+
+/// The largest request line and headers together
+const MAX_HEADER: u64 = 8 * 1024;
+
+/// The largest request body, a job is a short list of arguments
+const MAX_BODY: usize = 64 * 1024;
+
+/// How long reading the whole request, or writing the response, may take
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Read the four hex digits of a `\u` escape
+fn hex4(chars: &mut impl Iterator<Item = char>) -> Result<u32, String> {
+    let hex: String = chars.take(4).collect();
+    match hex.len() == 4 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        true => Ok(u32::from_str_radix(&hex, 16).unwrap()),
+        false => Err("invalid \\u escape".to_string()),
+    }
+}
+
+/// Parse a JSON array of strings, like `["deflate-all", "6", "silesia-small.tar"]`
+fn parse_args(body: &str) -> Result<Vec<String>, String> {
+    let mut chars = body.trim().chars();
+    let mut args = Vec::new();
+
+    if chars.next() != Some('[') {
+        return Err("expected a JSON array of strings".to_string());
+    }
+
+    loop {
+        match chars.find(|c| !c.is_whitespace()) {
+            Some(']') if args.is_empty() => break,
+            Some('"') => {}
+            _ => return Err("expected a string".to_string()),
+        }
+
+        let mut arg = String::new();
+        loop {
+            match chars.next() {
+                Some('"') => break,
+                Some('\\') => match chars.next() {
+                    Some(c @ ('"' | '\\' | '/')) => arg.push(c),
+                    Some('b') => arg.push('\u{8}'),
+                    Some('f') => arg.push('\u{c}'),
+                    Some('n') => arg.push('\n'),
+                    Some('r') => arg.push('\r'),
+                    Some('t') => arg.push('\t'),
+                    Some('u') => {
+                        let mut code = hex4(&mut chars)?;
+                        // a character outside of the basic multilingual plane is a surrogate pair
+                        if (0xd800..0xdc00).contains(&code) {
+                            let low = match (chars.next(), chars.next()) {
+                                (Some('\\'), Some('u')) => hex4(&mut chars)?,
+                                _ => return Err("unpaired surrogate".to_string()),
+                            };
+                            if !(0xdc00..0xe000).contains(&low) {
+                                return Err("unpaired surrogate".to_string());
+                            }
+                            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                        }
+                        arg.push(char::from_u32(code).ok_or("unpaired surrogate")?);
+                    }
+                    Some(c) => return Err(format!("invalid escape \\{c}")),
+                    None => return Err("unterminated string".to_string()),
+                },
+                Some(c) => arg.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+        args.push(arg);
+
+        match chars.find(|c| !c.is_whitespace()) {
+            Some(',') => {}
+            Some(']') => break,
+            _ => return Err("expected ',' or ']'".to_string()),
+        }
+    }
+
+    Ok(args)
+}
+
+/// Run a job, and return the response body
+fn run(args: &[String]) -> Result<String, String> {
+    match args.first() {
+        Some(command) if COMMANDS.contains(&command.as_str()) => {}
+        Some(command) => return Err(format!("command {command:?} is not allowed")),
+        None => return Err("no command given".to_string()),
+    }
+    if let Some(arg) = args.iter().find(|arg| FORBIDDEN.contains(&arg.as_str())) {
+        return Err(format!("option {arg:?} is not allowed"));
+    }
+
+    let exe = std::env::current_exe().map_err(|e| e.to_string())?;
+    let path = std::env::temp_dir().join(format!("zlib-bench-serve-{}.jsonl", std::process::id()));
+
+    let mut command = Command::new(exe);
+    command.args(args);
+    if args[0] != "check-env" {
+        command.arg("--jsonl").arg(&path);
+    }
+
+    let output = command.output().map_err(|e| e.to_string())?;
+    let events = std::fs::read_to_string(&path).unwrap_or_default();
+    let _ = std::fs::remove_file(&path);
+
+    let args: Vec<_> = args.iter().map(|arg| escape(arg)).collect();
+    let events: Vec<_> = events.lines().collect();
+
+    Ok(format!(
+        "{{\"args\":[{}],\"exit_code\":{},\"stdout\":{},\"stderr\":{},\"events\":[{}]}}\n",
+        args.join(","),
+        output.status.code().unwrap_or(-1),
+        escape(&String::from_utf8_lossy(&output.stdout)),
+        escape(&String::from_utf8_lossy(&output.stderr)),
+        events.join(","),
+    ))
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let _ = stream.write_all(response.as_bytes());
+}
+
+/// Reads from a stream until a deadline, so that a client that trickles in its request byte by byte
+/// can't hold up the server for longer than `TIMEOUT` either
+struct Deadline {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Deadline {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(std::io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        self.stream.read(buf)
+    }
+}
+
+fn handle(mut stream: TcpStream) -> std::io::Result<()> {
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut reader = BufReader::new(Deadline {
+        stream: stream.try_clone()?,
+        deadline: Instant::now() + TIMEOUT,
+    });
+
+    let mut head = reader.by_ref().take(MAX_HEADER);
+    let mut request_line = String::new();
+    let mut content_length = Some(0);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        head.read_line(&mut line)?;
+        if !line.ends_with('\n') {
+            if head.limit() == 0 {
+                let message = format!("the headers are larger than {MAX_HEADER} bytes\n");
+                respond(
+                    &mut stream,
+                    "431 Request Header Fields Too Large",
+                    "text/plain",
+                    &message,
+                );
+            }
+            return Ok(());
+        }
+
+        let header = line.trim_end();
+        if request_line.is_empty() {
+            request_line = header.to_string();
+            continue;
+        }
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().ok();
+            }
+        }
+    }
+
+    let Some(content_length) = content_length else {
+        respond(
+            &mut stream,
+            "400 Bad Request",
+            "text/plain",
+            "invalid Content-Length\n",
+        );
+        return Ok(());
+    };
+
+    if content_length > MAX_BODY {
+        let message = format!("the body is larger than {MAX_BODY} bytes\n");
+        respond(&mut stream, "413 Payload Too Large", "text/plain", &message);
+        return Ok(());
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8_lossy(&body);
+
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("POST"), Some("/run")) => {
+            let result = parse_args(&body).and_then(|args| {
+                eprintln!("# running {args:?}");
+                run(&args)
+            });
+
+            match result {
+                Ok(json) => respond(&mut stream, "200 OK", "application/json", &json),
+                Err(e) => {
+                    let json = format!("{{\"error\":{}}}\n", escape(&e));
+                    respond(&mut stream, "400 Bad Request", "application/json", &json)
+                }
+            }
+        }
+        (Some("GET"), Some("/")) => {
+            let usage = format!(
+                "POST /run with a JSON array of arguments, one of the commands {COMMANDS:?}\n"
+            );
+            respond(&mut stream, "200 OK", "text/plain", &usage)
+        }
+        _ => respond(&mut stream, "404 Not Found", "text/plain", "not found\n"),
+    }
+
+    Ok(())
+}
+
+pub fn serve(address: &str) {
+    let listener = TcpListener::bind(address).unwrap();
+    eprintln!("# listening on {}", listener.local_addr().unwrap());
+
+    // one job at a time: concurrent jobs would disturb each other's timings
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(e) = handle(stream) {
+                    eprintln!("# error handling request: {e}");
+                }
+            }
+            Err(e) => eprintln!("# error accepting connection: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args() {
+        let args = parse_args(r#" ["deflate-all", "6", "silesia-small.tar"] "#).unwrap();
+        assert_eq!(args, ["deflate-all", "6", "silesia-small.tar"]);
+        assert_eq!(parse_args("[]").unwrap(), Vec::<String>::new());
+
+        let args = parse_args(r#"["a\"b\\c\/d", "\b\f\n\r\t", "\u00e9\ud83d\ude00"]"#).unwrap();
+        assert_eq!(args, ["a\"b\\c/d", "\u{8}\u{c}\n\r\t", "é😀"]);
+
+        assert!(parse_args(r#"["\x"]"#).is_err());
+        assert!(parse_args(r#"["\u12"]"#).is_err());
+        assert!(parse_args(r#"["\u+123"]"#).is_err());
+        assert!(parse_args(r#"["\ud83d"]"#).is_err());
+        assert!(parse_args(r#"["\ude00"]"#).is_err());
+        assert!(parse_args(r#"["a""#).is_err());
+        assert!(parse_args(r#"["a" "b"]"#).is_err());
+        assert!(parse_args("deflate-all").is_err());
+    }
+}
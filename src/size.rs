@@ -0,0 +1,84 @@
+//! Attribute the symbols in a binary to the implementations that contributed them.
+//!
+//! We use `nm -l`, which looks up the source location of every symbol in the debug info (the release
+//! profile has `debug = true`). The path of that location tells us which crate a symbol came from.
+//! Symbols without a location fall back to the (v0-mangled) symbol name.
+
+use std::process::Command;
+
+use crate::FUNCTIONS;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Size {
+    text: u64,
+    data: u64,
+}
+
+pub fn size_all(binary: &str) {
+    let output = match Command::new("nm")
+        .args(["--print-size", "--line-numbers", "--defined-only", binary])
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        Ok(output) => panic!("nm failed: {}", String::from_utf8_lossy(&output.stderr)),
+        Err(e) => panic!("error running nm: {e}"),
+    };
+
+    let mut sizes = [Size::default(); FUNCTIONS.len()];
+    let mut other = Size::default();
+
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let (symbol, location) = line.split_once('\t').unwrap_or((line, ""));
+
+        // symbols without a size (e.g. linker-generated labels) have only 3 fields
+        let [_address, size, kind, name] = symbol.split_whitespace().collect::<Vec<_>>()[..] else {
+            continue;
+        };
+
+        let Ok(size) = u64::from_str_radix(size, 16) else {
+            continue;
+        };
+
+        let entry = match implementation(location, name) {
+            Some(name) => {
+                let index = FUNCTIONS
+                    .iter()
+                    .position(|(n, _, _, _, _, _)| *n == name)
+                    .unwrap();
+                &mut sizes[index]
+            }
+            None => &mut other,
+        };
+
+        match kind {
+            "T" | "t" | "W" | "w" => entry.text += size,
+            _ => entry.data += size,
+        }
+    }
+
+    println!("implementation, text bytes, data bytes");
+    for ((name, _, _, _, _, _), size) in FUNCTIONS.iter().zip(sizes) {
+        println!("{name}, {}, {}", size.text, size.data);
+    }
+    println!("other, {}, {}", other.text, other.data);
+}
+
+fn implementation(location: &str, name: &str) -> Option<&'static str> {
+    let from_location = match location {
+        _ if location.contains("libz-ng-sys") => Some("ng"),
+        _ if location.contains("cloudflare-zlib-sys") => Some("cloudflare"),
+        _ if location.contains("libz-sys") => Some("og"),
+        _ if location.contains("libz-rs-sys") || location.contains("/zlib-rs") => Some("rs"),
+        _ if location.contains("miniz_oxide") => Some("miniz"),
+        _ => None,
+    };
+
+    // generic code (e.g. `drop_in_place`) has a location in the standard library, but the
+    // instantiated type is still part of the mangled name.
+    from_location.or(match name {
+        _ if name.starts_with("zng_") => Some("ng"),
+        _ if name.contains("zlib_rs") || name.contains("libz_rs_sys") => Some("rs"),
+        _ if name.contains("miniz_oxide") => Some("miniz"),
+        _ => None,
+    })
+}
@@ -0,0 +1,412 @@
+//! Summary statistics of repeated measurements.
+//!
+//! The confidence interval is a percentile bootstrap of the mean: the measurements are resampled
+//! with replacement many times, and the interval is the middle 95% of the means of the resamples.
+//! It makes no assumption about the distribution of the measurements, which for timings is skewed
+//! by the occasional slow run. The resampling is seeded, so the same measurements always give the
+//! same interval.
+//!
+//! A run that the OS interrupted (a context switch, an interrupt storm, a frequency change) is much
+//! slower than the others. Such outliers are found with the median absolute deviation (MAD), which,
+//! unlike the standard deviation, is not itself inflated by the outliers. They are counted, and can
+//! be left out of the statistics.
+//!
+//! Whether two sets of measurements differ is decided with Welch's t-test, which unlike Student's
+//! t-test does not assume that both have the same variance.
+
+use crate::rng::Rng;
+
+/// The number of resamples of the bootstrap
+const RESAMPLES: usize = 10_000;
+
+/// The modified z-score above which a measurement is an outlier, as recommended by Iglewicz and
+/// Hoaglin
+const OUTLIER_SCORE: f64 = 3.5;
+
+/// Scales the MAD to the standard deviation of a normal distribution
+const MAD_SCALE: f64 = 0.6745;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Summary {
+    pub mean: f64,
+    pub median: f64,
+    /// The sample standard deviation, 0 for a single measurement
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+    /// The 95% confidence interval of the mean, as (low, high)
+    pub ci: (f64, f64),
+    /// The number of outliers, which are not in the other statistics when they were discarded
+    pub outliers: usize,
+}
+
+fn average(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// The value at `fraction` (in `0.0..=1.0`) of `sorted`
+pub fn percentile(sorted: &[f64], fraction: f64) -> f64 {
+    let index = (fraction * (sorted.len() - 1) as f64).round() as usize;
+    sorted[index]
+}
+
+/// Whether each of `values` is an outlier
+pub fn outliers(values: &[f64]) -> Vec<bool> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = percentile(&sorted, 0.5);
+
+    let mut deviations: Vec<_> = values.iter().map(|v| (v - median).abs()).collect();
+    deviations.sort_by(f64::total_cmp);
+    let mad = percentile(&deviations, 0.5);
+
+    // more than half of the values are identical, so there is no spread to compare with
+    if mad == 0.0 {
+        return vec![false; values.len()];
+    }
+
+    values
+        .iter()
+        .map(|v| MAD_SCALE * (v - median).abs() / mad > OUTLIER_SCORE)
+        .collect()
+}
+
+impl Summary {
+    /// Summarize `values`, which must not be empty. `flags` are the `outliers` of the measurements
+    /// (of the times, when `values` are derived from them), and with `discard` those are left out.
+    pub fn of(values: &[f64], flags: &[bool], discard: bool) -> Self {
+        assert!(!values.is_empty(), "no measurements to summarize");
+
+        let outliers = flags.iter().filter(|outlier| **outlier).count();
+
+        let kept: Vec<_> = match discard {
+            true => values
+                .iter()
+                .zip(flags)
+                .filter(|(_, outlier)| !**outlier)
+                .map(|(value, _)| *value)
+                .collect(),
+            false => values.to_vec(),
+        };
+        let values = kept.as_slice();
+
+        let mut sorted = values.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        let mean = average(values);
+        let stddev = match values.len() {
+            1 => 0.0,
+            n => {
+                let squares: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+                (squares / (n - 1) as f64).sqrt()
+            }
+        };
+
+        let mut rng = Rng::new(values.len() as u64);
+        let mut resample = vec![0.0; values.len()];
+        let mut means: Vec<f64> = (0..RESAMPLES)
+            .map(|_| {
+                for value in resample.iter_mut() {
+                    *value = values[rng.below(values.len() as u64) as usize];
+                }
+                average(&resample)
+            })
+            .collect();
+        means.sort_by(f64::total_cmp);
+
+        Summary {
+            mean,
+            median: percentile(&sorted, 0.5),
+            stddev,
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            ci: (percentile(&means, 0.025), percentile(&means, 0.975)),
+            outliers,
+        }
+    }
+
+    /// The column names of `columns`
+    pub const HEADER: &'static str =
+        "mean, median, stddev, min, max, 95% CI low, 95% CI high, outliers";
+
+    /// The statistics as CSV columns, in the order of `HEADER`
+    pub fn columns(&self) -> String {
+        let (low, high) = self.ci;
+        format!(
+            "{:.2}, {:.2}, {:.2}, {:.2}, {:.2}, {:.2}, {:.2}, {}",
+            self.mean, self.median, self.stddev, self.min, self.max, low, high, self.outliers
+        )
+    }
+}
+
+/// The result of Welch's t-test
+#[derive(Debug, Clone, Copy)]
+pub struct Welch {
+    pub t: f64,
+    /// The (Welch-Satterthwaite) degrees of freedom
+    pub df: f64,
+    /// The two-sided p-value: the chance of a difference at least this large if there is none
+    pub p: f64,
+}
+
+/// The mean and sample variance of `values`
+fn mean_variance(values: &[f64]) -> (f64, f64) {
+    let mean = average(values);
+    let squares: f64 = values.iter().map(|v| (v - mean).powi(2)).sum();
+    (mean, squares / (values.len() - 1) as f64)
+}
+
+/// Welch's t-test of whether `a` and `b` have the same mean. Both need at least 2 measurements.
+pub fn welch(a: &[f64], b: &[f64]) -> Welch {
+    assert!(
+        a.len() >= 2 && b.len() >= 2,
+        "the t-test needs 2 measurements of each"
+    );
+
+    let (mean_a, variance_a) = mean_variance(a);
+    let (mean_b, variance_b) = mean_variance(b);
+    let se_a = variance_a / a.len() as f64;
+    let se_b = variance_b / b.len() as f64;
+    let se = se_a + se_b;
+
+    // identical measurements: either certainly the same, or certainly different
+    if se == 0.0 {
+        let p = if mean_a == mean_b { 1.0 } else { 0.0 };
+        return Welch { t: 0.0, df: 0.0, p };
+    }
+
+    let t = (mean_a - mean_b) / se.sqrt();
+    let df =
+        se.powi(2) / (se_a.powi(2) / (a.len() - 1) as f64 + se_b.powi(2) / (b.len() - 1) as f64);
+    let p = incomplete_beta(df / 2.0, 0.5, df / (df + t * t));
+
+    Welch { t, df, p }
+}
+
+/// ln Γ(x) for x > 0, with the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| {
+            sum + c / (x + i as f64 + 1.0)
+        });
+
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+/// The regularized incomplete beta function I_x(a, b)
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+
+    let ln_front = ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln();
+    let front = ln_front.exp();
+
+    // the continued fraction converges quickly on this side, use the symmetry otherwise
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// The continued fraction of the incomplete beta function, with the modified Lentz method
+fn beta_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let nonzero = |v: f64| if v.abs() < TINY { TINY } else { v };
+
+    let mut c = 1.0;
+    let mut d = 1.0 / nonzero(1.0 - (a + b) * x / (a + 1.0));
+    let mut fraction = d;
+
+    for m in 1..=300 {
+        let m = m as f64;
+
+        let even = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        d = 1.0 / nonzero(1.0 + even * d);
+        c = nonzero(1.0 + even / c);
+        fraction *= d * c;
+
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        d = 1.0 / nonzero(1.0 + odd * d);
+        c = nonzero(1.0 + odd / c);
+        let delta = d * c;
+        fraction *= delta;
+
+        if (delta - 1.0).abs() < 1e-12 {
+            break;
+        }
+    }
+
+    fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn percentile_rounds_to_the_nearest_index() {
+        let sorted = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+        assert_eq!(percentile(&sorted, 0.25), 2.0);
+        assert_eq!(percentile(&sorted, 0.5), 3.0);
+        assert_eq!(percentile(&sorted, 1.0), 5.0);
+        assert_eq!(percentile(&[7.0], 0.99), 7.0);
+    }
+
+    #[test]
+    fn incomplete_beta_closed_forms() {
+        // I_x(1, 1) = x, I_x(a, 1) = x^a and I_x(1, b) = 1 - (1 - x)^b
+        for x in [0.1, 0.5, 0.9] {
+            assert_close(incomplete_beta(1.0, 1.0, x), x, 1e-12);
+            assert_close(incomplete_beta(3.0, 1.0, x), x.powi(3), 1e-12);
+            assert_close(incomplete_beta(1.0, 4.0, x), 1.0 - (1.0 - x).powi(4), 1e-12);
+        }
+
+        assert_eq!(incomplete_beta(2.0, 3.0, 0.0), 0.0);
+        assert_eq!(incomplete_beta(2.0, 3.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn incomplete_beta_reference_values() {
+        // scipy.special.betainc(2.5, 1.5, 0.3) and betainc(10, 0.5, 0.9)
+        assert_close(
+            incomplete_beta(2.5, 1.5, 0.3),
+            0.088_943_723_170_665_6,
+            1e-10,
+        );
+        assert_close(
+            incomplete_beta(10.0, 0.5, 0.9),
+            0.151_640_909_634_709_97,
+            1e-10,
+        );
+    }
+
+    #[test]
+    fn welch_p_value_at_critical_values() {
+        // the two-sided 5% critical values of the t distribution, qt(0.975, df) in R
+        for (t, df) in [(12.706_204_736_17, 1.0), (2.228_138_851_986, 10.0)] {
+            let p = incomplete_beta(df / 2.0, 0.5, df / (df + t * t));
+            assert_close(p, 0.05, 1e-9);
+        }
+    }
+
+    #[test]
+    fn welch_reference() {
+        // t.test(a, b) in R: t = -2.4554, df = 24.989, p-value = 0.02138
+        let a = [
+            27.5, 21.0, 19.0, 23.6, 17.0, 17.9, 16.9, 20.1, 21.9, 22.6, 23.1, 19.6, 19.0, 21.7,
+            21.4,
+        ];
+        let b = [
+            27.1, 22.0, 20.8, 23.4, 23.4, 23.5, 25.8, 22.0, 24.8, 20.2, 21.9, 22.1, 22.9, 20.5,
+            24.4,
+        ];
+
+        let test = welch(&a, &b);
+        assert_close(test.t, -2.455_356_398_286, 1e-9);
+        assert_close(test.df, 24.988_529_290_231, 1e-9);
+        assert_close(test.p, 0.021_378_001_462_867, 1e-9);
+
+        // the test is symmetric
+        let reversed = welch(&b, &a);
+        assert_close(reversed.t, -test.t, 1e-12);
+        assert_close(reversed.p, test.p, 1e-12);
+    }
+
+    #[test]
+    fn welch_identical_measurements() {
+        assert_eq!(welch(&[1.0, 1.0], &[1.0, 1.0]).p, 1.0);
+        assert_eq!(welch(&[1.0, 1.0], &[2.0, 2.0]).p, 0.0);
+    }
+
+    #[test]
+    fn outliers_by_modified_z_score() {
+        // median 12, MAD 1: the score of 100 is 0.6745 * 88 = 59.4, that of 10 is 0.6745 * 2
+        let flags = outliers(&[10.0, 11.0, 12.0, 13.0, 100.0]);
+        assert_eq!(flags, [false, false, false, false, true]);
+
+        // median 11, MAD 1: a deviation of 5 scores 3.37, one of 6 scores 4.05
+        let flags = outliers(&[9.0, 10.0, 11.0, 12.0, 16.0]);
+        assert_eq!(flags, [false, false, false, false, false]);
+        let flags = outliers(&[9.0, 10.0, 11.0, 12.0, 17.0]);
+        assert_eq!(flags, [false, false, false, false, true]);
+    }
+
+    #[test]
+    fn outliers_without_spread() {
+        // more than half identical: the MAD is 0
+        assert_eq!(outliers(&[5.0, 5.0, 5.0, 9.0]), [false; 4]);
+    }
+
+    #[test]
+    fn summary_statistics() {
+        let values = [3.0, 1.0, 4.0, 1.0, 5.0, 9.0, 2.0];
+        let summary = Summary::of(&values, &[false; 7], false);
+
+        assert_close(summary.mean, 25.0 / 7.0, 1e-12);
+        assert_eq!(summary.median, 3.0);
+        // the squared deviations sum to 137 - 25^2 / 7, over n - 1
+        assert_close(summary.stddev, (334.0 / 7.0 / 6.0f64).sqrt(), 1e-12);
+        assert_eq!((summary.min, summary.max), (1.0, 9.0));
+        assert_eq!(summary.outliers, 0);
+    }
+
+    #[test]
+    fn summary_discards_outliers() {
+        let values = [10.0, 11.0, 12.0, 13.0, 100.0];
+        let flags = outliers(&values);
+
+        let kept = Summary::of(&values, &flags, false);
+        assert_eq!((kept.outliers, kept.max), (1, 100.0));
+
+        let discarded = Summary::of(&values, &flags, true);
+        assert_eq!((discarded.outliers, discarded.max), (1, 13.0));
+        assert_close(discarded.mean, 11.5, 1e-12);
+    }
+
+    #[test]
+    fn bootstrap_interval() {
+        // the same values always give the same interval
+        let values: Vec<f64> = (1..=10).map(f64::from).collect();
+        let summary = Summary::of(&values, &[false; 10], false);
+        let again = Summary::of(&values, &[false; 10], false);
+        assert_eq!(summary.ci, again.ci);
+
+        // the normal approximation is 5.5 ± 1.96 * 0.908
+        let (low, high) = summary.ci;
+        assert!(3.4 < low && low < 4.1, "{low}");
+        assert!(6.9 < high && high < 7.6, "{high}");
+
+        // no spread, no uncertainty
+        let constant = Summary::of(&[2.0; 5], &[false; 5], false);
+        assert_eq!(constant.ci, (2.0, 2.0));
+    }
+}
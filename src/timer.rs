@@ -0,0 +1,112 @@
+//! The clock that measurements are timed with.
+//!
+//! By default that is `Instant`, which on Linux reads the vDSO clock: tens of nanoseconds per call,
+//! and a resolution that depends on the clock source. For a small input or a single record that is
+//! a noticeable part of what is measured. `--timer cycles` reads the cycle counter instead (`rdtsc`
+//! on x86_64, `cntvct_el0` on aarch64), which costs a few nanoseconds. The counter runs at a fixed
+//! rate, which is calibrated against `Instant` once, so measurements are still reported in
+//! nanoseconds.
+
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// Counter ticks per nanosecond, set when the cycle counter is in use
+static TICKS_PER_NS: OnceLock<f64> = OnceLock::new();
+
+/// How long the calibration runs
+const CALIBRATION: Duration = Duration::from_millis(50);
+
+#[cfg(target_arch = "x86_64")]
+fn ticks() -> u64 {
+    use core::arch::x86_64::{_mm_lfence, _rdtsc};
+
+    // keep the read from moving past the surrounding code
+    unsafe {
+        _mm_lfence();
+        let ticks = _rdtsc();
+        _mm_lfence();
+        ticks
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn ticks() -> u64 {
+    let ticks: u64;
+    unsafe { core::arch::asm!("isb", "mrs {}, cntvct_el0", out(reg) ticks) };
+    ticks
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn ticks() -> u64 {
+    unreachable!("the cycle counter is only used on x86_64 and aarch64")
+}
+
+/// Whether the counter runs at a fixed rate, independent of the clock speed and sleep states
+fn invariant() -> Result<(), String> {
+    if cfg!(target_arch = "aarch64") {
+        // the generic timer always runs at a fixed frequency
+        return Ok(());
+    }
+    if !cfg!(target_arch = "x86_64") {
+        return Err("there is no cycle counter on this architecture".to_string());
+    }
+
+    let Ok(cpuinfo) = std::fs::read_to_string("/proc/cpuinfo") else {
+        return Ok(());
+    };
+    let flags = cpuinfo.lines().find(|line| line.starts_with("flags"));
+    let flags: Vec<_> = flags.unwrap_or_default().split_whitespace().collect();
+
+    match flags.contains(&"constant_tsc") && flags.contains(&"nonstop_tsc") {
+        true => Ok(()),
+        false => Err("the TSC of this cpu does not run at a constant rate".to_string()),
+    }
+}
+
+/// Time with the cycle counter from now on, and return its calibrated ticks per nanosecond
+pub fn use_cycles() -> Result<f64, String> {
+    invariant()?;
+
+    let start = Instant::now();
+    let first = ticks();
+    while start.elapsed() < CALIBRATION {
+        core::hint::spin_loop();
+    }
+    let last = ticks();
+    let elapsed = start.elapsed();
+
+    let ticks_per_ns = (last - first) as f64 / elapsed.as_nanos() as f64;
+    Ok(*TICKS_PER_NS.get_or_init(|| ticks_per_ns))
+}
+
+/// Whether the cycle counter is in use
+pub fn cycles() -> bool {
+    TICKS_PER_NS.get().is_some()
+}
+
+/// The start of a measurement
+#[derive(Debug, Clone, Copy)]
+pub enum Start {
+    Instant(Instant),
+    Ticks(u64),
+}
+
+pub fn now() -> Start {
+    match TICKS_PER_NS.get() {
+        None => Start::Instant(Instant::now()),
+        Some(_) => Start::Ticks(ticks()),
+    }
+}
+
+impl Start {
+    pub fn elapsed(&self) -> Duration {
+        match *self {
+            Start::Instant(start) => start.elapsed(),
+            Start::Ticks(start) => {
+                let ticks_per_ns = TICKS_PER_NS.get().unwrap();
+                let ticks = ticks().wrapping_sub(start);
+                Duration::from_nanos((ticks as f64 / ticks_per_ns).round() as u64)
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! Command-line tools, run as subprocesses on temporary files.
+//!
+//! Comparisons with the tools that people actually run (gzip, pigz, igzip, zstd's gzip mode) are
+//! a common question. The timing is around the whole subprocess, so process startup and file
+//! I/O are included, unlike for the implementations that run in this process. All tools produce
+//! and consume gzip, so inflate mode first compresses the input with `gzip -6` (untimed), and then
+//! every tool decompresses that same file.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::{isal, Mode};
+
+struct Tool {
+    name: &'static str,
+    program: &'static str,
+    /// The arguments to compress stdin to stdout at a level
+    compress: fn(i32) -> Vec<String>,
+    /// The arguments to decompress stdin to stdout
+    decompress: &'static [&'static str],
+}
+
+const TOOLS: [Tool; 4] = [
+    Tool {
+        name: "gzip",
+        program: "gzip",
+        compress: |level| vec!["-c".into(), format!("-{level}")],
+        decompress: &["-d", "-c"],
+    },
+    Tool {
+        name: "pigz",
+        program: "pigz",
+        compress: |level| vec!["-c".into(), format!("-{level}")],
+        decompress: &["-d", "-c"],
+    },
+    // igzip has levels 0 to 3, mapped like for the `isal` implementation
+    Tool {
+        name: "igzip",
+        program: "igzip",
+        compress: |level| {
+            let level = isal::level(level).unwrap_or(1);
+            vec!["-c".into(), format!("-{level}")]
+        },
+        decompress: &["-d", "-c"],
+    },
+    Tool {
+        name: "zstd --format=gzip",
+        program: "zstd",
+        compress: |level| {
+            let level = format!("-{level}");
+            vec!["--format=gzip".into(), "-q".into(), "-c".into(), level]
+        },
+        decompress: &["--format=gzip", "-q", "-d", "-c"],
+    },
+];
+
+fn available(program: &str) -> bool {
+    Command::new(program)
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok()
+}
+
+/// Run `program` with `input` as stdin and `output` as stdout, and return how long it took
+fn run(program: &str, args: &[String], input: &Path, output: &Path) -> Result<Duration, String> {
+    let stdin = File::open(input).map_err(|e| format!("{}: {e}", input.display()))?;
+    let stdout = File::create(output).map_err(|e| format!("{}: {e}", output.display()))?;
+
+    let start = Instant::now();
+    let status = Command::new(program)
+        .args(args)
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(Stdio::null())
+        .status()
+        .map_err(|e| format!("running {program}: {e}"))?;
+    let elapsed = start.elapsed();
+
+    match status.success() {
+        true => Ok(elapsed),
+        false => Err(format!("{program} {} failed ({status})", args.join(" "))),
+    }
+}
+
+fn temp_file(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("zlib-bench-{}-{name}", std::process::id()))
+}
+
+pub fn tools_all(mode: Mode, path: &str, level: i32) {
+    let n = 5;
+
+    // the input is copied, so that every tool reads it from the same (temporary) file system
+    let input = temp_file("input");
+    std::fs::copy(path, &input).unwrap();
+    let bytes = std::fs::metadata(&input).unwrap().len();
+
+    let compressed = temp_file("input.gz");
+    if let Mode::Inflate = mode {
+        let args = ["-c".to_string(), "-6".to_string()];
+        if let Err(e) = run("gzip", &args, &input, &compressed) {
+            panic!("compressing the input: {e}");
+        }
+    }
+
+    let output = temp_file("output");
+    println!("tool, MB/s, output bytes");
+    for tool in TOOLS {
+        if !available(tool.program) {
+            println!("{}, unavailable, -", tool.name);
+            continue;
+        }
+
+        let (args, source) = match mode {
+            Mode::Deflate => ((tool.compress)(level), &input),
+            Mode::Inflate => {
+                let args = tool.decompress.iter().map(|a| a.to_string()).collect();
+                (args, &compressed)
+            }
+        };
+
+        let mut total = Duration::ZERO;
+        let mut result = Ok(());
+        for _ in 0..n {
+            match run(tool.program, &args, source, &output) {
+                Ok(elapsed) => total += elapsed,
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        match result {
+            Ok(()) => {
+                let mb_per_s = n as f64 * bytes as f64 / 1e6 / total.as_secs_f64();
+                let output_len = std::fs::metadata(&output).unwrap().len();
+                println!("{}, {mb_per_s:.2}, {output_len}", tool.name);
+            }
+            Err(e) => println!("{}, FAILED ({e}), -", tool.name),
+        }
+    }
+
+    for file in [input, compressed, output] {
+        let _ = std::fs::remove_file(file);
+    }
+}
@@ -0,0 +1,171 @@
+//! Run zlib-rs and miniz_oxide compiled to WebAssembly, next to their native builds.
+//!
+//! The crate in `wasm/` wraps both in a few exported functions. It is built for `wasm32-wasip1` (the
+//! target has to be installed with `rustup target add wasm32-wasip1`), and run with wasmtime in the
+//! same process as the native builds, so that the cost of the wasm sandbox can be measured with the
+//! same inputs and configuration.
+
+use std::process::Command;
+use std::time::Instant;
+
+use wasmtime::{Engine, Instance, Linker, Memory, Module, Store, TypedFunc};
+use wasmtime_wasi::preview1::{self, WasiP1Ctx};
+use wasmtime_wasi::WasiCtxBuilder;
+
+use crate::guard::Allocation;
+use crate::{helper, ng_versions, Function, MinizOxide, Mode, ZlibRs};
+
+const TARGET: &str = "wasm32-wasip1";
+
+/// The implementations in the guest: (name, native build, exported prefix)
+const IMPLEMENTATIONS: [(&str, Function, &str); 2] = [
+    ("rs", helper::<ZlibRs> as _, "rs"),
+    ("miniz", helper::<MinizOxide> as _, "miniz"),
+];
+
+/// Build the guest, and return the path of the module
+fn build() -> Result<String, String> {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let target_dir = format!("{manifest_dir}/target/wasm");
+
+    let status = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()))
+        .args(["build", "--release", "--quiet", "--target", TARGET])
+        .args([
+            "--manifest-path",
+            &format!("{manifest_dir}/wasm/Cargo.toml"),
+        ])
+        .args(["--target-dir", &target_dir])
+        // replaces the flags of `.cargo/config.toml`, `target-cpu=native` means nothing for wasm
+        .env("RUSTFLAGS", "-C target-feature=+simd128")
+        .status()
+        .map_err(|e| format!("running cargo: {e}"))?;
+    if !status.success() {
+        return Err(format!(
+            "cargo build failed ({status}), is the {TARGET} target installed?"
+        ));
+    }
+
+    Ok(format!(
+        "{target_dir}/{TARGET}/release/zlib_bench_wasm.wasm"
+    ))
+}
+
+struct Guest {
+    store: Store<WasiP1Ctx>,
+    instance: Instance,
+    memory: Memory,
+    alloc: TypedFunc<u32, u32>,
+}
+
+impl Guest {
+    fn load(module: &str) -> Result<Self, String> {
+        let engine = Engine::default();
+        let module = Module::from_file(&engine, module).map_err(|e| format!("{module}: {e:#}"))?;
+
+        let mut linker = Linker::new(&engine);
+        preview1::add_to_linker_sync(&mut linker, |ctx| ctx).map_err(|e| format!("{e:#}"))?;
+
+        // panic messages of the guest end up on our stderr
+        let wasi = WasiCtxBuilder::new().inherit_stderr().build_p1();
+        let mut store = Store::new(&engine, wasi);
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .map_err(|e| format!("{e:#}"))?;
+
+        // a `cdylib` is a reactor, which has to be initialized before any other call
+        if let Ok(initialize) = instance.get_typed_func::<(), ()>(&mut store, "_initialize") {
+            initialize
+                .call(&mut store, ())
+                .map_err(|e| format!("{e:#}"))?;
+        }
+
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or("no exported memory")?;
+        let alloc = instance
+            .get_typed_func(&mut store, "alloc")
+            .map_err(|e| format!("{e:#}"))?;
+
+        Ok(Guest {
+            store,
+            instance,
+            memory,
+            alloc,
+        })
+    }
+
+    fn alloc(&mut self, len: usize) -> u32 {
+        self.alloc.call(&mut self.store, len as u32).unwrap()
+    }
+
+    /// Returns (MB/s, output bytes), measured like `ng_versions::measure`
+    fn measure(&mut self, prefix: &str, mode: Mode, path: &str, level: i32) -> (f64, usize) {
+        let n = 5;
+
+        // the guest never frees, so the buffers are allocated once
+        let input_len = std::fs::metadata(path).unwrap().len() as usize;
+        let capacity = Allocation::HEAP.output_len;
+        let input = self.alloc(input_len);
+        let output = self.alloc(capacity);
+
+        let uncompress: TypedFunc<(u32, u32, u32, u32), i32> = self
+            .instance
+            .get_typed_func(&mut self.store, &format!("{prefix}_uncompress"))
+            .unwrap();
+        let compress: TypedFunc<(u32, u32, u32, u32, i32), i32> = self
+            .instance
+            .get_typed_func(&mut self.store, &format!("{prefix}_compress"))
+            .unwrap();
+
+        let mut output_len = 0;
+        let start = Instant::now();
+        for _ in 0..n {
+            let Ok(bytes) = std::fs::read(path) else {
+                panic!("error opening {path:?}")
+            };
+            self.memory
+                .write(&mut self.store, input as usize, &bytes)
+                .unwrap();
+
+            let ret = match mode {
+                Mode::Inflate => uncompress.call(
+                    &mut self.store,
+                    (input, input_len as u32, output, capacity as u32),
+                ),
+                Mode::Deflate => compress.call(
+                    &mut self.store,
+                    (input, input_len as u32, output, capacity as u32, level),
+                ),
+            };
+            match ret {
+                Ok(len) if len >= 0 => output_len = len as usize,
+                Ok(ret) => panic!("{prefix} (wasm) failed with return code {ret}"),
+                Err(e) => panic!("{prefix} (wasm) trapped: {e:#}"),
+            }
+        }
+        let total = start.elapsed();
+
+        let mb_per_s = n as f64 * input_len as f64 / 1e6 / total.as_secs_f64();
+
+        (mb_per_s, output_len)
+    }
+}
+
+pub fn wasm_all(mode: Mode, path: &str, level: i32, module: Option<&str>) {
+    let module = match module {
+        Some(module) => module.to_string(),
+        None => build().unwrap_or_else(|e| panic!("error building the guest: {e}")),
+    };
+    let mut guest = Guest::load(&module).unwrap_or_else(|e| panic!("error loading {e}"));
+    println!("# guest: {module}");
+
+    println!("implementation, native MB/s, wasm MB/s, % of native, native output bytes, wasm output bytes");
+
+    for (name, native, prefix) in IMPLEMENTATIONS {
+        let (native_mb_per_s, native_output_len) = ng_versions::measure(native, mode, path, level);
+        let (mb_per_s, output_len) = guest.measure(prefix, mode, path, level);
+
+        let relative = 100.0 * mb_per_s / native_mb_per_s;
+        println!("{name}, {native_mb_per_s:.2}, {mb_per_s:.2}, {relative:.1}, {native_output_len}, {output_len}");
+    }
+}
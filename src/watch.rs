@@ -0,0 +1,190 @@
+//! Rebuild a local zlib-rs checkout whenever it changes, and report the effect on performance.
+//!
+//! An inner loop for optimization work: the checkout's C API (`libz-rs-sys-cdylib`) is rebuilt as a
+//! shared library, loaded with `dlopen`, and a small pinned workload is timed. Every build is
+//! compared with the previous one, and the first with the statically linked zlib-rs.
+
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::dylib::{self, Abi, Library, ZlibDylib};
+use crate::{
+    DeflateConfig, DeflateImplementation, InflateConfig, Method, ReturnCode, Strategy, ZlibRs,
+};
+
+/// Only the start of the input is used, so that a run takes a couple of seconds at most
+const WORKLOAD: usize = 4 << 20;
+
+/// Every measurement is the best of this many runs
+const RUNS: usize = 5;
+
+/// The most recent modification of a source file in the checkout
+fn last_modified(dir: &Path) -> Option<SystemTime> {
+    let mut last = None;
+
+    for entry in std::fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+
+        let modified = if path.is_dir() {
+            if name.starts_with('.') || name == "target" {
+                continue;
+            }
+            last_modified(&path)
+        } else if name.ends_with(".rs") || name == "Cargo.toml" {
+            entry.metadata().and_then(|m| m.modified()).ok()
+        } else {
+            continue;
+        };
+
+        last = Ord::max(last, modified);
+    }
+
+    last
+}
+
+/// Build the shared library, and load a copy of it (a path can only be loaded once). The copy is
+/// removed once it is loaded.
+fn build(checkout: &str, generation: usize) -> Result<&'static Library, String> {
+    let status = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".into()))
+        .current_dir(checkout)
+        .args(["build", "--release", "--quiet"])
+        .args(["--manifest-path", "libz-rs-sys-cdylib/Cargo.toml"])
+        .args(["--target-dir", "target/zlib-bench-watch"])
+        // like `.cargo/config.toml` does for the statically linked zlib-rs
+        .env("RUSTFLAGS", "-C target-cpu=native")
+        .status()
+        .map_err(|e| format!("running cargo: {e}"))?;
+    if !status.success() {
+        return Err(format!("cargo build failed ({status})"));
+    }
+
+    let built = format!("{checkout}/target/zlib-bench-watch/release/libz_rs.so");
+    let copy = std::env::temp_dir().join(format!(
+        "zlib-bench-watch-{}-{generation}.so",
+        std::process::id()
+    ));
+    std::fs::copy(&built, &copy).map_err(|e| format!("copying {built}: {e}"))?;
+
+    // the mapping outlives the file, so that a long session does not fill the temp directory
+    let library = Library::open(copy.to_str().unwrap(), Abi::Zlib);
+    let _ = std::fs::remove_file(&copy);
+    library
+}
+
+struct Measurement {
+    deflate_mb_per_s: f64,
+    inflate_mb_per_s: f64,
+    compressed_bytes: usize,
+}
+
+fn best_of(mut f: impl FnMut()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            f();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn measure<T: DeflateImplementation>(input: &[u8], level: i32) -> Measurement {
+    let config = DeflateConfig {
+        level,
+        method: Method::Deflated,
+        window_bits: 15,
+        mem_level: 8,
+        strategy: Strategy::Default,
+    };
+
+    let mut compressed = vec![0u8; 2 * input.len() + 1024];
+    let mut compressed_bytes = 0;
+    let deflate = best_of(|| {
+        let (output, err) = T::compress_slice(&mut compressed, input, config);
+        assert_eq!(err, ReturnCode::Ok);
+        compressed_bytes = output.len();
+    });
+    compressed.truncate(compressed_bytes);
+
+    let mut output = vec![0u8; input.len()];
+    let inflate = best_of(|| {
+        let config = InflateConfig { window_bits: 15 };
+        let (decompressed, err) = T::uncompress_slice(&mut output, &compressed, config);
+        assert_eq!(err, ReturnCode::Ok);
+        assert!(decompressed == input, "roundtrip failed");
+    });
+
+    let mb = input.len() as f64 / 1e6;
+    Measurement {
+        deflate_mb_per_s: mb / deflate.as_secs_f64(),
+        inflate_mb_per_s: mb / inflate.as_secs_f64(),
+        compressed_bytes,
+    }
+}
+
+fn delta(new: f64, old: f64) -> String {
+    format!("{:+.1}%", 100.0 * (new - old) / old)
+}
+
+pub fn watch_all(checkout: &str, path: &str, level: i32) {
+    if !Path::new(checkout).join("libz-rs-sys-cdylib").is_dir() {
+        panic!("{checkout:?} is not a zlib-rs checkout");
+    }
+
+    let Ok(mut input) = std::fs::read(path) else {
+        panic!("error opening {path:?}")
+    };
+    input.truncate(WORKLOAD);
+
+    let vendored = measure::<ZlibRs>(&input, level);
+    println!(
+        "# vendored zlib-rs: deflate {:.2} MB/s, inflate {:.2} MB/s, {} bytes",
+        vendored.deflate_mb_per_s, vendored.inflate_mb_per_s, vendored.compressed_bytes
+    );
+    println!("build, deflate MB/s, change, inflate MB/s, change, compressed bytes, change");
+
+    let mut previous = vendored;
+    let mut seen = None;
+    for generation in 0.. {
+        // wait for a change
+        loop {
+            let modified = last_modified(Path::new(checkout));
+            if modified != seen {
+                seen = modified;
+                break;
+            }
+            std::thread::sleep(Duration::from_secs(1));
+        }
+
+        let library = match build(checkout, generation) {
+            Ok(library) => library,
+            Err(e) => {
+                println!("# build {generation} failed: {e}");
+                continue;
+            }
+        };
+        dylib::select(library);
+
+        // a broken build should not end the loop
+        let current = match std::panic::catch_unwind(|| measure::<ZlibDylib>(&input, level)) {
+            Ok(current) => current,
+            Err(_) => {
+                println!("# build {generation} failed the workload");
+                continue;
+            }
+        };
+        println!(
+            "{generation}, {:.2}, {}, {:.2}, {}, {}, {:+}",
+            current.deflate_mb_per_s,
+            delta(current.deflate_mb_per_s, previous.deflate_mb_per_s),
+            current.inflate_mb_per_s,
+            delta(current.inflate_mb_per_s, previous.inflate_mb_per_s),
+            current.compressed_bytes,
+            current.compressed_bytes as i64 - previous.compressed_bytes as i64,
+        );
+        previous = current;
+    }
+}
@@ -0,0 +1,119 @@
+//! The functions that the `wasm` command calls, with the same configuration as `helper`: the zlib
+//! format, a 15-bit window and memory level 8.
+//!
+//! Every function takes the input and output buffers as pointers into the guest's memory (from
+//! `alloc`), and returns the number of bytes written to the output, or a negative zlib return code.
+
+use miniz_oxide::deflate::core::{
+    compress, create_comp_flags_from_zip_params, CompressorOxide, TDEFLFlush, TDEFLStatus,
+};
+use miniz_oxide::inflate::core::{decompress, inflate_flags, DecompressorOxide};
+use miniz_oxide::inflate::TINFLStatus;
+
+const Z_BUF_ERROR: i32 = -5;
+const Z_DATA_ERROR: i32 = -3;
+
+/// Allocate `len` zeroed bytes, which are never freed
+#[no_mangle]
+pub extern "C" fn alloc(len: usize) -> *mut u8 {
+    Vec::leak(vec![0u8; len]).as_mut_ptr()
+}
+
+/// # Safety
+///
+/// The buffers must be allocations of at least the given length
+unsafe fn buffers<'a>(
+    input: *const u8,
+    input_len: usize,
+    output: *mut u8,
+    output_len: usize,
+) -> (&'a [u8], &'a mut [u8]) {
+    (
+        core::slice::from_raw_parts(input, input_len),
+        core::slice::from_raw_parts_mut(output, output_len),
+    )
+}
+
+/// # Safety
+///
+/// The buffers must be allocations of at least the given length
+#[no_mangle]
+pub unsafe extern "C" fn rs_compress(
+    input: *const u8,
+    input_len: usize,
+    output: *mut u8,
+    output_len: usize,
+    level: i32,
+) -> i32 {
+    let mut dest_len = output_len as libz_rs_sys::uLongf;
+    let ret = libz_rs_sys::compress2(output, &mut dest_len, input, input_len as _, level);
+
+    match ret {
+        0 => dest_len as i32,
+        _ => ret,
+    }
+}
+
+/// # Safety
+///
+/// The buffers must be allocations of at least the given length
+#[no_mangle]
+pub unsafe extern "C" fn rs_uncompress(
+    input: *const u8,
+    input_len: usize,
+    output: *mut u8,
+    output_len: usize,
+) -> i32 {
+    let mut dest_len = output_len as libz_rs_sys::uLongf;
+    let ret = libz_rs_sys::uncompress(output, &mut dest_len, input, input_len as _);
+
+    match ret {
+        0 => dest_len as i32,
+        _ => ret,
+    }
+}
+
+/// # Safety
+///
+/// The buffers must be allocations of at least the given length
+#[no_mangle]
+pub unsafe extern "C" fn miniz_compress(
+    input: *const u8,
+    input_len: usize,
+    output: *mut u8,
+    output_len: usize,
+    level: i32,
+) -> i32 {
+    let (input, output) = buffers(input, input_len, output, output_len);
+
+    let flags = create_comp_flags_from_zip_params(level, 15, 0);
+    let mut compressor = Box::new(CompressorOxide::new(flags));
+
+    match compress(&mut compressor, input, output, TDEFLFlush::Finish) {
+        (TDEFLStatus::Done, _, bytes_out) => bytes_out as i32,
+        _ => Z_BUF_ERROR,
+    }
+}
+
+/// # Safety
+///
+/// The buffers must be allocations of at least the given length
+#[no_mangle]
+pub unsafe extern "C" fn miniz_uncompress(
+    input: *const u8,
+    input_len: usize,
+    output: *mut u8,
+    output_len: usize,
+) -> i32 {
+    let (input, output) = buffers(input, input_len, output, output_len);
+
+    let flags = inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF
+        | inflate_flags::TINFL_FLAG_PARSE_ZLIB_HEADER;
+    let mut decompressor = Box::<DecompressorOxide>::default();
+
+    match decompress(&mut decompressor, input, output, 0, flags) {
+        (TINFLStatus::Done, _, bytes_out) => bytes_out as i32,
+        (TINFLStatus::HasMoreOutput, _, _) => Z_BUF_ERROR,
+        _ => Z_DATA_ERROR,
+    }
+}